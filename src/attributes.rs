@@ -0,0 +1,190 @@
+use std::{fs, path::Path};
+
+use crate::{common_dir, glob_match, GitError};
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum AttributeValue {
+    // `attr`
+    Set,
+    // `-attr`
+    Unset,
+    // `attr=value`
+    Value(String),
+    // `!attr`, as if no earlier pattern had set it
+    Unspecified,
+}
+
+// One line of a `.gitattributes`: a pattern followed by the attributes of the paths it matches
+#[derive(Clone, Debug)]
+struct AttributePattern {
+    pattern: String,
+    // Same rule as ignore files, see `IgnorePattern`
+    anchored: bool,
+    // Directory of the `.gitattributes` the pattern comes from, with a trailing `/`, empty for the
+    // root
+    base: String,
+    attributes: Vec<(String, AttributeValue)>,
+}
+
+impl AttributePattern {
+    fn parse(line: &str, base: &str) -> Option<AttributePattern> {
+        let mut fields = line.split_whitespace();
+        let pattern = fields.next()?;
+
+        // Negative patterns are forbidden, and attributes only apply to files, not directories
+        if pattern.starts_with('#') || pattern.starts_with('!') || pattern.ends_with('/') {
+            return None;
+        }
+
+        let mut attributes = Vec::new();
+
+        for field in fields {
+            let (name, value) = if let Some(name) = field.strip_prefix('-') {
+                (name, AttributeValue::Unset)
+            } else if let Some(name) = field.strip_prefix('!') {
+                (name, AttributeValue::Unspecified)
+            } else if let Some((name, value)) = field.split_once('=') {
+                (name, AttributeValue::Value(value.to_string()))
+            } else {
+                (field, AttributeValue::Set)
+            };
+
+            // The only built-in macro
+            if name == "binary" && value == AttributeValue::Set {
+                for name in ["diff", "merge", "text"] {
+                    attributes.push((name.to_string(), AttributeValue::Unset));
+                }
+            }
+
+            attributes.push((name.to_string(), value));
+        }
+
+        let anchored = pattern.contains('/');
+        let pattern = pattern.strip_prefix('/').unwrap_or(pattern);
+
+        return Some(AttributePattern {
+            pattern: pattern.to_string(),
+            anchored,
+            base: base.to_string(),
+            attributes,
+        });
+    }
+
+    fn matches(self: &AttributePattern, path: &str) -> bool {
+        let relative_path = match path.strip_prefix(self.base.as_str()) {
+            Some(relative_path) => relative_path,
+            None => return false,
+        };
+
+        if self.anchored {
+            return glob_match(self.pattern.as_bytes(), relative_path.as_bytes());
+        }
+
+        let name = relative_path.rsplit('/').next().unwrap_or(relative_path);
+
+        return glob_match(self.pattern.as_bytes(), name.as_bytes());
+    }
+}
+
+// Patterns from every attributes file that applies to a directory, outermost first
+#[derive(Clone, Debug, Default)]
+pub struct Attributes {
+    patterns: Vec<AttributePattern>,
+    // `.git/info/attributes`, which takes precedence over every `.gitattributes`
+    overrides: Vec<AttributePattern>,
+}
+
+impl Attributes {
+    pub fn for_repository(git_dir: &Path) -> Result<Attributes, GitError> {
+        let path = common_dir(git_dir)?.join("info").join("attributes");
+
+        return Ok(Attributes {
+            patterns: Vec::new(),
+            overrides: read_attributes_file(&path, "")?,
+        });
+    }
+
+    // These attributes, stacked with the `.gitattributes` of `directory` if it has one
+    // `relative_path` is the path of `directory` from the root, empty for the root
+    pub fn for_directory(
+        self: &Attributes,
+        directory: &Path,
+        relative_path: &str,
+    ) -> Result<Attributes, GitError> {
+        let base = if relative_path.is_empty() {
+            String::new()
+        } else {
+            format!("{relative_path}/")
+        };
+
+        let mut attributes = self.clone();
+        attributes.patterns.extend(read_attributes_file(
+            &directory.join(".gitattributes"),
+            &base,
+        )?);
+
+        return Ok(attributes);
+    }
+
+    // Everything that applies to `path`, relative to `root`: the attributes of the repository and
+    // the `.gitattributes` of each of its parent directories
+    pub fn for_path(git_dir: &Path, root: &Path, path: &str) -> Result<Attributes, GitError> {
+        let mut attributes = Attributes::for_repository(git_dir)?.for_directory(root, "")?;
+        let mut directory = root.to_path_buf();
+        let mut relative_path = String::new();
+
+        if let Some((parents, _)) = path.rsplit_once('/') {
+            for name in parents.split('/').filter(|name| !name.is_empty()) {
+                directory = directory.join(name);
+                relative_path = if relative_path.is_empty() {
+                    name.to_string()
+                } else {
+                    format!("{relative_path}/{name}")
+                };
+                attributes = attributes.for_directory(&directory, &relative_path)?;
+            }
+        }
+
+        return Ok(attributes);
+    }
+
+    // `path` is relative to the root of the repository, the last matching pattern that mentions
+    // `name` wins, `None` when none does or it is unspecified
+    pub fn get(self: &Attributes, path: &str, name: &str) -> Option<&AttributeValue> {
+        let patterns = self
+            .overrides
+            .iter()
+            .rev()
+            .chain(self.patterns.iter().rev());
+
+        for pattern in patterns.filter(|pattern| pattern.matches(path)) {
+            let value = pattern
+                .attributes
+                .iter()
+                .rev()
+                .find(|(attribute, _)| attribute == name)
+                .map(|(_, value)| value);
+
+            match value {
+                Some(AttributeValue::Unspecified) => return None,
+                Some(value) => return Some(value),
+                None => {}
+            }
+        }
+
+        return None;
+    }
+}
+
+fn read_attributes_file(path: &Path, base: &str) -> Result<Vec<AttributePattern>, GitError> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(error) => return Err(error.into()),
+    };
+
+    return Ok(content
+        .lines()
+        .filter_map(|line| AttributePattern::parse(line, base))
+        .collect());
+}
@@ -0,0 +1,192 @@
+use std::{fs, io, path::Path};
+
+use crate::{common_dir, GitError};
+
+// One `key = value` line, names of sections and keys are stored lowercased
+#[derive(Clone, Debug)]
+struct ConfigEntry {
+    section: String,
+    // Case sensitive, unless it comes from the legacy `[section.subsection]` syntax
+    subsection: Option<String>,
+    key: String,
+    value: String,
+}
+
+// A parsed `.git/config`, in file order
+#[derive(Clone, Debug, Default)]
+pub struct Config {
+    entries: Vec<ConfigEntry>,
+}
+
+impl Config {
+    // The config of `git_dir`, shared with every worktree, empty if it has none
+    pub fn read(git_dir: &Path) -> Result<Config, GitError> {
+        match fs::read_to_string(common_dir(git_dir)?.join("config")) {
+            Ok(content) => return Config::parse(&content),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Config::default()),
+            Err(error) => return Err(error.into()),
+        }
+    }
+
+    pub fn parse(content: &str) -> Result<Config, GitError> {
+        let mut entries = Vec::new();
+        let mut section: Option<(String, Option<String>)> = None;
+
+        let mut lines = content.lines().enumerate();
+
+        while let Some((number, line)) = lines.next() {
+            let invalid_line = || GitError::InvalidConfig(number + 1);
+            let mut line = line.trim_start();
+
+            if line.starts_with('[') {
+                let (header, rest) = parse_section_header(line).ok_or_else(invalid_line)?;
+                section = Some(header);
+                // A key may follow the header on the same line
+                line = rest.trim_start();
+            }
+
+            if line.is_empty() || line.starts_with(['#', ';']) {
+                continue;
+            }
+
+            let (section, subsection) = section.clone().ok_or_else(invalid_line)?;
+
+            let key_len = line
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '-')
+                .unwrap_or(line.len());
+            let key = &line[..key_len];
+
+            if !key.starts_with(|c: char| c.is_ascii_alphabetic()) {
+                return Err(invalid_line());
+            }
+
+            let rest = line[key_len..].trim_start();
+            let value = match rest.strip_prefix('=') {
+                Some(rest) => parse_value(rest, &mut lines).ok_or_else(invalid_line)?,
+                // A key on its own is a boolean set to true
+                None if rest.is_empty() || rest.starts_with(['#', ';']) => "true".to_string(),
+                None => return Err(invalid_line()),
+            };
+
+            entries.push(ConfigEntry {
+                section,
+                subsection,
+                key: key.to_lowercase(),
+                value,
+            });
+        }
+
+        return Ok(Config { entries });
+    }
+
+    // Last value of `section.subsection.key`, like git when a key is set more than once
+    pub fn get(self: &Config, section: &str, subsection: Option<&str>, key: &str) -> Option<&str> {
+        return self
+            .entries
+            .iter()
+            .rev()
+            .find(|entry| {
+                entry.section.eq_ignore_ascii_case(section)
+                    && entry.subsection.as_deref() == subsection
+                    && entry.key.eq_ignore_ascii_case(key)
+            })
+            .map(|entry| entry.value.as_str());
+    }
+}
+
+// `[section]`, `[section "subsection"]` or `[section.subsection]`, along with what follows it
+fn parse_section_header(line: &str) -> Option<((String, Option<String>), &str)> {
+    let line = line.strip_prefix('[')?;
+
+    let name_len = line.find(|c: char| !c.is_ascii_alphanumeric() && c != '-' && c != '.')?;
+    let name = line[..name_len].to_lowercase();
+    let rest = &line[name_len..];
+
+    if name.is_empty() {
+        return None;
+    }
+
+    if let Some(rest) = rest.strip_prefix(']') {
+        match name.split_once('.') {
+            Some((section, subsection)) => {
+                return Some(((section.to_string(), Some(subsection.to_string())), rest))
+            }
+            None => return Some(((name, None), rest)),
+        }
+    }
+
+    let mut chars = rest
+        .trim_start_matches([' ', '\t'])
+        .strip_prefix('"')?
+        .chars();
+    let mut subsection = String::new();
+
+    loop {
+        match chars.next()? {
+            '"' => break,
+            '\\' => subsection.push(chars.next()?),
+            c => subsection.push(c),
+        }
+    }
+
+    let rest = chars.as_str().strip_prefix(']')?;
+
+    return Some(((name, Some(subsection)), rest));
+}
+
+// What follows the `=`, a trailing `\` continues the value on the next line
+// Outside of double quotes a comment ends the value and surrounding whitespace is dropped
+fn parse_value<'a>(
+    first_line: &str,
+    lines: &mut impl Iterator<Item = (usize, &'a str)>,
+) -> Option<String> {
+    let mut value = String::new();
+    // Length of the value without its trailing unquoted whitespace
+    let mut kept_len = 0;
+    let mut quoted = false;
+
+    let mut chars = first_line.chars();
+
+    loop {
+        let c = match chars.next() {
+            Some(c) => c,
+            // Quotes can't span lines
+            None if quoted => return None,
+            None => break,
+        };
+
+        match c {
+            '"' => {
+                quoted = !quoted;
+                kept_len = value.len();
+                continue;
+            }
+            '#' | ';' if !quoted => break,
+            c if c.is_whitespace() && !quoted => {
+                if !value.is_empty() {
+                    value.push(c);
+                }
+                continue;
+            }
+            '\\' => match chars.next() {
+                Some('n') => value.push('\n'),
+                Some('t') => value.push('\t'),
+                Some('b') => value.push('\u{8}'),
+                Some(c @ ('\\' | '"')) => value.push(c),
+                Some(_) => return None,
+                None => {
+                    let (_, next_line) = lines.next()?;
+                    chars = next_line.chars();
+                    continue;
+                }
+            },
+            c => value.push(c),
+        }
+
+        kept_len = value.len();
+    }
+
+    value.truncate(kept_len);
+
+    return Some(value);
+}
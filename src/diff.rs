@@ -0,0 +1,157 @@
+use std::cmp::Ordering;
+
+use crate::{read_tree, GitError, Repository, TreeEntry};
+
+// One line of `git diff-tree --raw`, a missing side has a zero mode and sha
+#[derive(Clone, Debug)]
+pub struct TreeChange {
+    pub old_mode: u32,
+    pub new_mode: u32,
+    pub old_sha: String,
+    pub new_sha: String,
+    // `A`dded, `D`eleted, `M`odified, or `T` when the type changed (file to symlink)
+    pub status: char,
+    pub path: String,
+}
+
+impl TreeChange {
+    // `:<old mode> <new mode> <old sha> <new sha> <status>\t<path>`
+    pub fn format_raw(self: &TreeChange) -> String {
+        return format!(
+            ":{:06o} {:06o} {} {} {}\t{}",
+            self.old_mode, self.new_mode, self.old_sha, self.new_sha, self.status, self.path
+        );
+    }
+}
+
+// Paths that differ between two trees, in tree order
+// Without `recursive` a differing subtree is reported as a whole, with it the files inside it are
+pub fn diff_trees(
+    repository: &Repository,
+    old_tree: &str,
+    new_tree: &str,
+    recursive: bool,
+) -> Result<Vec<TreeChange>, GitError> {
+    let old_entries = read_tree(repository, old_tree)?.entries;
+    let new_entries = read_tree(repository, new_tree)?.entries;
+
+    let mut changes = Vec::new();
+    diff_tree_entries(
+        repository,
+        old_entries,
+        new_entries,
+        "",
+        recursive,
+        &mut changes,
+    )?;
+
+    return Ok(changes);
+}
+
+// Both sides are sorted the way trees are stored, then walked in lockstep
+fn diff_tree_entries(
+    repository: &Repository,
+    mut old_entries: Vec<TreeEntry>,
+    mut new_entries: Vec<TreeEntry>,
+    prefix: &str,
+    recursive: bool,
+    changes: &mut Vec<TreeChange>,
+) -> Result<(), GitError> {
+    old_entries.sort();
+    new_entries.sort();
+
+    let mut old_entries = old_entries.into_iter().peekable();
+    let mut new_entries = new_entries.into_iter().peekable();
+
+    loop {
+        let ordering = match (old_entries.peek(), new_entries.peek()) {
+            (None, None) => break,
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (Some(old_entry), Some(new_entry)) => old_entry.cmp(new_entry),
+        };
+
+        let (old_entry, new_entry) = match ordering {
+            Ordering::Less => (old_entries.next(), None),
+            Ordering::Greater => (None, new_entries.next()),
+            Ordering::Equal => (old_entries.next(), new_entries.next()),
+        };
+
+        diff_entry(repository, old_entry, new_entry, prefix, recursive, changes)?;
+    }
+
+    return Ok(());
+}
+
+// `old_entry` and `new_entry` share the same name, either may be missing
+fn diff_entry(
+    repository: &Repository,
+    old_entry: Option<TreeEntry>,
+    new_entry: Option<TreeEntry>,
+    prefix: &str,
+    recursive: bool,
+    changes: &mut Vec<TreeChange>,
+) -> Result<(), GitError> {
+    if let (Some(old_entry), Some(new_entry)) = (&old_entry, &new_entry) {
+        if old_entry.mode == new_entry.mode && old_entry.sha == new_entry.sha {
+            return Ok(());
+        }
+    }
+
+    let entry = match old_entry.as_ref().or(new_entry.as_ref()) {
+        Some(entry) => entry,
+        None => return Ok(()),
+    };
+    // Names that aren't UTF-8 are only displayed approximately
+    let path = format!("{prefix}{}", String::from_utf8_lossy(&entry.name));
+
+    if recursive && entry.mode == 0o40000 {
+        let old_entries = match &old_entry {
+            Some(old_entry) => read_tree(repository, &old_entry.sha)?.entries,
+            None => Vec::new(),
+        };
+        let new_entries = match &new_entry {
+            Some(new_entry) => read_tree(repository, &new_entry.sha)?.entries,
+            None => Vec::new(),
+        };
+
+        return diff_tree_entries(
+            repository,
+            old_entries,
+            new_entries,
+            &format!("{path}/"),
+            recursive,
+            changes,
+        );
+    }
+
+    let status = match (&old_entry, &new_entry) {
+        (None, _) => 'A',
+        (_, None) => 'D',
+        // The file type lives in the upper bits of the mode
+        (Some(old_entry), Some(new_entry)) if old_entry.mode >> 12 != new_entry.mode >> 12 => 'T',
+        _ => 'M',
+    };
+
+    let missing_sha = "0".repeat(repository.object_format().hex_len());
+
+    let (old_mode, old_sha) = match old_entry {
+        Some(old_entry) => (old_entry.mode, old_entry.sha),
+        None => (0, missing_sha.clone()),
+    };
+    let (new_mode, new_sha) = match new_entry {
+        Some(new_entry) => (new_entry.mode, new_entry.sha),
+        None => (0, missing_sha),
+    };
+
+    changes.push(TreeChange {
+        old_mode,
+        new_mode,
+        old_sha,
+        new_sha,
+        status,
+        path,
+    });
+
+    return Ok(());
+}
@@ -0,0 +1,118 @@
+use std::{io, path::PathBuf, time::SystemTimeError};
+
+// Everything that can go wrong in the library, so callers can match on the failure mode
+#[derive(Debug, thiserror::Error)]
+pub enum GitError {
+    #[error("Not a valid object name {0}")]
+    ObjectNotFound(String),
+
+    #[error("Object name `{0}` is not a hexadecimal hash or hash prefix")]
+    InvalidObjectName(String),
+
+    #[error("Object name `{0}` is too short, expected at least 4 characters")]
+    ObjectNameTooShort(String),
+
+    #[error("Object name `{0}` is ambiguous")]
+    AmbiguousObjectName(String),
+
+    #[error("Invalid header for object {object_hash}: {reason}")]
+    InvalidObjectHeader {
+        object_hash: String,
+        reason: &'static str,
+    },
+
+    #[error("Object type `{0}` is not supported")]
+    UnsupportedType(String),
+
+    #[error("{object_hash} is a {actual} object, expected a {expected}")]
+    TypeMismatch {
+        object_hash: String,
+        actual: &'static str,
+        expected: &'static str,
+    },
+
+    #[error("Invalid tree entry `{0}`, expected `<mode> <type> <sha>\t<name>`")]
+    InvalidTreeEntry(String),
+
+    #[error("Object format `{0}` is not supported, expected `sha1` or `sha256`")]
+    UnsupportedObjectFormat(String),
+
+    #[error("Prefix `{0}` is not a directory of the tree")]
+    PrefixNotFound(String),
+
+    #[error("Invalid path `{0}`")]
+    InvalidPath(String),
+
+    #[error("Invalid config line {0}")]
+    InvalidConfig(usize),
+
+    #[error("Corrupt object: {0}")]
+    Corrupt(String),
+
+    #[error("Not a git repository (or any of the parent directories): .git")]
+    NotARepository,
+
+    #[error("Invalid gitfile format: {}", .0.display())]
+    InvalidGitFile(PathBuf),
+
+    #[error("No such file or directory: {}", .0.display())]
+    FileNotFound(PathBuf),
+
+    #[error("Neither file nor dir: {}", .0.display())]
+    UnsupportedFileType(PathBuf),
+
+    #[error("Invalid pack: {0}")]
+    InvalidPack(String),
+
+    #[error("Invalid index: {0}")]
+    InvalidIndex(String),
+
+    #[error("`{0}` is not a valid ref name")]
+    InvalidRefName(String),
+
+    #[error("Invalid packed-refs line `{0}`, expected `<sha> <refname>` or `^<sha>`")]
+    InvalidPackedRef(String),
+
+    #[error("Ref `{0}` is broken, it holds neither a hash nor a symbolic ref")]
+    BrokenRef(String),
+
+    #[error("No such ref: {0}")]
+    RefNotFound(String),
+
+    #[error("Ref `{0}` is not a symbolic ref")]
+    NotASymbolicRef(String),
+
+    #[error("Symbolic ref `{0}` is nested too deeply")]
+    SymbolicRefTooDeep(String),
+
+    #[error("Refusing to point `{name}` outside of refs/: {target}")]
+    SymbolicRefOutsideRefs { name: String, target: String },
+
+    #[error("Cannot update ref `{refname}`: it is at {current} but expected {expected}")]
+    RefConflict {
+        refname: String,
+        current: String,
+        expected: String,
+    },
+
+    #[error(
+        "{0} identity unknown: set GIT_{0}_NAME and GIT_{0}_EMAIL, \
+         or user.name and user.email in the repository config"
+    )]
+    UnknownIdentity(String),
+
+    #[error("Invalid {variable} `{value}`, expected `@<seconds> <tz>`")]
+    InvalidDate { variable: String, value: String },
+
+    #[error("Invalid timezone `{0}`, expected `+HHMM` or `-HHMM`")]
+    InvalidTimezone(String),
+
+    #[error("Could not determine the local timezone")]
+    UnknownTimezone,
+
+    #[error(transparent)]
+    Time(#[from] SystemTimeError),
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
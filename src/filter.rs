@@ -0,0 +1,156 @@
+use std::path::Path;
+
+use crate::{AttributeValue, Attributes, Config, GitError};
+
+// The `text` attribute of a file
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum TextAttribute {
+    // Left to `core.autocrlf`
+    #[default]
+    Unspecified,
+    // `text=auto`: line endings are normalized unless the content looks binary
+    Auto,
+    // `text`: line endings are always normalized
+    Set,
+    // `-text` or `binary`: the content is never converted
+    Unset,
+}
+
+// Conversions applied to the content of a file in the working tree before storing it as a blob
+// The default is no conversion at all, files are stored byte for byte
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Filters {
+    // `core.autocrlf`: text files are stored with LF line endings and checked out with CRLF
+    pub autocrlf: bool,
+    pub text: TextAttribute,
+}
+
+impl Filters {
+    // `core.autocrlf` from the config of `git_dir`, `input` isn't supported and is ignored
+    pub fn for_repository(git_dir: &Path) -> Result<Filters, GitError> {
+        let autocrlf = match Config::read(git_dir)?.get("core", None, "autocrlf") {
+            Some(value) => ["true", "yes", "on", "1"]
+                .iter()
+                .any(|enabled| value.eq_ignore_ascii_case(enabled)),
+            None => false,
+        };
+
+        return Ok(Filters {
+            autocrlf,
+            text: TextAttribute::Unspecified,
+        });
+    }
+
+    // The filters of the file at `path`, relative to the root of the working tree `work_tree`
+    pub fn for_file(git_dir: &Path, work_tree: &Path, path: &str) -> Result<Filters, GitError> {
+        let attributes = Attributes::for_path(git_dir, work_tree, path)?;
+
+        return Ok(Filters::for_repository(git_dir)?.for_path(&attributes, path));
+    }
+
+    // These filters, for the file at `path` given the attributes that apply to it
+    pub fn for_path(self: &Filters, attributes: &Attributes, path: &str) -> Filters {
+        let text = match attributes.get(path, "text") {
+            Some(AttributeValue::Set) => TextAttribute::Set,
+            Some(AttributeValue::Unset) => TextAttribute::Unset,
+            Some(AttributeValue::Value(value)) if value == "auto" => TextAttribute::Auto,
+            _ => TextAttribute::Unspecified,
+        };
+
+        return Filters { text, ..*self };
+    }
+
+    pub fn is_noop(self: &Filters) -> bool {
+        match self.text {
+            TextAttribute::Unspecified => return !self.autocrlf,
+            TextAttribute::Auto | TextAttribute::Set => return false,
+            TextAttribute::Unset => return true,
+        }
+    }
+
+    // Working tree content to blob content
+    pub fn clean(self: &Filters, content: Vec<u8>) -> Vec<u8> {
+        match self.text {
+            TextAttribute::Unspecified if self.autocrlf => return crlf_to_lf(content, true),
+            TextAttribute::Auto => return crlf_to_lf(content, true),
+            TextAttribute::Set => return crlf_to_lf(content, false),
+            TextAttribute::Unspecified | TextAttribute::Unset => return content,
+        }
+    }
+
+    // Blob content to working tree content, line endings are only converted with `core.autocrlf`
+    pub fn smudge(self: &Filters, content: Vec<u8>) -> Vec<u8> {
+        if !self.autocrlf {
+            return content;
+        }
+
+        match self.text {
+            TextAttribute::Unspecified | TextAttribute::Auto => return lf_to_crlf(content, true),
+            TextAttribute::Set => return lf_to_crlf(content, false),
+            TextAttribute::Unset => return content,
+        }
+    }
+}
+
+// git's heuristic: a NUL, a CR that doesn't end a line, or too many control characters
+fn is_binary(content: &[u8]) -> bool {
+    let mut printable = 0;
+    let mut non_printable = 0;
+
+    for (i, &byte) in content.iter().enumerate() {
+        match byte {
+            0 => return true,
+            b'\r' if content.get(i + 1) != Some(&b'\n') => return true,
+            b'\r' | b'\n' => {}
+            b'\t' | 0x08 | 0x0c | 0x1b => printable += 1,
+            0x7f => non_printable += 1,
+            byte if byte < 0x20 => non_printable += 1,
+            _ => printable += 1,
+        }
+    }
+
+    return printable / 128 < non_printable;
+}
+
+// Only content with at least one CRLF is converted, with `detect` it must also look like text
+fn crlf_to_lf(content: Vec<u8>, detect: bool) -> Vec<u8> {
+    if !content.windows(2).any(|pair| pair == b"\r\n") || (detect && is_binary(&content)) {
+        return content;
+    }
+
+    let mut converted = Vec::with_capacity(content.len());
+
+    for (i, &byte) in content.iter().enumerate() {
+        if byte == b'\r' && content.get(i + 1) == Some(&b'\n') {
+            continue;
+        }
+
+        converted.push(byte);
+    }
+
+    return converted;
+}
+
+// With `detect`, content with any CR already is left as it was committed, so is binary content
+fn lf_to_crlf(content: Vec<u8>, detect: bool) -> Vec<u8> {
+    if !content.contains(&b'\n') {
+        return content;
+    }
+
+    if detect && (content.contains(&b'\r') || is_binary(&content)) {
+        return content;
+    }
+
+    let mut converted = Vec::with_capacity(content.len());
+
+    for (i, &byte) in content.iter().enumerate() {
+        // Lines already ending with CRLF are kept as they are
+        if byte == b'\n' && (i == 0 || content[i - 1] != b'\r') {
+            converted.push(b'\r');
+        }
+
+        converted.push(byte);
+    }
+
+    return converted;
+}
@@ -0,0 +1,102 @@
+use std::{io::Write, str::FromStr};
+
+use sha1::{Digest, Sha1};
+use sha2::Sha256;
+
+use crate::GitError;
+
+// Hash function naming the objects of a repository, chosen once when it is created
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ObjectFormat {
+    #[default]
+    Sha1,
+    Sha256,
+}
+
+impl ObjectFormat {
+    pub fn as_str(self: &ObjectFormat) -> &'static str {
+        match self {
+            ObjectFormat::Sha1 => "sha1",
+            ObjectFormat::Sha256 => "sha256",
+        }
+    }
+
+    // Size of a hash in bytes, as stored in trees, the index and packs
+    pub fn raw_len(self: &ObjectFormat) -> usize {
+        match self {
+            ObjectFormat::Sha1 => 20,
+            ObjectFormat::Sha256 => 32,
+        }
+    }
+
+    // Size of a hash in hex, as printed and used to name loose objects
+    pub fn hex_len(self: &ObjectFormat) -> usize {
+        return self.raw_len() * 2;
+    }
+
+    pub fn hasher(self: &ObjectFormat) -> ObjectHasher {
+        match self {
+            ObjectFormat::Sha1 => ObjectHasher::Sha1(Sha1::new()),
+            ObjectFormat::Sha256 => ObjectHasher::Sha256(Sha256::new()),
+        }
+    }
+
+    // Hex hash of `content` as a whole
+    pub fn digest(self: &ObjectFormat, content: &[u8]) -> String {
+        let mut hasher = self.hasher();
+        hasher.update(content);
+
+        return hex::encode(hasher.finalize());
+    }
+
+    pub fn is_valid_hash(self: &ObjectFormat, hash: &str) -> bool {
+        return hash.len() == self.hex_len() && hash.chars().all(|c| c.is_ascii_hexdigit());
+    }
+}
+
+impl FromStr for ObjectFormat {
+    type Err = GitError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sha1" => Ok(ObjectFormat::Sha1),
+            "sha256" => Ok(ObjectFormat::Sha256),
+            _ => Err(GitError::UnsupportedObjectFormat(s.to_string())),
+        }
+    }
+}
+
+// Hashes content fed to it in chunks, with the function of an `ObjectFormat`
+pub enum ObjectHasher {
+    Sha1(Sha1),
+    Sha256(Sha256),
+}
+
+impl ObjectHasher {
+    pub fn update(self: &mut ObjectHasher, content: &[u8]) {
+        match self {
+            ObjectHasher::Sha1(hasher) => hasher.update(content),
+            ObjectHasher::Sha256(hasher) => hasher.update(content),
+        }
+    }
+
+    // Raw hash, see `ObjectFormat::raw_len`
+    pub fn finalize(self: ObjectHasher) -> Vec<u8> {
+        match self {
+            ObjectHasher::Sha1(hasher) => return hasher.finalize().to_vec(),
+            ObjectHasher::Sha256(hasher) => return hasher.finalize().to_vec(),
+        }
+    }
+}
+
+impl Write for ObjectHasher {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.update(buf);
+
+        return Ok(buf.len());
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        return Ok(());
+    }
+}
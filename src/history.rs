@@ -0,0 +1,116 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, HashSet},
+};
+
+use crate::{
+    open_object, read_git_object, CommitObject, GitError, GitObject, ObjectType, Repository,
+};
+
+// Reads `object_hash`, which must be a commit
+pub fn read_commit_object(
+    repository: &Repository,
+    object_hash: &str,
+) -> Result<CommitObject, GitError> {
+    let mut reader = open_object(repository, object_hash)?;
+
+    let object_type = match read_git_object(&mut reader, object_hash, repository.object_format())? {
+        GitObject::Commit(commit) => return Ok(commit),
+        GitObject::Blob(_) => ObjectType::Blob,
+        GitObject::Tree(_) => ObjectType::Tree,
+        GitObject::Tag(_) => ObjectType::Tag,
+    };
+
+    return Err(GitError::TypeMismatch {
+        object_hash: object_hash.to_string(),
+        actual: object_type.as_str(),
+        expected: ObjectType::Commit.as_str(),
+    });
+}
+
+// `starts` and all their ancestors, each listed once, newest first like `git rev-list`
+// Commits are ordered by committer date, so children come before their parents unless clocks
+// were skewed, commits with the same date are listed in the order they were reached
+pub fn rev_list(
+    repository: &Repository,
+    starts: &[String],
+    max_count: Option<usize>,
+) -> Result<Vec<(String, CommitObject)>, GitError> {
+    let mut seen = HashSet::new();
+    let mut queue = BinaryHeap::new();
+    let mut commits = HashMap::new();
+    let mut reached = 0;
+
+    let mut pending = starts.to_vec();
+    let mut ancestors = Vec::new();
+
+    loop {
+        for object_hash in pending.drain(..) {
+            if !seen.insert(object_hash.clone()) {
+                continue;
+            }
+
+            let commit = read_commit_object(repository, &object_hash)?;
+
+            queue.push((
+                commit.committer_date_seconds,
+                Reverse(reached),
+                object_hash.clone(),
+            ));
+            commits.insert(object_hash, commit);
+            reached += 1;
+        }
+
+        if max_count.is_some_and(|max_count| ancestors.len() >= max_count) {
+            break;
+        }
+
+        let object_hash = match queue.pop() {
+            Some((_, _, object_hash)) => object_hash,
+            None => break,
+        };
+
+        let commit = match commits.remove(&object_hash) {
+            Some(commit) => commit,
+            None => continue,
+        };
+
+        pending.extend(commit.parents.iter().cloned());
+        ancestors.push((object_hash, commit));
+    }
+
+    return Ok(ancestors);
+}
+
+// Best common ancestors of `first` and `second`, newest first: those reachable from both that
+// aren't an ancestor of another common ancestor
+// Ancestors of a common ancestor are common too, so a common ancestor that isn't best is
+// necessarily the parent of another one
+pub fn merge_bases(
+    repository: &Repository,
+    first: &str,
+    second: &str,
+) -> Result<Vec<String>, GitError> {
+    let first_ancestors: HashSet<String> = rev_list(repository, &[first.to_string()], None)?
+        .into_iter()
+        .map(|(object_hash, _)| object_hash)
+        .collect();
+
+    let common: Vec<(String, CommitObject)> = rev_list(repository, &[second.to_string()], None)?
+        .into_iter()
+        .filter(|(object_hash, _)| first_ancestors.contains(object_hash))
+        .collect();
+
+    let not_best: HashSet<&String> = common
+        .iter()
+        .flat_map(|(_, commit)| commit.parents.iter())
+        .collect();
+
+    let bases = common
+        .iter()
+        .filter(|(object_hash, _)| !not_best.contains(object_hash))
+        .map(|(object_hash, _)| object_hash.clone())
+        .collect();
+
+    return Ok(bases);
+}
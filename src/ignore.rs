@@ -0,0 +1,174 @@
+use std::{fs, path::Path};
+
+use crate::{common_dir, GitError};
+
+// One line of a `.gitignore`
+#[derive(Clone, Debug)]
+struct IgnorePattern {
+    pattern: String,
+    // `!pattern` re-includes what a previous pattern excluded
+    negated: bool,
+    // `pattern/` only matches directories
+    directory_only: bool,
+    // A `/` at the start or in the middle ties the pattern to the directory of its `.gitignore`,
+    // otherwise it matches a name at any depth
+    anchored: bool,
+    // Directory of the `.gitignore` the pattern comes from, with a trailing `/`, empty for the root
+    base: String,
+}
+
+impl IgnorePattern {
+    fn parse(line: &str, base: &str) -> Option<IgnorePattern> {
+        let line = line.trim_end_matches([' ', '\r']);
+
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+
+        let (line, negated) = match line.strip_prefix('!') {
+            Some(line) => (line, true),
+            None => (line, false),
+        };
+        // `\#` and `\!` stand for a literal first character
+        let line = line.strip_prefix('\\').unwrap_or(line);
+
+        let (line, directory_only) = match line.strip_suffix('/') {
+            Some(line) => (line, true),
+            None => (line, false),
+        };
+
+        let anchored = line.contains('/');
+        let pattern = line.strip_prefix('/').unwrap_or(line);
+
+        if pattern.is_empty() {
+            return None;
+        }
+
+        return Some(IgnorePattern {
+            pattern: pattern.to_string(),
+            negated,
+            directory_only,
+            anchored,
+            base: base.to_string(),
+        });
+    }
+
+    fn matches(self: &IgnorePattern, path: &str, is_dir: bool) -> bool {
+        if self.directory_only && !is_dir {
+            return false;
+        }
+
+        let relative_path = match path.strip_prefix(self.base.as_str()) {
+            Some(relative_path) => relative_path,
+            None => return false,
+        };
+
+        if self.anchored {
+            return glob_match(self.pattern.as_bytes(), relative_path.as_bytes());
+        }
+
+        let name = relative_path.rsplit('/').next().unwrap_or(relative_path);
+
+        return glob_match(self.pattern.as_bytes(), name.as_bytes());
+    }
+}
+
+// `*` and `?` never match a `/`, `**` does
+pub fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern.first() {
+        None => return text.is_empty(),
+        Some(b'*') if pattern.get(1) == Some(&b'*') => {
+            // `**/` matches zero or more leading directories
+            let rest = &pattern[2..];
+            let rest = rest.strip_prefix(b"/").unwrap_or(rest);
+
+            // A trailing `/**` matches everything inside
+            if rest.is_empty() {
+                return true;
+            }
+
+            return (0..=text.len())
+                .filter(|&i| i == 0 || text[i - 1] == b'/')
+                .any(|i| glob_match(rest, &text[i..]));
+        }
+        Some(b'*') => {
+            let end = text
+                .iter()
+                .position(|&byte| byte == b'/')
+                .unwrap_or(text.len());
+
+            return (0..=end).any(|i| glob_match(&pattern[1..], &text[i..]));
+        }
+        Some(b'?') => {
+            return !text.is_empty() && text[0] != b'/' && glob_match(&pattern[1..], &text[1..]);
+        }
+        Some(b'\\') if pattern.len() > 1 => {
+            return text.first() == Some(&pattern[1]) && glob_match(&pattern[2..], &text[1..]);
+        }
+        Some(&byte) => {
+            return text.first() == Some(&byte) && glob_match(&pattern[1..], &text[1..]);
+        }
+    }
+}
+
+// Patterns from every ignore file that applies to a directory, outermost first
+#[derive(Clone, Debug, Default)]
+pub struct IgnoreRules {
+    patterns: Vec<IgnorePattern>,
+}
+
+impl IgnoreRules {
+    // `.git/info/exclude`, which applies to the whole repository
+    pub fn for_repository(git_dir: &Path) -> Result<IgnoreRules, GitError> {
+        let mut rules = IgnoreRules::default();
+        rules.add_file(&common_dir(git_dir)?.join("info").join("exclude"), "")?;
+
+        return Ok(rules);
+    }
+
+    // These rules, stacked with the `.gitignore` of `directory` if it has one
+    // `relative_path` is the path of `directory` from the root, empty for the root
+    pub fn for_directory(
+        self: &IgnoreRules,
+        directory: &Path,
+        relative_path: &str,
+    ) -> Result<IgnoreRules, GitError> {
+        let base = if relative_path.is_empty() {
+            String::new()
+        } else {
+            format!("{relative_path}/")
+        };
+
+        let mut rules = self.clone();
+        rules.add_file(&directory.join(".gitignore"), &base)?;
+
+        return Ok(rules);
+    }
+
+    fn add_file(self: &mut IgnoreRules, path: &Path, base: &str) -> Result<(), GitError> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(error) => return Err(error.into()),
+        };
+
+        self.patterns.extend(
+            content
+                .lines()
+                .filter_map(|line| IgnorePattern::parse(line, base)),
+        );
+
+        return Ok(());
+    }
+
+    // `path` is relative to the root of the repository, the last matching pattern wins
+    pub fn is_ignored(self: &IgnoreRules, path: &str, is_dir: bool) -> bool {
+        for pattern in self.patterns.iter().rev() {
+            if pattern.matches(path, is_dir) {
+                return !pattern.negated;
+            }
+        }
+
+        return false;
+    }
+}
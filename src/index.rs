@@ -0,0 +1,545 @@
+use std::{
+    ffi::OsStr,
+    fs::{self, OpenOptions},
+    io::Write,
+    os::unix::{
+        ffi::OsStrExt,
+        fs::{symlink, MetadataExt, OpenOptionsExt},
+    },
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    hash_content, hash_object, read_tree, read_verified_content, write_object_file, Filters,
+    GitError, ObjectFormat, ObjectType, Repository, TreeEntry, TreeObject,
+};
+
+// One staged file, as stored in `.git/index`
+#[derive(Clone, Debug)]
+pub struct IndexEntry {
+    pub ctime_seconds: u32,
+    pub ctime_nanoseconds: u32,
+    pub mtime_seconds: u32,
+    pub mtime_nanoseconds: u32,
+    pub dev: u32,
+    pub ino: u32,
+    pub mode: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub size: u32,
+    pub sha: String,
+    // 0 unless the path is being merged, in which case 1 (base), 2 (ours) or 3 (theirs)
+    pub stage: u8,
+    pub path: String,
+}
+
+pub struct Index {
+    pub version: u32,
+    pub entries: Vec<IndexEntry>,
+}
+
+impl Index {
+    // Stages `entry`, replacing whatever was staged at its path, merge stages included
+    pub fn add(self: &mut Index, entry: IndexEntry) {
+        self.entries.retain(|staged| staged.path != entry.path);
+
+        let position = self
+            .entries
+            .partition_point(|staged| staged.path < entry.path);
+        self.entries.insert(position, entry);
+    }
+
+    // Unstages every version of `path`, returning whether it was staged at all
+    pub fn remove(self: &mut Index, path: &str) -> bool {
+        let count = self.entries.len();
+        self.entries.retain(|staged| staged.path != path);
+
+        return self.entries.len() != count;
+    }
+
+    // Inverse of `parse_index`, entries are written in the order they are in and must be sorted
+    // Extended flags are never set, which makes version 3 entries the same as version 2 ones
+    pub fn pack(self: &Index, object_format: ObjectFormat) -> Result<Vec<u8>, GitError> {
+        let mut content = Vec::new();
+        content.extend_from_slice(b"DIRC");
+        content.extend_from_slice(&self.version.to_be_bytes());
+        content.extend_from_slice(&(self.entries.len() as u32).to_be_bytes());
+
+        for entry in self.entries.iter() {
+            let entry_start = content.len();
+
+            for field in [
+                entry.ctime_seconds,
+                entry.ctime_nanoseconds,
+                entry.mtime_seconds,
+                entry.mtime_nanoseconds,
+                entry.dev,
+                entry.ino,
+                entry.mode,
+                entry.uid,
+                entry.gid,
+                entry.size,
+            ] {
+                content.extend_from_slice(&field.to_be_bytes());
+            }
+
+            let sha = hex::decode(&entry.sha)
+                .ok()
+                .filter(|sha| sha.len() == object_format.raw_len())
+                .ok_or_else(|| {
+                    GitError::InvalidIndex(format!("invalid sha {} for {}", entry.sha, entry.path))
+                })?;
+            content.extend_from_slice(&sha);
+
+            // Longer paths have their length saturated, readers look for the NUL instead
+            let flags = (u16::from(entry.stage & 0b11) << 12) | entry.path.len().min(0xfff) as u16;
+            content.extend_from_slice(&flags.to_be_bytes());
+
+            content.extend_from_slice(entry.path.as_bytes());
+
+            // At least one NUL, up to the next multiple of 8 from the start of the entry
+            let padded_length = (content.len() - entry_start + 8) / 8 * 8;
+            content.resize(entry_start + padded_length, 0);
+        }
+
+        let mut hasher = object_format.hasher();
+        hasher.update(&content);
+        content.extend_from_slice(&hasher.finalize());
+
+        return Ok(content);
+    }
+}
+
+fn read_u32(content: &[u8], offset: usize) -> Result<u32, GitError> {
+    let bytes = content
+        .get(offset..offset + 4)
+        .ok_or_else(|| GitError::InvalidIndex("truncated".to_string()))?;
+
+    return Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]));
+}
+
+fn read_u16(content: &[u8], offset: usize) -> Result<u16, GitError> {
+    let bytes = content
+        .get(offset..offset + 2)
+        .ok_or_else(|| GitError::InvalidIndex("truncated".to_string()))?;
+
+    return Ok(u16::from_be_bytes([bytes[0], bytes[1]]));
+}
+
+// `DIRC <version> <entry count>`, then the entries sorted by path, then optional extensions,
+// then the sha of everything before it
+// Each entry is `<10 x 32 bits of stat data> <sha> <16 bits flags> <path>\0`, padded with NULs to
+// a multiple of 8 bytes
+// Shas, including the checksum, are as wide as the hashes of `object_format`
+pub fn parse_index(content: &[u8], object_format: ObjectFormat) -> Result<Index, GitError> {
+    let hash_length = object_format.raw_len();
+
+    if content.len() < 12 + hash_length || &content[0..4] != b"DIRC" {
+        return Err(GitError::InvalidIndex("missing DIRC signature".to_string()));
+    }
+
+    let (body, checksum) = content.split_at(content.len() - hash_length);
+
+    if object_format.digest(body) != hex::encode(checksum) {
+        return Err(GitError::InvalidIndex("checksum mismatch".to_string()));
+    }
+
+    let version = read_u32(content, 4)?;
+
+    // Version 4 compresses paths against the previous entry, which we don't support
+    if version != 2 && version != 3 {
+        return Err(GitError::InvalidIndex(format!(
+            "version {version} is not supported"
+        )));
+    }
+
+    let count = read_u32(content, 8)?;
+    let mut entries = Vec::new();
+    let mut offset = 12;
+
+    for _ in 0..count {
+        let entry_start = offset;
+
+        let mut stat = [0u32; 10];
+        for (i, field) in stat.iter_mut().enumerate() {
+            *field = read_u32(body, offset + i * 4)?;
+        }
+        offset += 40;
+
+        let sha = body
+            .get(offset..offset + hash_length)
+            .ok_or_else(|| GitError::InvalidIndex("truncated".to_string()))?;
+        offset += hash_length;
+
+        let flags = read_u16(body, offset)?;
+        offset += 2;
+
+        // Version 3 entries may carry a second set of flags, none of which we use
+        if version == 3 && flags & 0x4000 != 0 {
+            offset += 2;
+        }
+
+        let path_length = body
+            .get(offset..)
+            .unwrap_or(&[])
+            .iter()
+            .position(|&byte| byte == 0)
+            .ok_or_else(|| GitError::InvalidIndex("unterminated path".to_string()))?;
+        let path = std::str::from_utf8(&body[offset..offset + path_length])
+            .map_err(|_| GitError::InvalidIndex("path is not utf8".to_string()))?;
+        validate_path(path)?;
+        offset += path_length;
+
+        // At least one NUL, up to the next multiple of 8 from the start of the entry
+        offset = entry_start + (offset - entry_start + 8) / 8 * 8;
+
+        entries.push(IndexEntry {
+            ctime_seconds: stat[0],
+            ctime_nanoseconds: stat[1],
+            mtime_seconds: stat[2],
+            mtime_nanoseconds: stat[3],
+            dev: stat[4],
+            ino: stat[5],
+            mode: stat[6],
+            uid: stat[7],
+            gid: stat[8],
+            size: stat[9],
+            sha: hex::encode(sha),
+            stage: ((flags >> 12) & 0b11) as u8,
+            path: path.to_string(),
+        });
+    }
+
+    return Ok(Index { version, entries });
+}
+
+// A repository without an index simply has nothing staged
+pub fn read_index(repository: &Repository) -> Result<Index, GitError> {
+    let content = match fs::read(repository.git_dir().join("index")) {
+        Ok(content) => content,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(Index {
+                version: 2,
+                entries: Vec::new(),
+            })
+        }
+        Err(error) => return Err(error.into()),
+    };
+
+    return parse_index(&content, repository.object_format());
+}
+
+// Paths are stored relative to the root of the working tree, and can't escape it or reach into
+// the repository itself
+pub fn validate_path(path: &str) -> Result<(), GitError> {
+    let valid = !path.is_empty()
+        && path
+            .split('/')
+            .all(|component| !matches!(component, "" | "." | ".." | ".git"));
+
+    if !valid {
+        return Err(GitError::InvalidPath(path.to_string()));
+    }
+
+    return Ok(());
+}
+
+// Stores the file at `path`, relative to the root of the working tree `work_tree`, as a blob and
+// describes it as an index entry, stat data included so unchanged files can be told apart without
+// hashing them again
+// Symlinks are stored as is, their content being the path they point to
+pub fn index_entry_from_file(
+    repository: &Repository,
+    work_tree: &Path,
+    path: &str,
+) -> Result<IndexEntry, GitError> {
+    validate_path(path)?;
+    let file_path = work_tree.join(path);
+
+    let metadata = match fs::symlink_metadata(&file_path) {
+        Ok(metadata) => metadata,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+            return Err(GitError::FileNotFound(PathBuf::from(path)))
+        }
+        Err(error) => return Err(error.into()),
+    };
+    let object_format = repository.object_format();
+
+    let (mode, sha) = if metadata.is_symlink() {
+        let target = fs::read_link(&file_path)?;
+        let sha = hash_content(
+            target.as_os_str().as_bytes(),
+            ObjectType::Blob,
+            Some(repository.git_dir()),
+            object_format,
+        )?;
+
+        (0o120000, sha)
+    } else if metadata.is_file() {
+        let sha = hash_object(
+            file_path,
+            ObjectType::Blob,
+            Some(repository.git_dir()),
+            object_format,
+            Filters::for_file(repository.git_dir(), work_tree, path)?,
+        )?;
+
+        // Only the executable bit of the owner matters, other permissions aren't tracked
+        let mode = if metadata.mode() & 0o100 != 0 {
+            0o100755
+        } else {
+            0o100644
+        };
+
+        (mode, sha)
+    } else {
+        return Err(GitError::UnsupportedFileType(PathBuf::from(path)));
+    };
+
+    // Stat fields are truncated to 32 bits, like git does
+    return Ok(IndexEntry {
+        ctime_seconds: metadata.ctime() as u32,
+        ctime_nanoseconds: metadata.ctime_nsec() as u32,
+        mtime_seconds: metadata.mtime() as u32,
+        mtime_nanoseconds: metadata.mtime_nsec() as u32,
+        dev: metadata.dev() as u32,
+        ino: metadata.ino() as u32,
+        mode,
+        uid: metadata.uid(),
+        gid: metadata.gid(),
+        size: metadata.size() as u32,
+        sha,
+        stage: 0,
+        path: path.to_string(),
+    });
+}
+
+// Replaces `.git/index`, through a lock file renamed over it so readers never see a partial index
+pub fn write_index(repository: &Repository, index: &Index) -> Result<(), GitError> {
+    let content = index.pack(repository.object_format())?;
+
+    let lock_path = repository.git_dir().join("index.lock");
+    fs::write(&lock_path, content)?;
+    fs::rename(&lock_path, repository.git_dir().join("index"))?;
+
+    return Ok(());
+}
+
+// Every blob of `tree_hash` and its subtrees staged at its path, sorted like the index expects
+// Stat data is left zeroed, which makes every entry look modified until it is refreshed
+pub fn index_from_tree(repository: &Repository, tree_hash: &str) -> Result<Index, GitError> {
+    let mut entries = Vec::new();
+    add_tree_entries(repository, tree_hash, "", &mut entries)?;
+
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+
+    return Ok(Index {
+        version: 2,
+        entries,
+    });
+}
+
+fn add_tree_entries(
+    repository: &Repository,
+    tree_hash: &str,
+    prefix: &str,
+    entries: &mut Vec<IndexEntry>,
+) -> Result<(), GitError> {
+    for entry in read_tree(repository, tree_hash)?.entries {
+        // Index paths are text, so a name that isn't UTF-8 can't be staged
+        let name = std::str::from_utf8(&entry.name).map_err(|_| {
+            GitError::InvalidPath(format!("{prefix}{}", String::from_utf8_lossy(&entry.name)))
+        })?;
+        let path = format!("{prefix}{name}");
+
+        // A name is a single component, which mustn't lead out of its directory once checked out
+        if name.contains(['/', '\0']) || validate_path(name).is_err() {
+            return Err(GitError::InvalidPath(path));
+        }
+
+        if entry.object_type() == ObjectType::Tree {
+            add_tree_entries(repository, &entry.sha, &format!("{path}/"), entries)?;
+            continue;
+        }
+
+        entries.push(IndexEntry {
+            ctime_seconds: 0,
+            ctime_nanoseconds: 0,
+            mtime_seconds: 0,
+            mtime_nanoseconds: 0,
+            dev: 0,
+            ino: 0,
+            mode: entry.mode,
+            uid: 0,
+            gid: 0,
+            size: 0,
+            sha: entry.sha,
+            stage: 0,
+            path,
+        });
+    }
+
+    return Ok(());
+}
+
+// Writes the blob of `entry` to `path`, replacing whatever is there, with its parents created
+// Nothing is written for an entry whose path could lead out of the working tree
+// Its filters are those of `entry.path` in the working tree rooted at `work_tree`
+// Executable files get every execute bit the umask allows, symlinks point to the blob content
+// Submodules are only created as an empty directory, their content lives in another repository
+pub fn checkout_entry(
+    repository: &Repository,
+    work_tree: &Path,
+    entry: &IndexEntry,
+    path: &Path,
+) -> Result<(), GitError> {
+    validate_path(&entry.path)?;
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    if entry.mode == 0o160000 {
+        if !path.is_dir() {
+            fs::create_dir(path)?;
+        }
+
+        return Ok(());
+    }
+
+    let (object_type, content) = read_verified_content(repository, &entry.sha)?;
+
+    if object_type != ObjectType::Blob {
+        return Err(GitError::TypeMismatch {
+            object_hash: entry.sha.clone(),
+            actual: object_type.as_str(),
+            expected: ObjectType::Blob.as_str(),
+        });
+    }
+
+    // Removing first lets the new file be created with its own mode, and a symlink be replaced
+    // instead of followed
+    match path.symlink_metadata() {
+        Ok(metadata) if metadata.is_dir() => fs::remove_dir_all(path)?,
+        Ok(_) => fs::remove_file(path)?,
+        Err(_) => {}
+    }
+
+    if entry.mode == 0o120000 {
+        symlink(OsStr::from_bytes(&content), path)?;
+
+        return Ok(());
+    }
+
+    let permissions = if entry.mode == 0o100755 { 0o777 } else { 0o666 };
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(permissions)
+        .open(path)?;
+    file.write_all(
+        &Filters::for_file(repository.git_dir(), work_tree, &entry.path)?.smudge(content),
+    )?;
+
+    return Ok(());
+}
+
+// Stores the tree described by the staged entries, along with every subtree, returning its hash
+// With a `prefix`, only the subtree of that directory is written
+pub fn write_tree_from_index(
+    repository: &Repository,
+    index: &Index,
+    prefix: &str,
+) -> Result<String, GitError> {
+    if let Some(entry) = index.entries.iter().find(|entry| entry.stage != 0) {
+        return Err(GitError::InvalidIndex(format!(
+            "{} is unmerged, cannot write a tree",
+            entry.path
+        )));
+    }
+
+    let object_format = repository.object_format();
+    let prefix = prefix.trim_end_matches('/');
+
+    if prefix.is_empty() {
+        return write_index_subtree(repository.git_dir(), &index.entries, 0, object_format);
+    }
+
+    validate_path(prefix)?;
+    let directory_prefix = format!("{prefix}/");
+
+    let start = index
+        .entries
+        .partition_point(|entry| entry.path < directory_prefix);
+    let end = start
+        + index.entries[start..]
+            .iter()
+            .take_while(|entry| entry.path.starts_with(&directory_prefix))
+            .count();
+
+    if start == end {
+        return Err(GitError::PrefixNotFound(prefix.to_string()));
+    }
+
+    return write_index_subtree(
+        repository.git_dir(),
+        &index.entries[start..end],
+        directory_prefix.len(),
+        object_format,
+    );
+}
+
+// `entries` all live under the same directory, whose path is `prefix_length` bytes long
+// Entries being sorted by path, those of a subdirectory are always next to each other
+fn write_index_subtree(
+    git_dir: &Path,
+    entries: &[IndexEntry],
+    prefix_length: usize,
+    object_format: ObjectFormat,
+) -> Result<String, GitError> {
+    let mut tree_entries = Vec::new();
+    let mut i = 0;
+
+    while i < entries.len() {
+        let entry = &entries[i];
+        let path = &entry.path[prefix_length..];
+
+        match path.split_once('/') {
+            None => {
+                tree_entries.push(TreeEntry {
+                    mode: entry.mode,
+                    name: path.as_bytes().to_vec(),
+                    sha: entry.sha.clone(),
+                });
+                i += 1;
+            }
+            Some((directory, _)) => {
+                let directory_prefix = format!("{}{directory}/", &entry.path[..prefix_length]);
+                let end = i + entries[i..]
+                    .iter()
+                    .take_while(|entry| entry.path.starts_with(&directory_prefix))
+                    .count();
+
+                let sha = write_index_subtree(
+                    git_dir,
+                    &entries[i..end],
+                    directory_prefix.len(),
+                    object_format,
+                )?;
+
+                tree_entries.push(TreeEntry {
+                    mode: 0o40000,
+                    name: directory.as_bytes().to_vec(),
+                    sha,
+                });
+                i = end;
+            }
+        }
+    }
+
+    let tree = TreeObject {
+        entries: tree_entries,
+    };
+
+    return write_object_file(git_dir, tree.pack(), object_format);
+}
@@ -0,0 +1,2296 @@
+//! Reading and writing git objects (blobs, trees, commits and tags) and refs, the building
+//! blocks of git's plumbing commands.
+//!
+//! Hashing a blob without storing it:
+//!
+//! ```
+//! use git_starter_rust::{hash_content, ObjectFormat, ObjectType};
+//!
+//! let hash = hash_content(&b"hello world\n"[..], ObjectType::Blob, None, ObjectFormat::Sha1)?;
+//! assert_eq!(hash, "3b18e512dba79e4c8300dd08aeb37f8e728b8dad");
+//! # Ok::<(), git_starter_rust::GitError>(())
+//! ```
+
+use std::{
+    cmp::Ordering,
+    collections::HashSet,
+    env,
+    fs::{self, File},
+    io::{BufRead, BufReader, BufWriter, Cursor, Read, Write},
+    num::NonZeroUsize,
+    os::unix::{
+        ffi::OsStrExt,
+        fs::{MetadataExt, PermissionsExt},
+    },
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::atomic::{AtomicUsize, Ordering as AtomicOrdering},
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use flate2::{bufread::ZlibDecoder, write::ZlibEncoder, Compression};
+
+mod attributes;
+mod config;
+mod diff;
+mod error;
+mod filter;
+mod hash;
+mod history;
+mod ignore;
+mod index;
+mod pack;
+mod repository;
+
+pub use attributes::{AttributeValue, Attributes};
+pub use config::Config;
+pub use diff::{diff_trees, TreeChange};
+pub use error::GitError;
+pub use filter::{Filters, TextAttribute};
+pub use hash::{ObjectFormat, ObjectHasher};
+pub use history::{merge_bases, read_commit_object, rev_list};
+pub use ignore::{glob_match, IgnoreRules};
+pub use index::{
+    checkout_entry, index_entry_from_file, index_from_tree, parse_index, read_index, validate_path,
+    write_index, write_tree_from_index, Index, IndexEntry,
+};
+pub use pack::{
+    apply_delta, build_pack, crc32, find_packed_object, parse_pack, read_pack_entry,
+    read_pack_index, read_pack_indexes, read_packed_object, unpack_objects, write_pack_index,
+    PackEntry, PackIndex, PackedObject,
+};
+pub use repository::Repository;
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum ObjectType {
+    Blob,
+    Tree,
+    Commit,
+    Tag,
+}
+
+impl ObjectType {
+    pub fn as_str(self: &ObjectType) -> &'static str {
+        match self {
+            ObjectType::Blob => "blob",
+            ObjectType::Tree => "tree",
+            ObjectType::Commit => "commit",
+            ObjectType::Tag => "tag",
+        }
+    }
+}
+
+impl FromStr for ObjectType {
+    type Err = GitError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "blob" => Ok(ObjectType::Blob),
+            "tree" => Ok(ObjectType::Tree),
+            "commit" => Ok(ObjectType::Commit),
+            "tag" => Ok(ObjectType::Tag),
+            _ => Err(GitError::UnsupportedType(s.to_string())),
+        }
+    }
+}
+
+// `<type> <content-size>\0<content>`
+pub fn pack_object(object_type: ObjectType, content: &[u8]) -> Vec<u8> {
+    return [
+        object_type.as_str().as_bytes(),
+        b" ",
+        content.len().to_string().as_bytes(),
+        b"\0",
+        content,
+    ]
+    .concat();
+}
+
+pub struct BlobObject {
+    pub data: Vec<u8>,
+}
+
+impl BlobObject {
+    pub fn pack(self: &BlobObject) -> Vec<u8> {
+        return pack_object(ObjectType::Blob, &self.data);
+    }
+}
+
+#[derive(Clone, Debug, Eq)]
+pub struct TreeEntry {
+    pub mode: u32,
+    // Raw bytes, names don't have to be valid UTF-8 on Unix
+    pub name: Vec<u8>,
+    pub sha: String,
+}
+
+impl TreeEntry {
+    fn sort_key(self: &TreeEntry) -> impl Iterator<Item = &u8> {
+        let suffix: &[u8] = if self.mode == 0o40000 { b"/" } else { b"" };
+
+        return self.name.iter().chain(suffix);
+    }
+
+    pub fn pack(self: &TreeEntry) -> Vec<u8> {
+        let sha = hex::decode(&self.sha).unwrap();
+
+        return [
+            format!("{:o}", self.mode).as_bytes(),
+            b" ",
+            &self.name,
+            b"\0",
+            sha.as_slice(),
+        ]
+        .concat();
+    }
+
+    // The type of the object an entry points to is implied by its mode
+    // 160000 (submodule) points to a commit, which we don't produce but can still display
+    pub fn object_type(self: &TreeEntry) -> ObjectType {
+        match self.mode {
+            0o40000 => ObjectType::Tree,
+            0o160000 => ObjectType::Commit,
+            _ => ObjectType::Blob,
+        }
+    }
+
+    // `<mode> <type> <sha>\t<path>`, as printed by `cat-file -p` and `ls-tree`
+    pub fn format_line(self: &TreeEntry, path: &[u8]) -> Vec<u8> {
+        let header = format!(
+            "{:06o} {} {}\t",
+            self.mode,
+            self.object_type().as_str(),
+            self.sha
+        );
+
+        return [header.as_bytes(), path].concat();
+    }
+}
+
+// Inverse of `format_line`, the type must agree with the mode
+pub fn parse_tree_line(line: &str, object_format: ObjectFormat) -> Result<TreeEntry, GitError> {
+    let invalid_entry = || GitError::InvalidTreeEntry(line.to_string());
+
+    let (header, name) = line.split_once('\t').ok_or_else(invalid_entry)?;
+    let mut fields = header.split(' ');
+
+    let (mode, object_type, sha) =
+        match (fields.next(), fields.next(), fields.next(), fields.next()) {
+            (Some(mode), Some(object_type), Some(sha), None) => (mode, object_type, sha),
+            _ => return Err(invalid_entry()),
+        };
+
+    let mode = u32::from_str_radix(mode, 8).map_err(|_| invalid_entry())?;
+    let object_type = ObjectType::from_str(object_type)?;
+
+    let is_valid_sha = object_format.is_valid_hash(sha);
+    let is_valid_name = !name.is_empty() && name != "." && name != ".." && !name.contains('/');
+
+    if !is_valid_sha || !is_valid_name {
+        return Err(invalid_entry());
+    }
+
+    let entry = TreeEntry {
+        mode,
+        name: name.as_bytes().to_vec(),
+        sha: sha.to_ascii_lowercase(),
+    };
+
+    let expected_type = entry.object_type();
+
+    if expected_type != object_type {
+        return Err(GitError::TypeMismatch {
+            object_hash: entry.sha,
+            actual: object_type.as_str(),
+            expected: expected_type.as_str(),
+        });
+    }
+
+    return Ok(entry);
+}
+
+// `path` as git prints it by default (`core.quotepath`): left alone unless it holds control
+// characters, `"`, `\` or non-ASCII bytes, in which case it is quoted with C-style escapes
+pub fn quote_path(path: &[u8]) -> String {
+    let needs_quoting = path
+        .iter()
+        .any(|&byte| !(0x20..0x7f).contains(&byte) || byte == b'"' || byte == b'\\');
+
+    if !needs_quoting {
+        return String::from_utf8_lossy(path).into_owned();
+    }
+
+    let mut quoted = String::from("\"");
+
+    for &byte in path {
+        match byte {
+            0x07 => quoted.push_str("\\a"),
+            0x08 => quoted.push_str("\\b"),
+            b'\t' => quoted.push_str("\\t"),
+            b'\n' => quoted.push_str("\\n"),
+            0x0b => quoted.push_str("\\v"),
+            0x0c => quoted.push_str("\\f"),
+            b'\r' => quoted.push_str("\\r"),
+            b'"' => quoted.push_str("\\\""),
+            b'\\' => quoted.push_str("\\\\"),
+            byte if !(0x20..0x7f).contains(&byte) => quoted.push_str(&format!("\\{byte:03o}")),
+            byte => quoted.push(byte as char),
+        }
+    }
+
+    quoted.push('"');
+
+    return quoted;
+}
+
+// git is very particular about how it sorts entries in a tree
+// 1. case-sensitive (uppercase before lowercase)
+// 2. for the sake of comparison, directories are treated as if there were a trailing `/`
+impl PartialOrd for TreeEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for TreeEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.sort_key().cmp(other.sort_key())
+    }
+}
+
+impl PartialEq for TreeEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && self.mode == other.mode
+    }
+}
+
+pub struct TreeObject {
+    pub entries: Vec<TreeEntry>,
+}
+
+impl TreeObject {
+    pub fn pack(self: &TreeObject) -> Vec<u8> {
+        let mut entries = self.entries.iter().collect::<Vec<&TreeEntry>>();
+        entries.sort();
+
+        let packed = entries
+            .into_iter()
+            .map(|entry| entry.pack())
+            .collect::<Vec<Vec<u8>>>()
+            .concat();
+
+        return pack_object(ObjectType::Tree, &packed);
+    }
+}
+
+pub struct CommitObject {
+    pub tree_hash: String,
+    pub parents: Vec<String>,
+    pub author_name: String,
+    pub author_email: String,
+    pub author_date_seconds: SystemTime,
+    pub author_date_timezone: String,
+    pub committer_name: String,
+    pub committer_email: String,
+    pub committer_date_seconds: SystemTime,
+    pub committer_date_timezone: String,
+    // Headers following `committer`, in order, such as `gpgsig` for signed commits
+    // Values spanning several lines are kept with their newlines, without the leading spaces
+    // Values are raw bytes, they may be in the encoding named by an `encoding` header
+    pub extra_headers: Vec<(String, Vec<u8>)>,
+    // Everything after the blank line ending the headers, usually ending with a newline
+    // Raw bytes, like the values of extra headers they may be in another encoding than UTF-8
+    pub commit_message: Vec<u8>,
+}
+
+// Offset from UTC of the local timezone at `time`, in seconds
+pub fn local_timezone_offset(time: SystemTime) -> Result<i64, GitError> {
+    let seconds = time.duration_since(UNIX_EPOCH)?.as_secs() as libc::time_t;
+    let mut tm = std::mem::MaybeUninit::<libc::tm>::zeroed();
+
+    // SAFETY: both pointers are valid for the duration of the call, `tm` is only read if
+    // `localtime_r` succeeded, in which case it has been fully initialized
+    let tm = unsafe {
+        if libc::localtime_r(&seconds, tm.as_mut_ptr()).is_null() {
+            return Err(GitError::UnknownTimezone);
+        }
+
+        tm.assume_init()
+    };
+
+    return Ok(tm.tm_gmtoff);
+}
+
+// `±HHMM`, the way git writes timezones
+pub fn format_timezone(offset_seconds: i64) -> String {
+    let sign = if offset_seconds < 0 { '-' } else { '+' };
+    let offset_minutes = offset_seconds.abs() / 60;
+
+    return format!("{sign}{:02}{:02}", offset_minutes / 60, offset_minutes % 60);
+}
+
+// `Tue Nov 14 22:13:20 2023 +0000`, the way `git log` shows dates, in the timezone they were
+// recorded in
+pub fn format_date(time: SystemTime, timezone: &str) -> Result<String, GitError> {
+    validate_timezone(timezone)?;
+
+    let hours: i64 = timezone[1..3].parse().unwrap_or(0);
+    let minutes: i64 = timezone[3..5].parse().unwrap_or(0);
+    let offset_seconds = (hours * 60 + minutes) * 60;
+    let offset_seconds = if timezone.starts_with('-') {
+        -offset_seconds
+    } else {
+        offset_seconds
+    };
+
+    let seconds = time.duration_since(UNIX_EPOCH)?.as_secs() as i64 + offset_seconds;
+    let days = seconds.div_euclid(86400);
+    let seconds_of_day = seconds.rem_euclid(86400);
+
+    // Days since the epoch to a proleptic gregorian date, with years starting in March so the
+    // leap day comes last
+    let shifted_days = days + 719468;
+    let era = shifted_days.div_euclid(146097);
+    let day_of_era = shifted_days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    const WEEKDAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+
+    return Ok(format!(
+        "{} {} {} {:02}:{:02}:{:02} {} {}",
+        WEEKDAYS[days.rem_euclid(7) as usize],
+        MONTHS[(month - 1) as usize],
+        day,
+        seconds_of_day / 3600,
+        seconds_of_day / 60 % 60,
+        seconds_of_day % 60,
+        year,
+        timezone
+    ));
+}
+
+pub fn validate_timezone(timezone: &str) -> Result<(), GitError> {
+    let bytes = timezone.as_bytes();
+
+    let is_valid = bytes.len() == 5
+        && (bytes[0] == b'+' || bytes[0] == b'-')
+        && bytes[1..].iter().all(|byte| byte.is_ascii_digit());
+
+    if !is_valid {
+        return Err(GitError::InvalidTimezone(timezone.to_string()));
+    }
+
+    return Ok(());
+}
+
+impl CommitObject {
+    pub fn pack(self: &CommitObject) -> Result<Vec<u8>, GitError> {
+        validate_timezone(&self.author_date_timezone)?;
+        validate_timezone(&self.committer_date_timezone)?;
+
+        let tree_hash = [b"tree ", self.tree_hash.as_bytes(), b"\n"].concat();
+        let parents = self
+            .parents
+            .as_slice()
+            .into_iter()
+            .map(|parent_hash| [b"parent ", parent_hash.as_bytes(), b"\n"].concat())
+            .collect::<Vec<Vec<u8>>>()
+            .concat();
+
+        let author = [
+            b"author ",
+            self.author_name.as_bytes(),
+            b" <",
+            self.author_email.as_bytes(),
+            b"> ",
+            self.author_date_seconds
+                .duration_since(UNIX_EPOCH)?
+                .as_secs()
+                .to_string()
+                .as_bytes(),
+            b" ",
+            self.author_date_timezone.as_bytes(),
+            b"\n",
+        ]
+        .concat();
+
+        let committer = [
+            b"committer ",
+            self.committer_name.as_bytes(),
+            b" <",
+            self.committer_email.as_bytes(),
+            b"> ",
+            self.committer_date_seconds
+                .duration_since(UNIX_EPOCH)?
+                .as_secs()
+                .to_string()
+                .as_bytes(),
+            b" ",
+            self.committer_date_timezone.as_bytes(),
+            b"\n",
+        ]
+        .concat();
+
+        let mut extra_headers = Vec::new();
+
+        for (key, value) in self.extra_headers.iter() {
+            extra_headers.extend_from_slice(key.as_bytes());
+            extra_headers.push(b' ');
+
+            // Every line after the first of a value is a continuation line, starting with a space
+            for &byte in value {
+                extra_headers.push(byte);
+
+                if byte == b'\n' {
+                    extra_headers.push(b' ');
+                }
+            }
+
+            extra_headers.push(b'\n');
+        }
+
+        let message = [b"\n", self.commit_message.as_slice()].concat();
+
+        let content = [
+            tree_hash,
+            parents,
+            author,
+            committer,
+            extra_headers,
+            message,
+        ]
+        .concat();
+
+        return Ok(pack_object(ObjectType::Commit, &content));
+    }
+}
+
+// An annotated tag, pointing to an object of any type (usually a commit)
+pub struct TagObject {
+    pub object_hash: String,
+    pub object_type: ObjectType,
+    pub tag_name: String,
+    pub tagger_name: String,
+    pub tagger_email: String,
+    pub tagger_date_seconds: SystemTime,
+    pub tagger_date_timezone: String,
+    pub tag_message: String,
+}
+
+impl TagObject {
+    pub fn pack(self: &TagObject) -> Result<Vec<u8>, GitError> {
+        validate_timezone(&self.tagger_date_timezone)?;
+
+        let content = format!(
+            "object {}\ntype {}\ntag {}\ntagger {} <{}> {} {}\n\n{}\n",
+            self.object_hash,
+            self.object_type.as_str(),
+            self.tag_name,
+            self.tagger_name,
+            self.tagger_email,
+            self.tagger_date_seconds
+                .duration_since(UNIX_EPOCH)?
+                .as_secs(),
+            self.tagger_date_timezone,
+            self.tag_message
+        );
+
+        return Ok(pack_object(ObjectType::Tag, content.as_bytes()));
+    }
+}
+
+pub enum GitObject {
+    Blob(BlobObject),
+    Tree(TreeObject),
+    Commit(CommitObject),
+    Tag(TagObject),
+}
+
+impl GitObject {
+    pub fn pack(self: &GitObject) -> Result<Vec<u8>, GitError> {
+        match self {
+            GitObject::Blob(blob) => return Ok(blob.pack()),
+            GitObject::Tree(tree) => return Ok(tree.pack()),
+            GitObject::Commit(commit) => return commit.pack(),
+            GitObject::Tag(tag) => return tag.pack(),
+        }
+    }
+}
+
+pub fn read_tree_entry(
+    reader: &mut impl BufRead,
+    object_format: ObjectFormat,
+) -> Result<(TreeEntry, usize), GitError> {
+    let mut buf = Vec::new();
+    let mut total = 0;
+
+    let n = reader.read_until(b' ', &mut buf)?;
+    total += n;
+
+    let mode = buf
+        .strip_suffix(&[b' '])
+        .ok_or_else(|| GitError::Corrupt("truncated tree entry".to_string()))?;
+
+    // modes are stored as octal text
+    let mode = std::str::from_utf8(mode)
+        .ok()
+        .and_then(|mode| u32::from_str_radix(mode, 8).ok())
+        .ok_or_else(|| GitError::Corrupt("tree entry mode is not an octal number".to_string()))?;
+
+    buf.clear();
+    let n = reader.read_until(0, &mut buf)?;
+    total += n;
+    let name = buf
+        .strip_suffix(&[0])
+        .ok_or_else(|| GitError::Corrupt("truncated tree entry".to_string()))?;
+
+    let mut sha = vec![0u8; object_format.raw_len()];
+    match reader.read_exact(&mut sha) {
+        Ok(()) => total += sha.len(),
+        Err(error) if error.kind() == std::io::ErrorKind::UnexpectedEof => {
+            return Err(GitError::Corrupt("truncated tree entry".to_string()))
+        }
+        Err(error) => return Err(error.into()),
+    }
+
+    return Ok((
+        TreeEntry {
+            mode,
+            name: name.to_vec(),
+            sha: hex::encode(sha),
+        },
+        total,
+    ));
+}
+
+// `<name> <<email>> <seconds> <timezone>`
+fn read_ident(ident: &str) -> Result<(String, String, SystemTime, String), GitError> {
+    let invalid_ident = || GitError::Corrupt(format!("invalid ident `{ident}`"));
+
+    let (rest, timezone) = ident.rsplit_once(' ').ok_or_else(invalid_ident)?;
+    let (rest, seconds) = rest.rsplit_once(' ').ok_or_else(invalid_ident)?;
+
+    let seconds: u64 = seconds.parse().map_err(|_| invalid_ident())?;
+
+    let (name, email) = match (rest.find('<'), rest.rfind('>')) {
+        (Some(start), Some(end)) if start < end => {
+            (rest[..start].trim_end(), &rest[start + 1..end])
+        }
+        // Commits written without brackets around the email
+        _ => rest.rsplit_once(' ').ok_or_else(invalid_ident)?,
+    };
+
+    return Ok((
+        name.to_string(),
+        email.to_string(),
+        UNIX_EPOCH + Duration::from_secs(seconds),
+        timezone.to_string(),
+    ));
+}
+
+// `tree <sha>\n`
+// `parent <sha>\n` (zero or more)
+// `author <ident>\n`
+// `committer <ident>\n`
+// Other headers (zero or more), continued over several lines by lines starting with a space
+// `\n<message>`
+// Only the values of other headers and the message may be something else than UTF-8
+pub fn read_commit(content: &[u8]) -> Result<CommitObject, GitError> {
+    let to_str = |bytes| {
+        return std::str::from_utf8(bytes)
+            .map_err(|_| GitError::Corrupt("commit is not utf8".to_string()));
+    };
+
+    let (headers, message) = match content.windows(2).position(|pair| pair == b"\n\n") {
+        Some(position) => (&content[..position], &content[position + 2..]),
+        None => (content, &b""[..]),
+    };
+
+    let mut tree_hash = None;
+    let mut parents = Vec::new();
+    let mut author = None;
+    let mut committer = None;
+    let mut extra_headers: Vec<(String, Vec<u8>)> = Vec::new();
+
+    for line in headers.split(|&byte| byte == b'\n') {
+        // Headers end with a newline when there is no message
+        if line.is_empty() {
+            continue;
+        }
+
+        // Signatures span several lines, none of which must be mistaken for a header
+        if let Some(continuation) = line.strip_prefix(b" ") {
+            match extra_headers.last_mut() {
+                Some((_, value)) => {
+                    value.push(b'\n');
+                    value.extend_from_slice(continuation);
+                }
+                None => {
+                    return Err(GitError::Corrupt(
+                        "commit has a continuation line without a header".to_string(),
+                    ))
+                }
+            }
+
+            continue;
+        }
+
+        let (key, value) = match line.iter().position(|&byte| byte == b' ') {
+            Some(position) => (&line[..position], &line[position + 1..]),
+            None => (line, &b""[..]),
+        };
+
+        match key {
+            b"tree" => tree_hash = Some(to_str(value)?.to_string()),
+            b"parent" => parents.push(to_str(value)?.to_string()),
+            b"author" => author = Some(read_ident(to_str(value)?)?),
+            b"committer" => committer = Some(read_ident(to_str(value)?)?),
+            _ => extra_headers.push((to_str(key)?.to_string(), value.to_vec())),
+        }
+    }
+
+    let tree_hash = tree_hash.ok_or_else(|| GitError::Corrupt("commit has no tree".to_string()))?;
+    let (author_name, author_email, author_date_seconds, author_date_timezone) =
+        author.ok_or_else(|| GitError::Corrupt("commit has no author".to_string()))?;
+    let (committer_name, committer_email, committer_date_seconds, committer_date_timezone) =
+        committer.ok_or_else(|| GitError::Corrupt("commit has no committer".to_string()))?;
+
+    let commit_message = message.to_vec();
+
+    return Ok(CommitObject {
+        tree_hash,
+        parents,
+        author_name,
+        author_email,
+        author_date_seconds,
+        author_date_timezone,
+        committer_name,
+        committer_email,
+        committer_date_seconds,
+        committer_date_timezone,
+        extra_headers,
+        commit_message,
+    });
+}
+
+// `object <sha>\n`
+// `type <type>\n`
+// `tag <name>\n`
+// `tagger <ident>\n`
+// `\n<message>\n`
+// The headers must come in this order, anything after them is ignored
+pub fn read_tag(content: &[u8], object_format: ObjectFormat) -> Result<TagObject, GitError> {
+    let content = std::str::from_utf8(content)
+        .map_err(|_| GitError::Corrupt("tag is not utf8".to_string()))?;
+
+    let (headers, message) = content.split_once("\n\n").unwrap_or((content, ""));
+    let mut headers = headers.lines();
+
+    let mut read_header = |key: &str| {
+        return headers
+            .next()
+            .and_then(|line| line.strip_prefix(key))
+            .and_then(|line| line.strip_prefix(' '))
+            .ok_or_else(|| GitError::Corrupt(format!("tag has no {key}")));
+    };
+
+    let object_hash = read_header("object")?;
+    let object_type = ObjectType::from_str(read_header("type")?)?;
+    let tag_name = read_header("tag")?;
+    let (tagger_name, tagger_email, tagger_date_seconds, tagger_date_timezone) =
+        read_ident(read_header("tagger")?)?;
+
+    if !object_format.is_valid_hash(object_hash) {
+        return Err(GitError::Corrupt(format!(
+            "tag points to an invalid object `{object_hash}`"
+        )));
+    }
+
+    if tag_name.is_empty() {
+        return Err(GitError::Corrupt("tag has an empty name".to_string()));
+    }
+
+    // `pack` adds the trailing newline back
+    let tag_message = message.strip_suffix('\n').unwrap_or(message).to_string();
+
+    return Ok(TagObject {
+        object_hash: object_hash.to_string(),
+        object_type,
+        tag_name: tag_name.to_string(),
+        tagger_name,
+        tagger_email,
+        tagger_date_seconds,
+        tagger_date_timezone,
+        tag_message,
+    });
+}
+
+// `<type> <content-size>\0`, `object_hash` is only used to report a malformed header
+// Reads are bounded, so a corrupt object without a space or NUL isn't read whole looking for it
+pub fn read_object_header(
+    reader: &mut impl BufRead,
+    object_hash: &str,
+) -> Result<(ObjectType, usize), GitError> {
+    let invalid_header = |reason| GitError::InvalidObjectHeader {
+        object_hash: object_hash.to_string(),
+        reason,
+    };
+
+    let mut buf = Vec::new();
+
+    // The longest type is `commit`
+    (&mut *reader).take(7).read_until(b' ', &mut buf)?;
+    let object_type = buf
+        .strip_suffix(&[b' '])
+        .ok_or_else(|| invalid_header("missing object type"))?;
+
+    let object_type = ObjectType::from_str(&String::from_utf8_lossy(object_type))
+        .map_err(|_| invalid_header("unknown object type"))?;
+
+    // `usize::MAX` has 20 digits
+    buf.clear();
+    (&mut *reader).take(21).read_until(0, &mut buf)?;
+    let size = buf
+        .strip_suffix(&[0])
+        .ok_or_else(|| invalid_header("missing object size"))?;
+
+    let size: usize = std::str::from_utf8(size)
+        .ok()
+        .filter(|size| !size.starts_with('+'))
+        .and_then(|size| size.parse().ok())
+        .ok_or_else(|| invalid_header("object size is not a number"))?;
+
+    return Ok((object_type, size));
+}
+
+// `<blob> <content-size>\0<content>`
+// `<tree> <content-size>\0<content>` where `<content>`
+//      `<mode> <name>\0<sha>`, the sha being 20 bytes (sha1) or 32 bytes (sha256)
+// `<commit> <content-size>\0<content>`
+pub fn read_git_object(
+    reader: &mut impl BufRead,
+    object_hash: &str,
+    object_format: ObjectFormat,
+) -> Result<GitObject, GitError> {
+    let (object_type, size) = read_object_header(reader, object_hash)?;
+
+    return read_object_body(reader, object_type, size, object_format);
+}
+
+// Raw `<content>` following the header, as stored
+pub fn read_object_content(reader: &mut impl BufRead, size: usize) -> Result<Vec<u8>, GitError> {
+    let mut buf = Vec::new();
+    let n = reader.read_to_end(&mut buf)?;
+
+    if n != size {
+        return Err(GitError::Corrupt(format!(
+            "expected {size} bytes, got {n} bytes"
+        )));
+    }
+
+    return Ok(buf);
+}
+
+pub fn read_object_body(
+    reader: &mut impl BufRead,
+    object_type: ObjectType,
+    size: usize,
+    object_format: ObjectFormat,
+) -> Result<GitObject, GitError> {
+    match object_type {
+        ObjectType::Tree => {
+            // Entries are parsed out of exactly `size` bytes, so none can run past the declared size
+            let content = read_object_content(reader, size)?;
+            let mut content = content.as_slice();
+            let mut entries: Vec<TreeEntry> = Vec::new();
+
+            while !content.is_empty() {
+                let (entry, _) = read_tree_entry(&mut content, object_format)?;
+                entries.push(entry);
+            }
+
+            let object = GitObject::Tree(TreeObject { entries });
+
+            return Ok(object);
+        }
+        ObjectType::Blob => {
+            let data = read_object_content(reader, size)?;
+
+            let object = GitObject::Blob(BlobObject { data });
+
+            return Ok(object);
+        }
+        ObjectType::Commit => {
+            let content = read_object_content(reader, size)?;
+
+            let object = GitObject::Commit(read_commit(&content)?);
+
+            return Ok(object);
+        }
+        ObjectType::Tag => {
+            let content = read_object_content(reader, size)?;
+
+            let object = GitObject::Tag(read_tag(&content, object_format)?);
+
+            return Ok(object);
+        }
+    };
+}
+
+// Walks up from the current directory until a repository is found, `GIT_DIR` takes precedence
+pub fn find_git_dir() -> Result<PathBuf, GitError> {
+    if let Some(git_dir) = env::var_os("GIT_DIR") {
+        return Ok(PathBuf::from(git_dir));
+    }
+
+    let current_dir = env::current_dir()?;
+
+    for directory in current_dir.ancestors() {
+        if let Some(git_dir) = git_dir_at(directory)? {
+            return Ok(git_dir);
+        }
+    }
+
+    return Err(GitError::NotARepository);
+}
+
+// Path of the current directory from the root of its working tree, empty at the root
+// With `GIT_DIR` set the current directory is the root, like in git
+pub fn current_prefix() -> Result<Vec<u8>, GitError> {
+    if env::var_os("GIT_DIR").is_some() {
+        return Ok(Vec::new());
+    }
+
+    let current_dir = env::current_dir()?;
+
+    for directory in current_dir.ancestors() {
+        if git_dir_at(directory)?.is_some() {
+            let prefix = current_dir.strip_prefix(directory).unwrap_or(Path::new(""));
+
+            return Ok(prefix.as_os_str().as_bytes().to_vec());
+        }
+    }
+
+    return Ok(Vec::new());
+}
+
+// Root of the working tree the current directory is in
+// With `GIT_DIR` set the current directory is the root, like in git
+pub fn find_work_tree() -> Result<PathBuf, GitError> {
+    let current_dir = env::current_dir()?;
+
+    if env::var_os("GIT_DIR").is_some() {
+        return Ok(current_dir);
+    }
+
+    for directory in current_dir.ancestors() {
+        if git_dir_at(directory)?.is_some() {
+            return Ok(directory.to_path_buf());
+        }
+    }
+
+    return Err(GitError::NotARepository);
+}
+
+// `path`, as given on the command line from the current directory, relative to the root of the
+// working tree instead, with `.` and `..` resolved. Paths leading out of the working tree are
+// invalid
+pub fn path_from_root(path: &str) -> Result<String, GitError> {
+    let invalid_path = || GitError::InvalidPath(path.to_string());
+
+    let (prefix, relative_path) = if path.starts_with('/') {
+        let relative_path = Path::new(path)
+            .strip_prefix(find_work_tree()?)
+            .map_err(|_| invalid_path())?
+            .to_str()
+            .ok_or_else(invalid_path)?;
+
+        (String::new(), relative_path)
+    } else {
+        let prefix = String::from_utf8(current_prefix()?).map_err(|_| invalid_path())?;
+
+        (prefix, path)
+    };
+
+    let mut names: Vec<&str> = prefix.split('/').filter(|name| !name.is_empty()).collect();
+
+    for name in relative_path.split('/') {
+        match name {
+            "" | "." => {}
+            ".." => {
+                names.pop().ok_or_else(invalid_path)?;
+            }
+            name => names.push(name),
+        }
+    }
+
+    return Ok(names.join("/"));
+}
+
+// The git directory of the repository rooted at `directory`, if there is one
+pub fn git_dir_at(directory: &Path) -> Result<Option<PathBuf>, GitError> {
+    let dot_git = directory.join(".git");
+
+    if dot_git.is_dir() {
+        return Ok(Some(dot_git));
+    }
+
+    // `.git` can be a file pointing to the actual git directory: `gitdir: <path>`
+    if dot_git.is_file() {
+        let content = fs::read_to_string(&dot_git)?;
+        let git_dir = content
+            .strip_prefix("gitdir: ")
+            .ok_or_else(|| GitError::InvalidGitFile(dot_git.clone()))?
+            .trim_end();
+
+        return Ok(Some(directory.join(git_dir)));
+    }
+
+    // Bare repository
+    if directory.join("HEAD").is_file() && directory.join("objects").is_dir() {
+        return Ok(Some(directory.to_path_buf()));
+    }
+
+    return Ok(None);
+}
+
+// Directory holding what every worktree of a repository shares: objects, most refs, the config
+// A linked worktree has its own git directory, which names the shared one in a `commondir` file,
+// relative to itself
+pub fn common_dir(git_dir: &Path) -> Result<PathBuf, GitError> {
+    match fs::read_to_string(git_dir.join("commondir")) {
+        Ok(content) => return Ok(git_dir.join(content.trim_end())),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(git_dir.to_path_buf())
+        }
+        Err(error) => return Err(error.into()),
+    }
+}
+
+pub fn objects_dir(git_dir: &Path) -> Result<PathBuf, GitError> {
+    return Ok(common_dir(git_dir)?.join("objects"));
+}
+
+// Every object directory objects are read from: the repository's own, then those it borrows from
+// An object directory lists the ones it borrows from in `info/alternates`, one per line, relative
+// paths being relative to itself, and those can have alternates of their own
+// Objects are only ever written to the repository's own directory
+pub fn object_dirs(git_dir: &Path) -> Result<Vec<PathBuf>, GitError> {
+    let objects_dir = objects_dir(git_dir)?;
+
+    // Compared canonicalized, so that alternates pointing at each other aren't followed forever
+    let canonical = |directory: &Path| fs::canonicalize(directory).ok();
+    let mut seen = HashSet::from([canonical(&objects_dir)]);
+
+    let mut directories = vec![objects_dir];
+    let mut i = 0;
+
+    while i < directories.len() {
+        let directory = directories[i].clone();
+        i += 1;
+
+        let alternates = match fs::read_to_string(directory.join("info").join("alternates")) {
+            Ok(alternates) => alternates,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(error) => return Err(error.into()),
+        };
+
+        for line in alternates.lines() {
+            let line = line.trim_end();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            // Missing alternates are skipped, like git does
+            let alternate = directory.join(line);
+            if alternate.is_dir() && seen.insert(canonical(&alternate)) {
+                directories.push(alternate);
+            }
+        }
+    }
+
+    return Ok(directories);
+}
+
+// `HEAD`, other pseudo refs and a few namespaces belong to a worktree, every other ref is shared
+pub fn ref_path(git_dir: &Path, refname: &str) -> Result<PathBuf, GitError> {
+    let is_per_worktree = !refname.starts_with("refs/")
+        || ["refs/worktree/", "refs/bisect/", "refs/rewritten/"]
+            .iter()
+            .any(|prefix| refname.starts_with(prefix));
+
+    if is_per_worktree {
+        return Ok(git_dir.join(refname));
+    }
+
+    return Ok(common_dir(git_dir)?.join(refname));
+}
+
+// Expand a (possibly abbreviated) object name into the full hash of a stored object
+pub fn resolve_object_hash(repository: &Repository, prefix: &str) -> Result<String, GitError> {
+    let object_format = repository.object_format();
+
+    // Anything else could reach outside of `objects/` once used as a path
+    let is_valid =
+        prefix.len() <= object_format.hex_len() && prefix.chars().all(|c| c.is_ascii_hexdigit());
+
+    if !is_valid {
+        return Err(GitError::InvalidObjectName(prefix.to_string()));
+    }
+
+    if prefix.len() < 4 {
+        return Err(GitError::ObjectNameTooShort(prefix.to_string()));
+    }
+
+    // Hashes are stored lowercase, but are accepted in any case
+    let prefix = prefix.to_ascii_lowercase();
+    let prefix = prefix.as_str();
+
+    let mut candidates = objects_with_prefix(repository, prefix)?;
+
+    match candidates.len() {
+        0 if is_empty_tree(prefix, object_format) => return Ok(prefix.to_string()),
+        0 => return Err(GitError::ObjectNotFound(prefix.to_string())),
+        1 => return Ok(candidates.remove(0)),
+        _ => return Err(GitError::AmbiguousObjectName(prefix.to_string())),
+    }
+}
+
+// Shortest prefix of a full `object_hash`, at least `min_len` long, that names no other object
+pub fn abbreviate_object_hash(
+    repository: &Repository,
+    object_hash: &str,
+    min_len: usize,
+) -> Result<String, GitError> {
+    if !repository.object_format().is_valid_hash(object_hash) {
+        return Err(GitError::InvalidObjectName(object_hash.to_string()));
+    }
+
+    let min_len = min_len.clamp(4, object_hash.len());
+    let mut len = min_len;
+
+    // Only objects sharing the shortest allowed prefix can make it ambiguous
+    for candidate in objects_with_prefix(repository, &object_hash[..min_len])? {
+        if candidate == object_hash {
+            continue;
+        }
+
+        let common_len = candidate
+            .bytes()
+            .zip(object_hash.bytes())
+            .take_while(|(a, b)| a == b)
+            .count();
+        len = len.max(common_len + 1);
+    }
+
+    return Ok(object_hash[..len.min(object_hash.len())].to_string());
+}
+
+// Full hashes of the stored objects starting with `prefix`, lowercase and at least 2 characters
+// long, sorted
+fn objects_with_prefix(repository: &Repository, prefix: &str) -> Result<Vec<String>, GitError> {
+    let (dirname, filename) = match (prefix.get(0..2), prefix.get(2..)) {
+        (Some(dirname), Some(filename)) => (dirname, filename),
+        _ => return Ok(Vec::new()),
+    };
+
+    let mut candidates = Vec::new();
+
+    for objects_dir in object_dirs(repository.git_dir())? {
+        if let Ok(directory) = fs::read_dir(objects_dir.join(dirname)) {
+            for entry in directory {
+                let entry = entry?;
+                let name = entry.file_name();
+
+                if let Some(name) = name.to_str() {
+                    if name.starts_with(filename) {
+                        candidates.push(format!("{dirname}{name}"));
+                    }
+                }
+            }
+        }
+    }
+
+    for pack in repository.packs()? {
+        candidates.extend(pack.find_prefix(prefix));
+    }
+
+    // The same object can be both loose and packed, or stored in several object directories
+    candidates.sort();
+    candidates.dedup();
+
+    return Ok(candidates);
+}
+
+// Every loose object as `(hash, path)`, sorted by hash
+// Anything in `objects/` that doesn't look like an object (`pack/`, `info/`, temporary files) is
+// skipped
+pub fn list_loose_objects(repository: &Repository) -> Result<Vec<(String, PathBuf)>, GitError> {
+    return list_loose_objects_in(
+        &objects_dir(repository.git_dir())?,
+        repository.object_format(),
+    );
+}
+
+// Hashes of every object that can be read: loose or packed, stored by the repository itself or
+// borrowed from its alternates, sorted
+pub fn list_objects(repository: &Repository) -> Result<Vec<String>, GitError> {
+    let object_format = repository.object_format();
+    let mut objects = Vec::new();
+
+    for objects_dir in object_dirs(repository.git_dir())? {
+        let loose_objects = list_loose_objects_in(&objects_dir, object_format)?;
+        objects.extend(
+            loose_objects
+                .into_iter()
+                .map(|(object_hash, _)| object_hash),
+        );
+    }
+
+    for pack in repository.packs()? {
+        objects.extend(pack.hashes());
+    }
+
+    // The same object can be both loose and packed, or stored in several object directories
+    objects.sort();
+    objects.dedup();
+
+    return Ok(objects);
+}
+
+fn list_loose_objects_in(
+    objects_dir: &Path,
+    object_format: ObjectFormat,
+) -> Result<Vec<(String, PathBuf)>, GitError> {
+    let mut objects = Vec::new();
+
+    for_each_loose_object_in(objects_dir, object_format, &mut |object_hash, path| {
+        objects.push((object_hash, path));
+
+        return Ok(());
+    })?;
+    objects.sort();
+
+    return Ok(objects);
+}
+
+// Calls `callback` with the full hash of each loose object of the repository, in no particular
+// order, stopping at the first error it returns
+pub fn for_each_loose_object(
+    repository: &Repository,
+    mut callback: impl FnMut(&str) -> Result<(), GitError>,
+) -> Result<(), GitError> {
+    return for_each_loose_object_in(
+        &objects_dir(repository.git_dir())?,
+        repository.object_format(),
+        &mut |object_hash, _| callback(&object_hash),
+    );
+}
+
+// Only `xx/` fan-out directories are walked, so `pack/` and `info/` are skipped, and only files
+// whose name completes a full hash are objects
+fn for_each_loose_object_in(
+    objects_dir: &Path,
+    object_format: ObjectFormat,
+    callback: &mut dyn FnMut(String, PathBuf) -> Result<(), GitError>,
+) -> Result<(), GitError> {
+    let is_hex = |name: &str| name.chars().all(|c| c.is_ascii_hexdigit());
+
+    let directories = match fs::read_dir(objects_dir) {
+        Ok(directories) => directories,
+        Err(_) => return Ok(()),
+    };
+
+    for directory in directories {
+        let directory = directory?;
+        let dirname = directory.file_name();
+
+        let dirname = match dirname.to_str() {
+            Some(dirname) if dirname.len() == 2 && is_hex(dirname) => dirname.to_string(),
+            _ => continue,
+        };
+
+        for entry in fs::read_dir(directory.path())? {
+            let entry = entry?;
+            let filename = entry.file_name();
+
+            if let Some(filename) = filename.to_str() {
+                if filename.len() == object_format.hex_len() - 2 && is_hex(filename) {
+                    callback(format!("{dirname}{filename}"), entry.path())?;
+                }
+            }
+        }
+    }
+
+    return Ok(());
+}
+
+// Loose objects are decompressed as they are read, packed ones are read whole since deltas need
+// their base, either way the reader yields `<type> <content-size>\0<content>`
+pub fn open_object(
+    repository: &Repository,
+    object_hash: &str,
+) -> Result<Box<dyn BufRead>, GitError> {
+    let (dirname, filename) = match (object_hash.get(0..2), object_hash.get(2..)) {
+        (Some(dirname), Some(filename)) if !filename.is_empty() => (dirname, filename),
+        _ => return Err(GitError::ObjectNotFound(object_hash.to_string())),
+    };
+    for objects_dir in object_dirs(repository.git_dir())? {
+        if let Ok(file) = File::open(objects_dir.join(dirname).join(filename)) {
+            let decoder = ZlibDecoder::new(BufReader::new(file));
+
+            return Ok(Box::new(BufReader::new(decoder)));
+        }
+    }
+
+    match find_packed_object(repository, object_hash)? {
+        Some((object_type, content)) => {
+            let packed = pack_object(object_type, &content);
+
+            return Ok(Box::new(Cursor::new(packed)));
+        }
+        None if is_empty_tree(object_hash, repository.object_format()) => {
+            return Ok(Box::new(Cursor::new(pack_object(ObjectType::Tree, b""))));
+        }
+        None => return Err(GitError::ObjectNotFound(object_hash.to_string())),
+    }
+}
+
+// Reads a whole object and checks it hashes back to `object_hash`, so corrupt objects are
+// caught instead of silently printed
+pub fn read_verified_content(
+    repository: &Repository,
+    object_hash: &str,
+) -> Result<(ObjectType, Vec<u8>), GitError> {
+    let mut reader = open_object(repository, object_hash)?;
+    let (object_type, size) = read_object_header(&mut reader, object_hash)?;
+    let content = read_object_content(&mut reader, size)?;
+
+    let actual_hash = compute_object_hash(
+        &pack_object(object_type, &content),
+        repository.object_format(),
+    )?;
+
+    if actual_hash != object_hash {
+        return Err(GitError::Corrupt(format!(
+            "{object_hash} hashes to {actual_hash}"
+        )));
+    }
+
+    return Ok((object_type, content));
+}
+
+// Whether `object_hash` is stored, loose or packed, without reading it
+pub fn object_exists(repository: &Repository, object_hash: &str) -> Result<bool, GitError> {
+    if let (Some(dirname), Some(filename)) = (object_hash.get(0..2), object_hash.get(2..)) {
+        for objects_dir in object_dirs(repository.git_dir())? {
+            if objects_dir.join(dirname).join(filename).exists() {
+                return Ok(true);
+            }
+        }
+    }
+
+    for pack in repository.packs()? {
+        if pack.find(object_hash).is_some() {
+            return Ok(true);
+        }
+    }
+
+    return Ok(is_empty_tree(object_hash, repository.object_format()));
+}
+
+// Like git, the empty tree is known without being stored, so that it can always be read
+fn is_empty_tree(object_hash: &str, object_format: ObjectFormat) -> bool {
+    return object_hash == object_format.digest(&pack_object(ObjectType::Tree, b""));
+}
+
+// Checks that an object hashes back to its name, parses according to its type, and that
+// everything it references (tree entries, commit tree and parents) is stored
+// Submodules point to commits of another repository, so they are not followed
+pub fn check_object(repository: &Repository, object_hash: &str) -> Result<(), GitError> {
+    let (object_type, content) = read_verified_content(repository, object_hash)?;
+    let object = read_object_body(
+        &mut content.as_slice(),
+        object_type,
+        content.len(),
+        repository.object_format(),
+    )?;
+
+    let references = match object {
+        GitObject::Blob(_) => Vec::new(),
+        GitObject::Tree(tree) => tree
+            .entries
+            .into_iter()
+            .filter(|entry| entry.mode != 0o160000)
+            .map(|entry| entry.sha)
+            .collect(),
+        GitObject::Commit(commit) => [vec![commit.tree_hash], commit.parents].concat(),
+        GitObject::Tag(tag) => vec![tag.object_hash],
+    };
+
+    for reference in references.iter() {
+        if !object_exists(repository, reference)? {
+            return Err(GitError::Corrupt(format!(
+                "{object_hash} references missing object {reference}"
+            )));
+        }
+    }
+
+    return Ok(());
+}
+
+// Every object reachable from `roots`, following commits to their tree and parents, and trees to
+// their entries
+// A missing object is an error rather than a dead end, so callers never act on a partial walk
+pub fn reachable_objects(
+    repository: &Repository,
+    roots: &[String],
+) -> Result<HashSet<String>, GitError> {
+    let object_format = repository.object_format();
+    let mut reachable = HashSet::new();
+    let mut pending = roots.to_vec();
+
+    while let Some(object_hash) = pending.pop() {
+        if !reachable.insert(object_hash.clone()) {
+            continue;
+        }
+
+        let mut reader = open_object(repository, &object_hash)?;
+
+        match read_git_object(&mut reader, &object_hash, object_format)? {
+            GitObject::Blob(_) => {}
+            GitObject::Tree(tree) => {
+                for entry in tree.entries {
+                    match entry.mode {
+                        // Submodule commits live in another repository
+                        0o160000 => {}
+                        // Blobs have nothing to follow, no need to read them
+                        0o40000 => pending.push(entry.sha),
+                        _ => {
+                            if !object_exists(repository, &entry.sha)? {
+                                return Err(GitError::ObjectNotFound(entry.sha));
+                            }
+                            reachable.insert(entry.sha);
+                        }
+                    }
+                }
+            }
+            GitObject::Commit(commit) => {
+                pending.push(commit.tree_hash);
+                pending.extend(commit.parents);
+            }
+            GitObject::Tag(tag) => pending.push(tag.object_hash),
+        }
+    }
+
+    return Ok(reachable);
+}
+
+// Loose objects that nothing references: not reachable from a ref, `HEAD` or the index
+// Objects modified less than `expire` ago are kept, another command may be about to reference them
+pub fn prunable_objects(
+    repository: &Repository,
+    expire: Duration,
+) -> Result<Vec<(String, PathBuf)>, GitError> {
+    let mut named_roots = list_refs(repository)?;
+
+    // Tags peel to objects reachable through them anyway, they are kept all the same
+    for packed_ref in read_packed_refs(repository.git_dir())? {
+        if let Some(peeled) = packed_ref.peeled {
+            named_roots.push((format!("{}^{{}}", packed_ref.refname), peeled));
+        }
+    }
+
+    // An unborn `HEAD` has nothing to keep, but one that can't be read must not be taken for it
+    if let Some(head) = read_ref(repository.git_dir(), "HEAD")? {
+        named_roots.push(("HEAD".to_string(), head));
+    }
+
+    let mut roots = Vec::new();
+    for (refname, hash) in named_roots {
+        if !repository.object_format().is_valid_hash(&hash) {
+            return Err(GitError::BrokenRef(refname));
+        }
+        roots.push(hash);
+    }
+
+    roots.extend(
+        read_index(repository)?
+            .entries
+            .into_iter()
+            .filter(|entry| entry.mode != 0o160000)
+            .map(|entry| entry.sha),
+    );
+
+    let reachable = reachable_objects(repository, &roots)?;
+    let now = SystemTime::now();
+    let mut prunable = Vec::new();
+
+    for (object_hash, path) in list_loose_objects(repository)? {
+        if reachable.contains(&object_hash) {
+            continue;
+        }
+
+        let modified = fs::metadata(&path)?.modified()?;
+        let age = now.duration_since(modified).unwrap_or(Duration::ZERO);
+
+        if age >= expire {
+            prunable.push((object_hash, path));
+        }
+    }
+
+    return Ok(prunable);
+}
+
+// Like `resolve_object_hash`, also checking the object is of the expected type
+pub fn resolve_object_hash_of_type(
+    repository: &Repository,
+    prefix: &str,
+    expected_type: ObjectType,
+) -> Result<String, GitError> {
+    let object_hash = resolve_object_hash(repository, prefix)?;
+
+    let mut reader = open_object(repository, &object_hash)?;
+    let (object_type, _) = read_object_header(&mut reader, &object_hash)?;
+
+    if object_type != expected_type {
+        return Err(GitError::TypeMismatch {
+            object_hash,
+            actual: object_type.as_str(),
+            expected: expected_type.as_str(),
+        });
+    }
+
+    return Ok(object_hash);
+}
+
+pub fn read_tree(repository: &Repository, object_hash: &str) -> Result<TreeObject, GitError> {
+    let mut reader = open_object(repository, object_hash)?;
+
+    let object_type = match read_git_object(&mut reader, object_hash, repository.object_format())? {
+        GitObject::Tree(tree) => return Ok(tree),
+        GitObject::Blob(_) => ObjectType::Blob,
+        GitObject::Commit(_) => ObjectType::Commit,
+        GitObject::Tag(_) => ObjectType::Tag,
+    };
+
+    return Err(GitError::TypeMismatch {
+        object_hash: object_hash.to_string(),
+        actual: object_type.as_str(),
+        expected: ObjectType::Tree.as_str(),
+    });
+}
+
+// The content of blobs goes through `filters` first
+pub fn hash_object(
+    filename: PathBuf,
+    object_type: ObjectType,
+    git_dir: Option<&Path>,
+    object_format: ObjectFormat,
+    filters: Filters,
+) -> Result<String, GitError> {
+    match File::open(&filename) {
+        Ok(mut input_file) => {
+            // Filters change the size of the content, so it has to be read whole, it is then
+            // streamed from memory rather than copied once more by `hash_content`
+            if object_type == ObjectType::Blob && !filters.is_noop() {
+                let mut content = Vec::new();
+                input_file.read_to_end(&mut content)?;
+                let content = filters.clean(content);
+                let size = content.len() as u64;
+
+                return hash_stream(
+                    content.as_slice(),
+                    size,
+                    object_type,
+                    git_dir,
+                    object_format,
+                );
+            }
+
+            // Files can be arbitrarily large, their size is known up front so they can be streamed
+            let size = input_file.metadata()?.len();
+            let reader = BufReader::new(input_file);
+
+            return hash_stream(reader, size, object_type, git_dir, object_format);
+        }
+        Err(_) => {
+            return Err(GitError::FileNotFound(filename));
+        }
+    }
+}
+
+static TEMP_OBJECT_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+// File an object is written to before being renamed to its hash, removed when dropped unless it
+// was moved into place, so that a failed write leaves nothing behind
+struct TempObject {
+    path: PathBuf,
+    is_in_place: bool,
+}
+
+impl TempObject {
+    // Unique within the process too, objects may be written from several threads at once
+    fn new(directory: &Path) -> TempObject {
+        let path = directory.join(format!(
+            "tmp_obj_{}_{}",
+            std::process::id(),
+            TEMP_OBJECT_COUNTER.fetch_add(1, AtomicOrdering::Relaxed)
+        ));
+
+        return TempObject {
+            path,
+            is_in_place: false,
+        };
+    }
+
+    // Objects are immutable, like git they are made read-only before getting their final name
+    fn move_into_place(mut self: TempObject, path: &Path) -> Result<(), GitError> {
+        fs::set_permissions(&self.path, fs::Permissions::from_mode(0o444))?;
+        fs::rename(&self.path, path)?;
+        self.is_in_place = true;
+
+        return Ok(());
+    }
+}
+
+impl Drop for TempObject {
+    fn drop(&mut self) {
+        if !self.is_in_place {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+// Compresses a loose object into `output`, buffered so that however the content is fed to the
+// compressor `output` only sees large writes
+// What `finish` returns must still be flushed
+pub fn object_encoder<W: Write>(output: W) -> ZlibEncoder<BufWriter<W>> {
+    return ZlibEncoder::new(BufWriter::new(output), Compression::best());
+}
+
+// Like `hash_content`, for `size` bytes fed through the hasher (and the compressor when storing)
+// chunk by chunk, without ever holding the whole content in memory
+pub fn hash_stream(
+    mut reader: impl Read,
+    size: u64,
+    object_type: ObjectType,
+    git_dir: Option<&Path>,
+    object_format: ObjectFormat,
+) -> Result<String, GitError> {
+    let header = format!("{} {size}\0", object_type.as_str());
+
+    let mut hasher = object_format.hasher();
+    hasher.write_all(header.as_bytes())?;
+
+    // The object's name is only known once everything has been hashed, so it is compressed into
+    // a temporary file first
+    let mut output = match git_dir {
+        Some(git_dir) => {
+            let objects_dir = objects_dir(git_dir)?;
+            fs::create_dir_all(&objects_dir)?;
+
+            let temp_object = TempObject::new(&objects_dir);
+            let mut encoder = object_encoder(File::create(&temp_object.path)?);
+            encoder.write_all(header.as_bytes())?;
+
+            // After the encoder, so that the file is closed by the time it is dropped
+            Some((encoder, temp_object, objects_dir))
+        }
+        None => None,
+    };
+
+    let mut buf = vec![0u8; 64 * 1024];
+    let mut total: u64 = 0;
+
+    loop {
+        let n = reader.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+
+        hasher.write_all(&buf[..n])?;
+        if let Some((encoder, _, _)) = output.as_mut() {
+            encoder.write_all(&buf[..n])?;
+        }
+        total += n as u64;
+    }
+
+    // The file changed while it was being read, the header is wrong
+    if total != size {
+        return Err(GitError::Corrupt(format!(
+            "expected {size} bytes, got {total} bytes"
+        )));
+    }
+
+    let hash = hex::encode(hasher.finalize());
+
+    if let Some((encoder, temp_object, objects_dir)) = output {
+        encoder.finish()?.flush()?;
+
+        // Already stored, the temporary file is dropped
+        let path = objects_dir.join(&hash[0..2]).join(&hash[2..]);
+
+        if !path.exists() {
+            fs::create_dir_all(objects_dir.join(&hash[0..2]))?;
+            temp_object.move_into_place(&path)?;
+        }
+    }
+
+    return Ok(hash);
+}
+
+// Hashes everything `reader` yields as an object of type `object_type`, only storing it when
+// given a `git_dir` to write to
+pub fn hash_content(
+    mut reader: impl Read,
+    object_type: ObjectType,
+    git_dir: Option<&Path>,
+    object_format: ObjectFormat,
+) -> Result<String, GitError> {
+    let mut content = Vec::new();
+    reader.read_to_end(&mut content)?;
+
+    let packed_object = pack_object(object_type, &content);
+    let object_hash = match git_dir {
+        Some(git_dir) => write_object_file(git_dir, packed_object, object_format)?,
+        None => compute_object_hash(&packed_object, object_format)?,
+    };
+
+    return Ok(object_hash);
+}
+
+// Paths matched by `.gitignore` files (and `.git/info/exclude`) are left out
+// With a `prefix`, only the subtree of that directory of `path` is written, the `.gitignore` files
+// of its parents still apply
+pub fn write_tree(
+    repository: &Repository,
+    path: PathBuf,
+    prefix: &str,
+) -> Result<String, GitError> {
+    let mut walk = TreeWalk {
+        repository,
+        filters: Filters::for_repository(repository.git_dir())?,
+        rules: IgnoreRules::for_repository(repository.git_dir())?,
+        attributes: Attributes::for_repository(repository.git_dir())?,
+    };
+
+    let mut directory = path;
+    let mut relative_path = String::new();
+    let prefix = prefix.trim_end_matches('/');
+
+    if !prefix.is_empty() {
+        validate_path(prefix)?;
+
+        for name in prefix.split('/') {
+            walk = walk.for_directory(&directory, &relative_path)?;
+            directory = directory.join(name);
+            relative_path = if relative_path.is_empty() {
+                name.to_string()
+            } else {
+                format!("{relative_path}/{name}")
+            };
+        }
+
+        if !directory.is_dir() {
+            return Err(GitError::PrefixNotFound(prefix.to_string()));
+        }
+    }
+
+    let mut written_trees = HashSet::new();
+
+    match write_directory_tree(&walk, directory, &relative_path, &mut written_trees)? {
+        Some(tree_hash) => return Ok(tree_hash),
+        // Nothing to track at all, which is still a valid (empty) tree
+        None => {
+            let tree = TreeObject {
+                entries: Vec::new(),
+            };
+
+            return write_object_file(
+                repository.git_dir(),
+                tree.pack(),
+                repository.object_format(),
+            );
+        }
+    }
+}
+
+// What applies to a directory of the working tree being written: the ignore rules and attributes
+// of it and its parents, and the filters of the repository
+struct TreeWalk<'a> {
+    repository: &'a Repository,
+    filters: Filters,
+    rules: IgnoreRules,
+    attributes: Attributes,
+}
+
+impl<'a> TreeWalk<'a> {
+    // Stacks the `.gitignore` and `.gitattributes` of `path` on top of those of its parents
+    fn for_directory(
+        self: &TreeWalk<'a>,
+        path: &Path,
+        relative_path: &str,
+    ) -> Result<TreeWalk<'a>, GitError> {
+        return Ok(TreeWalk {
+            repository: self.repository,
+            filters: self.filters,
+            rules: self.rules.for_directory(path, relative_path)?,
+            attributes: self.attributes.for_directory(path, relative_path)?,
+        });
+    }
+}
+
+// `relative_path` is the path of `path` from the root of the tree being written
+// `None` when there is nothing to track in `path`, git only records files, not directories
+// `written_trees` holds the hashes of the trees stored so far, identical directories are only
+// stored once
+fn write_directory_tree(
+    walk: &TreeWalk,
+    path: PathBuf,
+    relative_path: &str,
+    written_trees: &mut HashSet<String>,
+) -> Result<Option<String>, GitError> {
+    let walk = walk.for_directory(&path, relative_path)?;
+    let git_dir = walk.repository.git_dir();
+    let object_format = walk.repository.object_format();
+    let directory = fs::read_dir(path)?;
+    let mut entries: Vec<TreeEntry> = Vec::new();
+    // Files are hashed all at once after the walk, see `hash_files`
+    let mut files: Vec<(PathBuf, Filters)> = Vec::new();
+    let mut file_entries: Vec<TreeEntry> = Vec::new();
+
+    for entry in directory {
+        if let Ok(entry) = entry {
+            let file_name = entry.file_name();
+            let file_type = entry.file_type()?;
+
+            // Ignore patterns are text, a name that isn't UTF-8 is matched approximately
+            let file_name_lossy = file_name.to_string_lossy();
+            let entry_path = if relative_path.is_empty() {
+                file_name_lossy.to_string()
+            } else {
+                format!("{relative_path}/{file_name_lossy}")
+            };
+
+            if walk.rules.is_ignored(&entry_path, file_type.is_dir()) {
+                continue;
+            }
+
+            let sha = if file_type.is_file() {
+                // Filled in once every file of the directory is hashed
+                String::new()
+            } else if file_type.is_dir() {
+                if file_name == ".git" {
+                    continue;
+                }
+                match write_directory_tree(&walk, entry.path(), &entry_path, written_trees)? {
+                    Some(sha) => sha,
+                    None => continue,
+                }
+            } else {
+                return Err(GitError::UnsupportedFileType(entry.path()));
+            };
+
+            // Took this small snippet to compute mode from johnoo's implementation.
+            // I'm not entirely sure i understand git object mode.
+            // Seems like mode is a mix of
+            // - file type (first 3 digits)
+            // - unix permissions (last 3 digits)
+            //
+            // 040 -> dir
+            // 120 -> symlink
+            // 100 -> normal file
+            // 160 -> submodule (not covered here)
+            let mode = if file_type.is_dir() {
+                0o40000
+            } else if file_type.is_symlink() {
+                0o120000
+            } else if (entry.metadata()?.mode() & 0o111) != 0 {
+                // has at least one executable bit set
+                0o100755
+            } else {
+                0o100644
+            };
+
+            let tree_entry = TreeEntry {
+                mode,
+                name: file_name.as_bytes().to_vec(),
+                sha,
+            };
+
+            if file_type.is_file() {
+                files.push((
+                    entry.path(),
+                    walk.filters.for_path(&walk.attributes, &entry_path),
+                ));
+                file_entries.push(tree_entry);
+            } else {
+                entries.push(tree_entry);
+            }
+        }
+    }
+
+    let hashes = hash_files(git_dir, &files, object_format)?;
+
+    for (mut tree_entry, sha) in file_entries.into_iter().zip(hashes) {
+        tree_entry.sha = sha;
+        entries.push(tree_entry);
+    }
+
+    if entries.is_empty() {
+        return Ok(None);
+    }
+
+    let tree = TreeObject { entries };
+    let packed_tree = tree.pack();
+    let tree_hash = compute_object_hash(&packed_tree, object_format)?;
+
+    if written_trees.insert(tree_hash.clone()) {
+        write_object_file(git_dir, packed_tree, object_format)?;
+    }
+
+    return Ok(Some(tree_hash));
+}
+
+// Stores the blobs of `files`, each through its own filters, returning their hashes in the same
+// order
+// Hashing dominates the time it takes to write a tree, large directories are split in chunks
+// hashed on their own thread
+fn hash_files(
+    git_dir: &Path,
+    files: &[(PathBuf, Filters)],
+    object_format: ObjectFormat,
+) -> Result<Vec<String>, GitError> {
+    // Below this, spawning a thread costs more than it saves
+    const MIN_FILES_PER_THREAD: usize = 16;
+
+    let hash_chunk = |chunk: &[(PathBuf, Filters)]| {
+        return chunk
+            .iter()
+            .map(|(path, filters)| {
+                hash_object(
+                    path.clone(),
+                    ObjectType::Blob,
+                    Some(git_dir),
+                    object_format,
+                    *filters,
+                )
+            })
+            .collect::<Result<Vec<String>, GitError>>();
+    };
+
+    let threads = thread::available_parallelism()
+        .map_or(1, NonZeroUsize::get)
+        .min(files.len().div_ceil(MIN_FILES_PER_THREAD));
+
+    if threads <= 1 {
+        return hash_chunk(files);
+    }
+
+    return thread::scope(|scope| {
+        let handles: Vec<_> = files
+            .chunks(files.len().div_ceil(threads))
+            .map(|chunk| scope.spawn(move || hash_chunk(chunk)))
+            .collect();
+
+        let mut hashes = Vec::with_capacity(files.len());
+
+        for handle in handles {
+            hashes.extend(handle.join().expect("hashing thread panicked")?);
+        }
+
+        return Ok(hashes);
+    });
+}
+
+// `extensions.objectformat`, repositories without it use sha1
+pub fn read_object_format(git_dir: &Path) -> Result<ObjectFormat, GitError> {
+    match Config::read(git_dir)?.get("extensions", None, "objectformat") {
+        Some(object_format) => return ObjectFormat::from_str(&object_format.to_lowercase()),
+        None => return Ok(ObjectFormat::default()),
+    }
+}
+
+// `role` is either `AUTHOR` or `COMMITTER`
+// `GIT_<role>_NAME` and `GIT_<role>_EMAIL` take precedence over `user.name` and `user.email`
+pub fn read_identity(git_dir: &Path, role: &str) -> Result<(String, String), GitError> {
+    let config = Config::read(git_dir)?;
+
+    let name = env::var(format!("GIT_{role}_NAME"))
+        .ok()
+        .or_else(|| config.get("user", None, "name").map(str::to_string));
+    let email = env::var(format!("GIT_{role}_EMAIL"))
+        .ok()
+        .or_else(|| config.get("user", None, "email").map(str::to_string));
+
+    match (name, email) {
+        (Some(name), Some(email)) => return Ok((name, email)),
+        _ => return Err(GitError::UnknownIdentity(role.to_string())),
+    }
+}
+
+// `GIT_<role>_DATE`, in git's internal `@<seconds> <timezone>` format
+pub fn read_date(role: &str) -> Result<Option<(SystemTime, String)>, GitError> {
+    let date = match env::var(format!("GIT_{role}_DATE")) {
+        Ok(date) => date,
+        Err(_) => return Ok(None),
+    };
+
+    let invalid_date = || GitError::InvalidDate {
+        variable: format!("GIT_{role}_DATE"),
+        value: date.clone(),
+    };
+
+    let (seconds, timezone) = date
+        .strip_prefix('@')
+        .and_then(|date| date.split_once(' '))
+        .ok_or_else(invalid_date)?;
+
+    let seconds: u64 = seconds.parse().map_err(|_| invalid_date())?;
+    validate_timezone(timezone)?;
+
+    return Ok(Some((
+        UNIX_EPOCH + Duration::from_secs(seconds),
+        timezone.to_string(),
+    )));
+}
+
+// Symbolic refs are chained at most this many times, protects against cycles
+const MAX_SYMBOLIC_REF_DEPTH: usize = 5;
+
+pub fn validate_refname(refname: &str) -> Result<(), GitError> {
+    let is_valid = !refname.is_empty()
+        && !refname.starts_with('/')
+        && !refname.contains("..")
+        && !refname.contains(char::is_whitespace);
+
+    if !is_valid {
+        return Err(GitError::InvalidRefName(refname.to_string()));
+    }
+
+    return Ok(());
+}
+
+// A ref as stored in `packed-refs` by `git pack-refs`, packed refs are never symbolic
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PackedRef {
+    pub refname: String,
+    pub hash: String,
+    // What an annotated tag points to, from the `^<sha>` line following it
+    pub peeled: Option<String>,
+}
+
+// `# pack-refs with: <traits>` then `<sha> <refname>` lines, sorted by refname, each possibly
+// followed by a `^<sha>` line. Empty if refs were never packed
+pub fn read_packed_refs(git_dir: &Path) -> Result<Vec<PackedRef>, GitError> {
+    let content = match fs::read_to_string(common_dir(git_dir)?.join("packed-refs")) {
+        Ok(content) => content,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(error) => return Err(error.into()),
+    };
+
+    let mut packed_refs: Vec<PackedRef> = Vec::new();
+
+    for line in content.lines() {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if let Some(peeled) = line.strip_prefix('^') {
+            match packed_refs.last_mut() {
+                Some(packed_ref) if packed_ref.peeled.is_none() => {
+                    packed_ref.peeled = Some(peeled.to_string());
+                }
+                _ => return Err(GitError::InvalidPackedRef(line.to_string())),
+            }
+            continue;
+        }
+
+        match line.split_once(' ') {
+            Some((hash, refname)) => packed_refs.push(PackedRef {
+                refname: refname.to_string(),
+                hash: hash.to_string(),
+                peeled: None,
+            }),
+            None => return Err(GitError::InvalidPackedRef(line.to_string())),
+        }
+    }
+
+    return Ok(packed_refs);
+}
+
+// Content of the loose ref file at `path`, trimmed, `None` if there is none
+// A directory is a namespace of refs (`refs/heads`), not a ref
+fn read_loose_ref(path: &Path) -> Result<Option<String>, GitError> {
+    if !path.is_file() {
+        return Ok(None);
+    }
+
+    return Ok(Some(fs::read_to_string(path)?.trim_end().to_string()));
+}
+
+// Hash `refname` itself holds, without following symbolic refs: a loose ref shadows the packed
+// one of the same name
+fn read_direct_ref(git_dir: &Path, refname: &str) -> Result<Option<String>, GitError> {
+    if let Some(content) = read_loose_ref(&ref_path(git_dir, refname)?)? {
+        return Ok(Some(content));
+    }
+
+    let packed_ref = read_packed_refs(git_dir)?
+        .into_iter()
+        .find(|packed_ref| packed_ref.refname == refname);
+
+    return Ok(packed_ref.map(|packed_ref| packed_ref.hash));
+}
+
+// Follows `ref: <target>` indirections, returning the name of the ref actually holding a hash
+// (which may not exist yet)
+pub fn resolve_symbolic_ref(git_dir: &Path, refname: &str) -> Result<String, GitError> {
+    let mut refname = refname.to_string();
+
+    for _ in 0..MAX_SYMBOLIC_REF_DEPTH {
+        // Packed refs can't be symbolic, no need to look at them
+        let content = match read_loose_ref(&ref_path(git_dir, &refname)?)? {
+            Some(content) => content,
+            None => return Ok(refname),
+        };
+
+        match content.strip_prefix("ref: ") {
+            Some(target) => refname = target.to_string(),
+            None => return Ok(refname),
+        }
+    }
+
+    return Err(GitError::SymbolicRefTooDeep(refname));
+}
+
+// Hash a ref points to, following symbolic refs. `None` if the ref (or its target) doesn't exist
+pub fn read_ref(git_dir: &Path, refname: &str) -> Result<Option<String>, GitError> {
+    let refname = resolve_symbolic_ref(git_dir, refname)?;
+
+    return read_direct_ref(git_dir, &refname);
+}
+
+// Resolves a ref name (`HEAD`, `main`, `refs/heads/main`, ...) or an object name to a full hash,
+// trying refs in the same order git does
+pub fn resolve_revision(
+    repository: &Repository,
+    revision: &str,
+) -> Result<Option<String>, GitError> {
+    if validate_refname(revision).is_ok() {
+        let candidates = [
+            revision.to_string(),
+            format!("refs/{revision}"),
+            format!("refs/tags/{revision}"),
+            format!("refs/heads/{revision}"),
+            format!("refs/remotes/{revision}"),
+            format!("refs/remotes/{revision}/HEAD"),
+        ];
+
+        // Only pseudo refs (`HEAD`, `ORIG_HEAD`, ...) live at the top of the git directory,
+        // anything else there (`config`, `index`, ...) is not a ref
+        let is_pseudo_ref = revision.chars().all(|c| c.is_ascii_uppercase() || c == '_');
+
+        for candidate in candidates.iter() {
+            if candidate == revision && !is_pseudo_ref && !revision.starts_with("refs/") {
+                continue;
+            }
+
+            if let Some(hash) = read_ref(repository.git_dir(), candidate)? {
+                return Ok(Some(hash));
+            }
+        }
+    }
+
+    // Only a name that can't be an object is unknown, an ambiguous one must be reported as such
+    match resolve_object_hash(repository, revision) {
+        Ok(object_hash) => return Ok(Some(object_hash)),
+        Err(
+            GitError::ObjectNotFound(_)
+            | GitError::InvalidObjectName(_)
+            | GitError::ObjectNameTooShort(_),
+        ) => return Ok(None),
+        Err(error) => return Err(error),
+    }
+}
+
+fn collect_refs(
+    directory: &Path,
+    refname: &str,
+    refs: &mut Vec<(String, String)>,
+    object_format: ObjectFormat,
+) -> Result<(), GitError> {
+    for entry in fs::read_dir(directory)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let name = name
+            .to_str()
+            .ok_or_else(|| GitError::InvalidRefName(name.to_string_lossy().to_string()))?;
+        let refname = format!("{refname}/{name}");
+
+        if entry.file_type()?.is_dir() {
+            collect_refs(&entry.path(), &refname, refs, object_format)?;
+            continue;
+        }
+
+        // Symbolic refs don't hold a hash themselves
+        let content = fs::read_to_string(entry.path())?;
+        let hash = content.trim_end();
+
+        if hash.starts_with("ref: ") {
+            continue;
+        }
+        if !object_format.is_valid_hash(hash) {
+            return Err(GitError::BrokenRef(refname));
+        }
+        refs.push((refname, hash.to_string()));
+    }
+
+    return Ok(());
+}
+
+// Every ref under `refs/` as `(refname, hash)`, loose or packed, sorted by refname
+pub fn list_refs(repository: &Repository) -> Result<Vec<(String, String)>, GitError> {
+    let mut refs = Vec::new();
+
+    let refs_dir = common_dir(repository.git_dir())?.join("refs");
+
+    if refs_dir.is_dir() {
+        collect_refs(&refs_dir, "refs", &mut refs, repository.object_format())?;
+    }
+
+    // A loose ref is more recent than the packed one of the same name
+    let loose_refnames: HashSet<String> = refs.iter().map(|(refname, _)| refname.clone()).collect();
+    for packed_ref in read_packed_refs(repository.git_dir())? {
+        if !loose_refnames.contains(&packed_ref.refname) {
+            refs.push((packed_ref.refname, packed_ref.hash));
+        }
+    }
+    refs.sort();
+
+    return Ok(refs);
+}
+
+// `ref: <target>`
+pub fn read_symbolic_ref(git_dir: &Path, name: &str) -> Result<String, GitError> {
+    let content =
+        read_direct_ref(git_dir, name)?.ok_or_else(|| GitError::RefNotFound(name.to_string()))?;
+
+    match content.strip_prefix("ref: ") {
+        Some(target) => return Ok(target.to_string()),
+        None => return Err(GitError::NotASymbolicRef(name.to_string())),
+    }
+}
+
+pub fn write_symbolic_ref(git_dir: &Path, name: &str, target: &str) -> Result<(), GitError> {
+    validate_refname(name)?;
+    validate_refname(target)?;
+    if !target.starts_with("refs/") {
+        return Err(GitError::SymbolicRefOutsideRefs {
+            name: name.to_string(),
+            target: target.to_string(),
+        });
+    }
+
+    let path = ref_path(git_dir, name)?;
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&path, format!("ref: {target}\n"))?;
+
+    return Ok(());
+}
+
+pub fn update_ref(
+    repository: &Repository,
+    refname: &str,
+    new_value: &str,
+    old_value: Option<&str>,
+) -> Result<(), GitError> {
+    validate_refname(refname)?;
+
+    let new_value = resolve_object_hash(repository, new_value)?;
+    let refname = resolve_symbolic_ref(repository.git_dir(), refname)?;
+    let path = ref_path(repository.git_dir(), &refname)?;
+
+    if let Some(old_value) = old_value {
+        let current_value = read_direct_ref(repository.git_dir(), &refname)?;
+
+        // An empty or null old value means the ref must not exist yet
+        let is_null = old_value.trim_start_matches('0').is_empty();
+        let is_expected = match &current_value {
+            Some(current_value) => {
+                !is_null && resolve_object_hash(repository, old_value)? == *current_value
+            }
+            None => is_null,
+        };
+
+        if !is_expected {
+            return Err(GitError::RefConflict {
+                refname,
+                current: current_value.unwrap_or_else(|| "nothing".to_string()),
+                expected: old_value.to_string(),
+            });
+        }
+    }
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&path, format!("{new_value}\n"))?;
+
+    return Ok(());
+}
+
+pub fn compute_object_hash(packed: &[u8], object_format: ObjectFormat) -> Result<String, GitError> {
+    return Ok(object_format.digest(packed));
+}
+
+pub fn write_object_file(
+    git_dir: &Path,
+    packed: Vec<u8>,
+    object_format: ObjectFormat,
+) -> Result<String, GitError> {
+    let hash = compute_object_hash(&packed, object_format)?;
+
+    let dirname = &hash[0..2];
+    let filename = &hash[2..];
+
+    let objects_dir = objects_dir(git_dir)?;
+    let path = objects_dir.join(dirname).join(filename);
+
+    // Objects are content-addressed and immutable, nothing to do if it is already stored
+    if path.exists() {
+        return Ok(hash);
+    }
+
+    fs::create_dir_all(objects_dir.join(dirname))?;
+
+    // Only renamed once complete, an interrupted write must not leave a truncated object under
+    // a valid name
+    let temp_object = TempObject::new(&objects_dir.join(dirname));
+    let mut encoder = object_encoder(File::create(&temp_object.path)?);
+    encoder.write_all(&packed)?;
+    encoder.finish()?.flush()?;
+
+    temp_object.move_into_place(&path)?;
+
+    return Ok(hash);
+}
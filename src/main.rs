@@ -1,551 +1,1380 @@
-#[allow(unused_imports)]
-use std::env;
 use std::{
-    cmp::Ordering,
-    fs::File,
-    io::{BufRead, BufReader, Read, Write},
+    env, fs,
+    io::{BufRead, Read, Write},
     os::unix::fs::MetadataExt,
-    path::PathBuf,
+    path::{Path, PathBuf},
+    process::ExitCode,
     str::FromStr,
-    time::{SystemTime, UNIX_EPOCH},
+    time::{Duration, SystemTime},
 };
 
-use anyhow::{anyhow, Context};
-use sha1::{Digest, Sha1};
-
+use anyhow::Context;
 use clap::{Parser, Subcommand};
-use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+
+use git_starter_rust::{
+    build_pack, current_prefix, find_work_tree, for_each_loose_object, format_date,
+    format_timezone, hash_content, hash_object, list_loose_objects, list_objects,
+    local_timezone_offset, objects_dir, parse_pack, parse_tree_line, path_from_root, quote_path,
+    read_date, read_object_body, read_object_header, read_tag, validate_path, write_pack_index,
+    CommitObject, Filters, GitError, GitObject, ObjectFormat, ObjectType, PackIndex, Repository,
+    TreeEntry, TreeObject,
+};
 
 #[derive(Parser, Debug)]
 #[command(version, about)]
 struct Args {
+    // Run as if started in this directory, each one relative to the previous
+    #[clap(short = 'C')]
+    directories: Vec<PathBuf>,
+
+    // Use this git directory instead of looking for one, takes precedence over `GIT_DIR`
+    #[clap(long = "git-dir")]
+    git_dir: Option<PathBuf>,
+
     #[command(subcommand)]
     command: Command,
 }
 
 #[derive(Subcommand, Debug)]
 enum Command {
-    Init,
+    Init {
+        // Lay the repository out in the current directory instead of `.git`
+        #[clap(long)]
+        bare: bool,
+
+        #[clap(short = 'b', long = "initial-branch", default_value = "main")]
+        initial_branch: String,
+
+        // Hash function naming objects, `sha1` or `sha256`
+        #[clap(long = "object-format", default_value = "sha1")]
+        object_format: String,
+    },
     CatFile {
         #[clap(short = 'p')]
         pretty_print: bool,
 
-        object_hash: String,
+        #[clap(short = 't', conflicts_with = "pretty_print")]
+        show_type: bool,
+
+        #[clap(short = 's', conflicts_with_all = ["pretty_print", "show_type"])]
+        show_size: bool,
+
+        #[clap(short = 'e', conflicts_with_all = ["pretty_print", "show_type", "show_size"])]
+        exists: bool,
+
+        // Print the header and content of every object named on stdin, one per line
+        #[clap(long, conflicts_with_all = ["pretty_print", "show_type", "show_size", "exists"])]
+        batch: bool,
+
+        // Like `--batch`, without the content
+        #[clap(
+            long = "batch-check",
+            conflicts_with_all = ["pretty_print", "show_type", "show_size", "exists", "batch"]
+        )]
+        batch_check: bool,
+
+        // With `--batch` or `--batch-check`, every stored object instead of the names on stdin
+        #[clap(long = "batch-all-objects")]
+        batch_all_objects: bool,
+
+        #[clap(
+            required_unless_present_any = ["batch", "batch_check"],
+            conflicts_with_all = ["batch", "batch_check"]
+        )]
+        object_hash: Option<String>,
     },
     HashObject {
         #[clap(short = 'w')]
         write: bool,
-        filename: String,
+
+        #[clap(short = 't', default_value = "blob")]
+        object_type: String,
+
+        #[clap(long, conflicts_with = "filenames")]
+        stdin: bool,
+
+        // One path per line
+        #[clap(long, conflicts_with_all = ["filenames", "stdin"])]
+        stdin_paths: bool,
+
+        // Hash files as they are, ignoring `core.autocrlf`
+        #[clap(long = "no-filters")]
+        no_filters: bool,
+
+        // Filter content read from stdin as if it was the file at this path
+        #[clap(long, conflicts_with = "no_filters")]
+        path: Option<String>,
+
+        // `-` reads from stdin
+        #[clap(required_unless_present_any = ["stdin", "stdin_paths"])]
+        filenames: Vec<String>,
     },
     LsTree {
         #[clap(long)]
         name_only: bool,
+
+        #[clap(short = 'r')]
+        recursive: bool,
+
+        #[clap(short = 'l', long = "long")]
+        long: bool,
+
+        #[clap(short = 'z')]
+        null_terminated: bool,
+
+        // Print hashes abbreviated to at least this many characters, 7 without a value
+        #[clap(long, num_args = 0..=1, require_equals = true, default_missing_value = "7")]
+        abbrev: Option<usize>,
+
+        // Only list trees, with `-r` every one of them rather than their content
+        #[clap(short = 'd')]
+        trees_only: bool,
+
+        // Print paths from the root of the repository rather than from the current directory
+        #[clap(long = "full-name")]
+        full_name: bool,
+
+        // List the whole tree, whatever the current directory is, implies `--full-name`
+        #[clap(long = "full-tree")]
+        full_tree: bool,
+
         object_hash: String,
     },
-    WriteTree,
+    WriteTree {
+        // Store every file of the working directory instead of what is staged
+        #[clap(long)]
+        working_tree: bool,
+
+        // Write the tree of this directory instead of the whole tree
+        #[clap(long, default_value = "")]
+        prefix: String,
+    },
+    DiffTree {
+        // Compare the files inside differing subtrees instead of the subtrees themselves
+        #[clap(short = 'r')]
+        recursive: bool,
+
+        old_tree: String,
+
+        new_tree: String,
+    },
+    Mktree {
+        // Allow entries pointing to objects that aren't stored
+        #[clap(long)]
+        missing: bool,
+    },
+    // Reads a tag from stdin
+    Mktag,
+    IndexPack {
+        // Defaults to the pack file name with an `.idx` extension
+        #[clap(short = 'o')]
+        index_file: Option<String>,
+
+        pack_file: String,
+    },
+    PackObjects {
+        // Write the pack to stdout instead of `<base-name>-<checksum>.pack` (and `.idx`)
+        #[clap(long)]
+        stdout: bool,
+
+        #[clap(required_unless_present = "stdout", conflicts_with = "stdout")]
+        base_name: Option<String>,
+    },
+    CountObjects {
+        // Also report packed objects
+        #[clap(short = 'v')]
+        verbose: bool,
+    },
+    Fsck,
+    Prune {
+        // List what would be removed without removing it
+        #[clap(short = 'n', long = "dry-run")]
+        dry_run: bool,
+
+        // Only prune objects older than this many seconds, two weeks by default
+        #[clap(long, default_value_t = 14 * 24 * 60 * 60)]
+        expire: u64,
+    },
+    UnpackObjects {
+        // Read from stdin when omitted
+        pack_file: Option<String>,
+    },
+    ReadTree {
+        // A commit stands for its tree
+        tree_ish: String,
+    },
+    LsFiles {
+        // Show the mode, sha and merge stage of each entry
+        #[clap(short = 's', long = "stage")]
+        stage: bool,
+    },
+    UpdateIndex {
+        // Stage paths that aren't in the index yet
+        #[clap(long)]
+        add: bool,
+
+        // Unstage paths missing from the working tree
+        #[clap(long)]
+        remove: bool,
+
+        // Unstage paths even if they are still in the working tree
+        #[clap(long = "force-remove")]
+        force_remove: bool,
+
+        #[clap(required = true)]
+        paths: Vec<String>,
+    },
+    CheckoutIndex {
+        // Every staged file instead of the given `paths`
+        #[clap(short = 'a', long = "all", conflicts_with = "paths")]
+        all: bool,
+
+        // Overwrite files that already exist
+        #[clap(short = 'f', long = "force")]
+        force: bool,
+
+        // Prepended to every path written, a trailing `/` makes it a directory
+        #[clap(long, default_value = "")]
+        prefix: String,
+
+        paths: Vec<String>,
+    },
     CommitTree {
+        // Read from stdin when neither `-m` nor `-F` are given
         #[clap(short = 'm')]
-        commit_message: String,
+        commit_message: Option<String>,
+
+        #[clap(short = 'F', conflicts_with = "commit_message")]
+        message_file: Option<String>,
 
+        // Omitted for a root commit
         #[clap(short = 'p')]
-        parent_hash: String,
+        parent_hashes: Vec<String>,
 
         tree_hash: String,
     },
-}
+    UpdateRef {
+        refname: String,
 
-#[allow(unused_imports)]
-use std::fs;
+        new_value: String,
 
-#[derive(Eq, PartialEq, Debug)]
-enum ObjectType {
-    Blob,
-    Tree,
-    Commit,
-}
+        // Only update the ref if it currently points to this value
+        old_value: Option<String>,
+    },
+    SymbolicRef {
+        name: String,
 
-struct BlobObject {
-    data: Vec<u8>,
-}
+        // When given, `name` is repointed to it instead of being read
+        target: Option<String>,
+    },
+    RevParse {
+        // Expect exactly one argument naming an object
+        #[clap(long)]
+        verify: bool,
 
-impl BlobObject {
-    pub fn pack(self: &BlobObject) -> Vec<u8> {
-        return [
-            b"blob ",
-            self.data.len().to_string().as_bytes(),
-            b"\0",
-            self.data.as_slice(),
-        ]
-        .concat();
-    }
-}
+        #[clap(required = true)]
+        revisions: Vec<String>,
+    },
+    Log {
+        // Stop after this many commits
+        #[clap(short = 'n', long = "max-count")]
+        max_count: Option<usize>,
 
-#[derive(Clone, Debug, Eq)]
-struct TreeEntry {
-    mode: u32,
-    name: String,
-    sha: String,
-}
+        #[clap(default_value = "HEAD")]
+        commit: String,
+    },
+    MergeBase {
+        // Print every best common ancestor instead of only the newest
+        #[clap(short = 'a', long = "all")]
+        all: bool,
 
-impl TreeEntry {
-    pub fn pack(self: &TreeEntry) -> Vec<u8> {
-        let sha = hex::decode(&self.sha).unwrap();
-
-        return [
-            self.mode.to_string().as_bytes(),
-            b" ",
-            self.name.to_string().as_bytes(),
-            b"\0",
-            sha.as_slice(),
-        ]
-        .concat();
-    }
+        first: String,
+
+        second: String,
+    },
+    RevList {
+        // Stop after this many commits
+        #[clap(short = 'n', long = "max-count")]
+        max_count: Option<usize>,
+
+        #[clap(required = true)]
+        commits: Vec<String>,
+    },
+    ShowRef {
+        // Only refs under `refs/heads/`
+        #[clap(long)]
+        heads: bool,
+
+        // Only refs under `refs/tags/`
+        #[clap(long)]
+        tags: bool,
+    },
 }
 
-// git is very particular about how it sorts entries in a tree
-// 1. case-sensitive (uppercase before lowercase)
-// 2. for the sake of comparison, directories are treated as if there were a trailing `/`
-impl PartialOrd for TreeEntry {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
-    }
+struct LsTreeOptions {
+    name_only: bool,
+    recursive: bool,
+    long: bool,
+    null_terminated: bool,
+    trees_only: bool,
+    abbrev: Option<usize>,
 }
 
-impl Ord for TreeEntry {
-    fn cmp(&self, other: &Self) -> Ordering {
-        let self_name = if self.mode == 040000 {
-            format!("{}/", self.name)
+// `prefix` is the path printed in front of the names of the entries of `tree`
+fn list_tree(
+    repository: &Repository,
+    tree: &TreeObject,
+    prefix: &[u8],
+    options: &LsTreeOptions,
+) -> Result<(), anyhow::Error> {
+    for entry in tree.entries.iter() {
+        let path = if prefix.is_empty() {
+            entry.name.clone()
         } else {
-            self.name.clone()
+            [prefix, b"/", &entry.name].concat()
         };
-        let other_name = if other.mode == 040000 {
-            format!("{}/", other.name)
+
+        let is_tree = entry.object_type() == ObjectType::Tree;
+
+        if options.trees_only && !is_tree {
+            continue;
+        }
+
+        // With `-r`, subtrees are not listed themselves, only their content, unless `-d` is given
+        if options.recursive && is_tree && !options.trees_only {
+            let subtree = repository.read_tree(&entry.sha)?;
+            list_tree(repository, &subtree, &path, options)?;
+
+            continue;
+        }
+
+        // Paths are only quoted when they can't be told apart by their terminator
+        let printed_path = if options.null_terminated {
+            path.clone()
         } else {
-            other.name.clone()
+            quote_path(&path).into_bytes()
+        };
+
+        let sha = match options.abbrev {
+            Some(min_len) if !options.name_only => {
+                repository.abbreviate_object_hash(&entry.sha, min_len)?
+            }
+            _ => entry.sha.clone(),
         };
 
-        self_name.cmp(&other_name)
+        let line = if options.name_only {
+            printed_path
+        } else if options.long {
+            // Only blobs have a size displayed, read from their header
+            let size = if entry.object_type() == ObjectType::Blob {
+                let mut reader = repository.open_object(&entry.sha)?;
+                let (_, size) = read_object_header(&mut reader, &entry.sha)?;
+
+                size.to_string()
+            } else {
+                "-".to_string()
+            };
+
+            let header = format!(
+                "{:06o} {} {} {:>7}\t",
+                entry.mode,
+                entry.object_type().as_str(),
+                sha,
+                size
+            );
+
+            [header.as_bytes(), &printed_path].concat()
+        } else {
+            let entry = TreeEntry {
+                sha,
+                ..entry.clone()
+            };
+
+            entry.format_line(&printed_path)
+        };
+
+        let terminator = if options.null_terminated {
+            b"\0"
+        } else {
+            b"\n"
+        };
+
+        let mut stdout = std::io::stdout().lock();
+        stdout.write_all(&line)?;
+        stdout.write_all(terminator)?;
+
+        // `-r -d` lists every subtree, each one before the subtrees it holds
+        if options.recursive && is_tree {
+            let subtree = repository.read_tree(&entry.sha)?;
+            list_tree(repository, &subtree, &path, options)?;
+        }
     }
+
+    return Ok(());
 }
 
-impl PartialEq for TreeEntry {
-    fn eq(&self, other: &Self) -> bool {
-        self.name == other.name && self.mode == other.mode
+// For each name, `<sha> <type> <size>` then the content followed by a newline,
+// or `<name> missing` if it doesn't name an object
+// Without `with_content` only the header of loose objects is decompressed
+// Output is flushed after every object so callers can interleave requests and answers
+fn cat_file_batch(
+    repository: &Repository,
+    names: impl Iterator<Item = std::io::Result<String>>,
+    with_content: bool,
+) -> Result<(), anyhow::Error> {
+    let mut stdout = std::io::stdout().lock();
+
+    for name in names {
+        let name = name?;
+
+        let object = match repository.resolve_ref(&name) {
+            Ok(Some(object_hash)) => repository
+                .open_object(&object_hash)
+                .map(|reader| (object_hash, reader)),
+            Ok(None) => Err(GitError::ObjectNotFound(name.clone())),
+            Err(error) => Err(error),
+        };
+
+        let (object_hash, mut reader) = match object {
+            Ok(object) => object,
+            Err(GitError::ObjectNotFound(_)) => {
+                writeln!(stdout, "{name} missing")?;
+                stdout.flush()?;
+
+                continue;
+            }
+            Err(GitError::AmbiguousObjectName(_)) => {
+                writeln!(stdout, "{name} ambiguous")?;
+                stdout.flush()?;
+
+                continue;
+            }
+            Err(error) => return Err(error.into()),
+        };
+
+        let (object_type, size) = read_object_header(&mut reader, &object_hash)?;
+        writeln!(stdout, "{object_hash} {} {size}", object_type.as_str())?;
+
+        if !with_content {
+            stdout.flush()?;
+
+            continue;
+        }
+
+        let copied = std::io::copy(&mut reader.take(size as u64), &mut stdout)?;
+        if copied != size as u64 {
+            return Err(GitError::Corrupt(format!("{object_hash} is truncated")).into());
+        }
+
+        writeln!(stdout)?;
+        stdout.flush()?;
     }
-}
 
-struct TreeObject {
-    entries: Vec<TreeEntry>,
+    return Ok(());
 }
 
-impl TreeObject {
-    pub fn pack(self: &mut TreeObject) -> Vec<u8> {
-        self.entries.sort();
-
-        let packed = self
-            .entries
-            .as_slice()
-            .into_iter()
-            .map(|entry| entry.pack())
-            .collect::<Vec<Vec<u8>>>()
-            .concat();
-
-        return [
-            b"tree ",
-            packed.len().to_string().as_bytes(),
-            b"\0",
-            packed.as_slice(),
-        ]
-        .concat();
+// Exit codes follow git: 129 for usage errors, 128 for fatal ones, which is every error that
+// makes it up here, and whatever a command returns otherwise (`cat-file -e` exits with 1 when the
+// object is missing, for instance)
+fn main() -> ExitCode {
+    let args = match Args::try_parse() {
+        Ok(args) => args,
+        Err(error) => {
+            let _ = error.print();
+
+            // `--help` and `--version` end up here too
+            if error.use_stderr() {
+                return ExitCode::from(129);
+            }
+
+            return ExitCode::SUCCESS;
+        }
+    };
+
+    match run(args) {
+        Ok(exit_code) => return exit_code,
+        Err(error) => {
+            eprintln!("fatal: {error:#}");
+
+            return ExitCode::from(128);
+        }
     }
 }
 
-struct CommitObject {
-    tree_hash: String,
-    parents: Vec<String>,
-    author_name: String,
-    author_email: String,
-    author_date_seconds: SystemTime,
-    author_date_timezone: String,
-    committer_name: String,
-    committer_email: String,
-    committer_date_seconds: SystemTime,
-    committer_date_timezone: String,
-    commit_message: String,
-}
+fn run(args: Args) -> Result<ExitCode, anyhow::Error> {
+    for directory in args.directories.iter() {
+        env::set_current_dir(directory)
+            .with_context(|| format!("Could not change to {}", directory.display()))?;
+    }
 
-impl CommitObject {
-    pub fn pack(self: &CommitObject) -> Result<Vec<u8>, anyhow::Error> {
-        let tree_hash = [b"tree ", self.tree_hash.as_bytes(), b"\n"].concat();
-        let parents = self
-            .parents
-            .as_slice()
-            .into_iter()
-            .map(|parent_hash| [b"parent ", parent_hash.as_bytes(), b"\n"].concat())
-            .collect::<Vec<Vec<u8>>>()
-            .concat();
-
-        let author = [
-            b"author ",
-            self.author_name.as_bytes(),
-            b" ",
-            self.author_email.as_bytes(),
-            b" ",
-            self.author_date_seconds
-                .duration_since(UNIX_EPOCH)?
-                .as_secs()
-                .to_string()
-                .as_bytes(),
-            b" ",
-            self.author_date_timezone.as_bytes(),
-            b"\n",
-        ]
-        .concat();
-
-        let committer = [
-            b"committer ",
-            self.committer_name.as_bytes(),
-            b" ",
-            self.committer_email.as_bytes(),
-            b" ",
-            self.committer_date_seconds
-                .duration_since(UNIX_EPOCH)?
-                .as_secs()
-                .to_string()
-                .as_bytes(),
-            b" ",
-            self.committer_date_timezone.as_bytes(),
-            b"\n",
-        ]
-        .concat();
-
-        let message = [b"\n", self.commit_message.as_bytes(), b"\n"].concat();
-
-        let content = [tree_hash, parents, author, committer, message].concat();
-
-        let packed = [
-            b"commit ",
-            content.len().to_string().as_bytes(),
-            b"\0",
-            content.as_slice(),
-        ]
-        .concat();
-
-        return Ok(packed);
+    // Like git, the option is passed on through the environment, which everything else reads
+    if let Some(git_dir) = &args.git_dir {
+        env::set_var("GIT_DIR", git_dir);
     }
-}
 
-enum GitObject {
-    Blob(BlobObject),
-    Tree(TreeObject),
-    #[allow(dead_code)]
-    Commit(CommitObject),
-}
+    match args.command {
+        Command::Init {
+            bare,
+            initial_branch,
+            object_format,
+        } => {
+            anyhow::ensure!(
+                !initial_branch.is_empty()
+                    && !initial_branch.contains(char::is_whitespace)
+                    && !initial_branch.contains(".."),
+                "`{initial_branch}` is not a valid branch name"
+            );
+
+            let git_dir = if let Some(git_dir) = env::var_os("GIT_DIR") {
+                PathBuf::from(git_dir)
+            } else if bare {
+                env::current_dir()?
+            } else {
+                env::current_dir()?.join(".git")
+            };
 
-fn read_tree_entry(
-    reader: &mut BufReader<ZlibDecoder<File>>,
-) -> Result<(TreeEntry, usize), anyhow::Error> {
-    let mut buf = Vec::new();
-    let mut total = 0;
-
-    let n = reader.read_until(b' ', &mut buf)?;
-    total += n;
-
-    let mode = buf.strip_suffix(&[b' ']).unwrap();
-
-    let mode: u32 = std::str::from_utf8(mode)
-        .context("not utf8 ?")?
-        .parse()
-        .context("not a number ?")?;
-
-    buf.clear();
-    let n = reader.read_until(0, &mut buf)?;
-    total += n;
-    let name = buf.strip_suffix(&[0]).unwrap();
-
-    let mut sha = vec![0u8; 20];
-    reader.read_exact(&mut sha)?;
-    total += 20;
-
-    return Ok((
-        TreeEntry {
-            mode,
-            name: String::from_str(std::str::from_utf8(name)?)?,
-            sha: hex::encode(sha),
-        },
-        total,
-    ));
-}
+            let object_format = ObjectFormat::from_str(&object_format)?;
 
-// `<blob> <content-size>\0<content>`
-// `<tree> <content-size>\0<content>` where `<content>`
-//      `<mode> <name>\0<20 bytes sha>`
-fn read_git_object(reader: &mut BufReader<ZlibDecoder<File>>) -> Result<GitObject, anyhow::Error> {
-    let mut buf = Vec::new();
-
-    reader.read_until(b' ', &mut buf)?;
-    let object_type = buf.strip_suffix(&[b' ']).unwrap();
-
-    let object_type = if object_type.starts_with(b"blob") {
-        ObjectType::Blob
-    } else if object_type.starts_with(b"tree") {
-        ObjectType::Tree
-    } else if object_type.starts_with(b"comit") {
-        ObjectType::Commit
-    } else {
-        let object_type = std::str::from_utf8(&buf).context("not utf8 ?")?;
-
-        return Err(anyhow!("Object type `{object_type}` is not supported"));
-    };
+            Repository::init(git_dir, bare, &initial_branch, object_format)?;
+            println!("Initialized git directory")
+        }
+        Command::CatFile {
+            pretty_print,
+            show_type,
+            show_size,
+            exists,
+            batch,
+            batch_check,
+            batch_all_objects,
+            object_hash,
+        } => {
+            anyhow::ensure!(
+                !batch_all_objects || batch || batch_check,
+                "--batch-all-objects requires --batch or --batch-check"
+            );
 
-    buf.clear();
-    reader.read_until(0, &mut buf)?;
-    let size = buf.strip_suffix(&[0]).unwrap();
+            if batch_all_objects {
+                let repository = Repository::discover()?;
+                let objects = list_objects(&repository)?;
+                cat_file_batch(&repository, objects.into_iter().map(Ok), batch)?;
 
-    let size: usize = std::str::from_utf8(size)
-        .context("not utf8 ?")?
-        .parse()
-        .context("not a number ?")?;
+                return Ok(ExitCode::SUCCESS);
+            }
 
-    match object_type {
-        ObjectType::Tree => {
-            let mut entries: Vec<TreeEntry> = Vec::new();
-            let mut remaining = size;
+            if batch || batch_check {
+                let names = std::io::stdin().lock().lines();
+                cat_file_batch(&Repository::discover()?, names, batch)?;
 
-            while remaining > 0 {
-                let (entry, n) = read_tree_entry(reader)?;
-                entries.push(entry);
-                remaining -= n;
+                return Ok(ExitCode::SUCCESS);
+            }
+
+            let object_hash = match object_hash {
+                Some(object_hash) => object_hash,
+                None => anyhow::bail!("expected an object name"),
+            };
+
+            anyhow::ensure!(
+                pretty_print || show_type || show_size || exists,
+                "expected one of -p, -t, -s, -e, --batch or --batch-check"
+            );
+
+            // Silent, the outcome is only reported through the exit code
+            if exists {
+                let is_valid = Repository::discover()
+                    .and_then(|repository| {
+                        let object_hash = repository.resolve_object_hash(&object_hash)?;
+                        let (object_type, content) =
+                            repository.read_verified_content(&object_hash)?;
+
+                        read_object_body(
+                            &mut content.as_slice(),
+                            object_type,
+                            content.len(),
+                            repository.object_format(),
+                        )
+                    })
+                    .is_ok();
+
+                return Ok(if is_valid {
+                    ExitCode::SUCCESS
+                } else {
+                    ExitCode::FAILURE
+                });
+            }
+
+            let repository = Repository::discover()?;
+            let object_hash = repository.resolve_object_hash(&object_hash)?;
+            let mut reader = repository.open_object(&object_hash)?;
+
+            if show_type {
+                let (object_type, _) = read_object_header(&mut reader, &object_hash)?;
+                println!("{}", object_type.as_str());
+
+                return Ok(ExitCode::SUCCESS);
+            }
+
+            // Only the header is decompressed, the content is never read
+            if show_size {
+                let (_, size) = read_object_header(&mut reader, &object_hash)?;
+                println!("{size}");
+
+                return Ok(ExitCode::SUCCESS);
+            }
+
+            let (object_type, content) = repository.read_verified_content(&object_hash)?;
+
+            // git prints commits and tags exactly as they are stored
+            if object_type == ObjectType::Commit || object_type == ObjectType::Tag {
+                std::io::stdout().write_all(&content)?;
+
+                return Ok(ExitCode::SUCCESS);
+            }
+
+            let object = read_object_body(
+                &mut content.as_slice(),
+                object_type,
+                content.len(),
+                repository.object_format(),
+            )?;
+
+            match object {
+                GitObject::Blob(blob) => {
+                    // Blobs are arbitrary bytes, binary files included
+                    std::io::stdout().write_all(&blob.data)?;
+                }
+                GitObject::Tree(tree) => {
+                    let mut stdout = std::io::stdout().lock();
+
+                    for entry in tree.entries.iter() {
+                        stdout.write_all(&entry.format_line(quote_path(&entry.name).as_bytes()))?;
+                        stdout.write_all(b"\n")?;
+                    }
+                }
+                GitObject::Commit(_) | GitObject::Tag(_) => {
+                    unreachable!("commits and tags are printed from their raw content");
+                }
+            }
+        }
+        Command::HashObject {
+            write,
+            object_type,
+            stdin,
+            stdin_paths,
+            no_filters,
+            path,
+            filenames,
+        } => {
+            let object_type = ObjectType::from_str(&object_type)?;
+
+            // A repository is only needed to store the objects, outside of one objects are hashed
+            // with the default format and without filters
+            let repository = match Repository::discover() {
+                Ok(repository) => Some(repository),
+                Err(error) if write => return Err(error.into()),
+                Err(_) => None,
+            };
+            let object_format = repository
+                .as_ref()
+                .map_or(ObjectFormat::default(), Repository::object_format);
+            let git_dir = if write {
+                repository.as_ref().map(Repository::git_dir)
+            } else {
+                None
+            };
+
+            let filters_for = |path: &str| -> Result<Filters, GitError> {
+                match &repository {
+                    Some(repository) if !no_filters => {
+                        return repository.filters(&find_work_tree()?, &path_from_root(path)?)
+                    }
+                    _ => return Ok(Filters::default()),
+                }
+            };
+
+            // Content read from stdin doesn't come from any file, it is only filtered with `--path`
+            let stdin_filters = match &path {
+                Some(path) => filters_for(path)?,
+                None => Filters::default(),
+            };
+            let hash_stdin = || -> Result<String, GitError> {
+                let mut content = Vec::new();
+                std::io::stdin().lock().read_to_end(&mut content)?;
+
+                if object_type == ObjectType::Blob {
+                    content = stdin_filters.clean(content);
+                }
+
+                return hash_content(content.as_slice(), object_type, git_dir, object_format);
+            };
+
+            if stdin_paths {
+                for line in std::io::stdin().lock().lines() {
+                    let line = line?;
+                    let hash = hash_object(
+                        PathBuf::from(&line),
+                        object_type,
+                        git_dir,
+                        object_format,
+                        filters_for(&line)?,
+                    )?;
+                    println!("{hash}");
+                }
             }
 
-            let object = GitObject::Tree(TreeObject { entries });
+            if stdin {
+                println!("{}", hash_stdin()?);
+            }
 
-            return Ok(object);
+            for filename in filenames.iter() {
+                let hash = if filename == "-" {
+                    hash_stdin()?
+                } else {
+                    hash_object(
+                        PathBuf::from(filename),
+                        object_type,
+                        git_dir,
+                        object_format,
+                        filters_for(filename)?,
+                    )?
+                };
+                println!("{hash}");
+            }
         }
-        ObjectType::Blob => {
-            buf.clear();
-            let n = reader.read_to_end(&mut buf)?;
+        Command::LsTree {
+            name_only,
+            recursive,
+            long,
+            null_terminated,
+            trees_only,
+            abbrev,
+            full_name,
+            full_tree,
+            object_hash,
+        } => {
+            let repository = Repository::discover()?;
+            let object = repository.read_object(&object_hash)?;
+
+            match object {
+                GitObject::Tree(mut tree) => {
+                    // From a subdirectory, only what is under it is listed
+                    let prefix = if full_tree {
+                        Vec::new()
+                    } else {
+                        current_prefix()?
+                    };
+
+                    for name in prefix.split(|&byte| byte == b'/') {
+                        let subtree = tree.entries.iter().find(|entry| {
+                            entry.name == name && entry.object_type() == ObjectType::Tree
+                        });
+
+                        match subtree {
+                            Some(entry) => tree = repository.read_tree(&entry.sha)?,
+                            None if name.is_empty() => {}
+                            None => return Ok(ExitCode::SUCCESS),
+                        }
+                    }
 
-            anyhow::ensure!(n == size, "Expected {size} bytes, got {n} bytes");
+                    let path_prefix = if full_name { prefix } else { Vec::new() };
 
-            let object = GitObject::Blob(BlobObject { data: buf });
+                    let options = LsTreeOptions {
+                        name_only,
+                        recursive,
+                        long,
+                        null_terminated,
+                        trees_only,
+                        abbrev,
+                    };
 
-            return Ok(object);
+                    list_tree(&repository, &tree, &path_prefix, &options)?;
+                }
+                _ => {
+                    anyhow::bail!("ls-tree can only read tree objects");
+                }
+            }
         }
-        ObjectType::Commit => {
-            anyhow::bail!("Unimplemented read: commit");
+        Command::WriteTree {
+            working_tree,
+            prefix,
+        } => {
+            let repository = Repository::discover()?;
+
+            // Always the whole working tree, even from one of its subdirectories, like the index
+            let tree_hash = if working_tree {
+                repository.write_tree(find_work_tree()?, &prefix)?
+            } else {
+                repository.write_tree_from_index(&prefix)?
+            };
+            println!("{tree_hash}");
         }
-    };
-}
+        Command::DiffTree {
+            recursive,
+            old_tree,
+            new_tree,
+        } => {
+            let repository = Repository::discover()?;
+
+            let mut hashes = Vec::new();
+            for tree in [&old_tree, &new_tree] {
+                match repository.resolve_ref(tree)? {
+                    Some(hash) => hashes.push(hash),
+                    None => anyhow::bail!("Not a valid object name {tree}"),
+                }
+            }
+
+            for change in repository.diff_trees(&hashes[0], &hashes[1], recursive)? {
+                println!("{}", change.format_raw());
+            }
+        }
+        Command::Mktree { missing } => {
+            let repository = Repository::discover()?;
+            let object_format = repository.object_format();
+
+            // One entry per line, as printed by `ls-tree`
+            let mut entries = Vec::new();
+            for line in std::io::stdin().lock().lines() {
+                let entry = parse_tree_line(&line?, object_format)?;
+
+                // Submodule commits live in another repository
+                if !missing && entry.mode != 0o160000 && !repository.object_exists(&entry.sha)? {
+                    anyhow::bail!(
+                        "Entry `{}` points to missing object {}",
+                        String::from_utf8_lossy(&entry.name),
+                        entry.sha
+                    );
+                }
+
+                entries.push(entry);
+            }
+
+            let tree_hash = repository.write_object(&GitObject::Tree(TreeObject { entries }))?;
+            println!("{tree_hash}");
+        }
+        Command::Mktag => {
+            let repository = Repository::discover()?;
+            let object_format = repository.object_format();
 
-fn hash_object(filename: PathBuf) -> Result<String, anyhow::Error> {
-    match File::open(&filename) {
-        Ok(input_file) => {
             let mut content = Vec::new();
-            let mut reader = BufReader::new(input_file);
-            reader.read_to_end(&mut content).unwrap();
+            std::io::stdin().read_to_end(&mut content)?;
+
+            let tag = read_tag(&content, object_format)?;
 
-            let object = BlobObject {
-                data: content.clone(),
+            // The tagged object must exist with the type the tag claims
+            let mut reader = repository.open_object(&tag.object_hash)?;
+            let (object_type, _) = read_object_header(&mut reader, &tag.object_hash)?;
+
+            if object_type != tag.object_type {
+                return Err(GitError::TypeMismatch {
+                    object_hash: tag.object_hash,
+                    actual: object_type.as_str(),
+                    expected: tag.object_type.as_str(),
+                }
+                .into());
+            }
+
+            // Stored exactly as given, like git does
+            let tag_hash = hash_content(
+                content.as_slice(),
+                ObjectType::Tag,
+                Some(repository.git_dir()),
+                object_format,
+            )?;
+            println!("{tag_hash}");
+        }
+        Command::IndexPack {
+            index_file,
+            pack_file,
+        } => {
+            let pack =
+                fs::read(&pack_file).with_context(|| format!("Could not read {pack_file}"))?;
+
+            // Only needed for thin packs, whose deltas may be based on objects outside the pack,
+            // and to know the width of hashes
+            let repository = Repository::discover().ok();
+            let object_format = repository
+                .as_ref()
+                .map(Repository::object_format)
+                .unwrap_or_default();
+
+            let (objects, pack_checksum) = parse_pack(&pack, repository.as_ref(), object_format)?;
+
+            let index_file = match index_file {
+                Some(index_file) => PathBuf::from(index_file),
+                None => Path::new(&pack_file).with_extension("idx"),
             };
+            write_pack_index(&index_file, &objects, &pack_checksum, object_format)?;
 
-            let packed_object = object.pack();
-            let object_hash = write_object_file(packed_object)?;
+            println!("{pack_checksum}");
+        }
+        Command::PackObjects { stdout, base_name } => {
+            let repository = Repository::discover()?;
 
-            return Ok(object_hash);
+            // One object name per line
+            let mut object_hashes = Vec::new();
+            for line in std::io::stdin().lock().lines() {
+                object_hashes.push(repository.resolve_object_hash(line?.trim())?);
+            }
+
+            let (pack, objects, pack_checksum) = build_pack(&repository, &object_hashes)?;
+
+            match base_name {
+                Some(base_name) if !stdout => {
+                    let pack_file = PathBuf::from(format!("{base_name}-{pack_checksum}.pack"));
+
+                    fs::write(&pack_file, &pack)?;
+                    write_pack_index(
+                        &pack_file.with_extension("idx"),
+                        &objects,
+                        &pack_checksum,
+                        repository.object_format(),
+                    )?;
+
+                    println!("{pack_checksum}");
+                }
+                _ => std::io::stdout().write_all(&pack)?,
+            }
         }
-        Err(_) => {
-            anyhow::bail!("No such file or directory: {:?}", filename);
+        Command::CountObjects { verbose } => {
+            let repository = Repository::discover()?;
+
+            // Sizes are disk usage in KiB, like `du`
+            let disk_usage = |path: &Path| -> Result<u64, anyhow::Error> {
+                return Ok(fs::metadata(path)?.blocks() * 512);
+            };
+
+            let loose_objects = list_loose_objects(&repository)?;
+            let mut size = 0;
+            for (_, path) in loose_objects.iter() {
+                size += disk_usage(path)?;
+            }
+
+            println!("count: {}", loose_objects.len());
+            println!("size: {}", size / 1024);
+
+            if verbose {
+                // Packs borrowed from alternates aren't the repository's own
+                let objects_dir = objects_dir(repository.git_dir())?;
+                let packs: Vec<&PackIndex> = repository
+                    .packs()?
+                    .iter()
+                    .filter(|pack| pack.pack_path.starts_with(&objects_dir))
+                    .collect();
+
+                let mut in_pack = 0;
+                let mut pack_size = 0;
+                for pack in packs.iter() {
+                    in_pack += pack.len();
+                    // Unlike loose objects, packs count by their length rather than disk usage
+                    pack_size += fs::metadata(&pack.pack_path)?.len();
+                    pack_size += fs::metadata(pack.pack_path.with_extension("idx"))?.len();
+                }
+
+                // Loose objects that are also packed could be removed
+                let prune_packable = loose_objects
+                    .iter()
+                    .filter(|(hash, _)| packs.iter().any(|pack| pack.find(hash).is_some()))
+                    .count();
+
+                println!("in-pack: {in_pack}");
+                println!("packs: {}", packs.len());
+                println!("size-pack: {}", pack_size / 1024);
+                println!("prune-packable: {prune_packable}");
+            }
         }
-    }
-}
+        Command::Fsck => {
+            let repository = Repository::discover()?;
+            let mut problems = 0;
+
+            // Every problem is reported before failing, not just the first one
+            for_each_loose_object(&repository, |object_hash| {
+                if let Err(error) = repository.check_object(object_hash) {
+                    eprintln!("error: {object_hash}: {error}");
+                    problems += 1;
+                }
+
+                return Ok(());
+            })?;
 
-fn write_tree(path: PathBuf) -> Result<String, anyhow::Error> {
-    let directory = fs::read_dir(path)?;
-    let mut entries: Vec<TreeEntry> = Vec::new();
+            if problems > 0 {
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+        Command::Prune { dry_run, expire } => {
+            let repository = Repository::discover()?;
 
-    for entry in directory {
-        if let Ok(entry) = entry {
-            let file_name = String::from_str(entry.file_name().to_str().unwrap())?;
-            let file_type = entry.file_type()?;
+            for (object_hash, path) in repository.prunable_objects(Duration::from_secs(expire))? {
+                if dry_run {
+                    let mut reader = repository.open_object(&object_hash)?;
+                    let (object_type, _) = read_object_header(&mut reader, &object_hash)?;
+                    println!("{object_hash} {}", object_type.as_str());
 
-            let sha = if file_type.is_file() {
-                hash_object(entry.path())?
-            } else if file_type.is_dir() {
-                if file_name == ".git" {
                     continue;
                 }
-                write_tree(entry.path())?
-            } else {
-                anyhow::bail!("Neither file nor dir");
-            };
 
-            // Took this small snippet to compute mode from johnoo's implementation.
-            // I'm not entirely sure i understand git object mode.
-            // Seems like mode is a mix of
-            // - file type (first 3 digits)
-            // - unix permissions (last 3 digits)
-            //
-            // 040 -> dir
-            // 120 -> symlink
-            // 100 -> normal file
-            // 160 -> submodule (not covered here)
-            let mode = if file_type.is_dir() {
-                040000
-            } else if file_type.is_symlink() {
-                120000
-            } else if (entry.metadata()?.mode() & 0o111) != 0 {
-                // has at least one executable bit set
-                100755
-            } else {
-                100644
-            };
+                fs::remove_file(&path)?;
 
-            entries.push(TreeEntry {
-                mode,
-                name: file_name,
-                sha,
-            })
+                // Fails as long as the directory holds other objects
+                if let Some(directory) = path.parent() {
+                    let _ = fs::remove_dir(directory);
+                }
+            }
         }
-    }
+        Command::UnpackObjects { pack_file } => {
+            let repository = Repository::discover()?;
 
-    let mut tree = TreeObject { entries };
-    let packed_tree = tree.pack();
-    let tree_hash = write_object_file(packed_tree)?;
+            let pack = match pack_file {
+                Some(pack_file) => {
+                    fs::read(&pack_file).with_context(|| format!("Could not read {pack_file}"))?
+                }
+                None => {
+                    let mut pack = Vec::new();
+                    std::io::stdin().read_to_end(&mut pack)?;
 
-    return Ok(tree_hash);
-}
+                    pack
+                }
+            };
 
-fn main() -> Result<(), anyhow::Error> {
-    let args = Args::parse();
+            let count = repository.unpack_objects(&pack)?;
+            println!("Unpacked {count} objects");
+        }
+        Command::ReadTree { tree_ish } => {
+            let repository = Repository::discover()?;
 
-    match args.command {
-        Command::Init => {
-            fs::create_dir(".git")?;
-            fs::create_dir(".git/objects")?;
-            fs::create_dir(".git/refs")?;
-            fs::write(".git/HEAD", "ref: refs/heads/main\n")?;
-            println!("Initialized git directory")
+            let object_hash = match repository.resolve_ref(&tree_ish)? {
+                Some(object_hash) => object_hash,
+                None => anyhow::bail!("Not a valid object name {tree_ish}"),
+            };
+
+            let tree_hash = match repository.read_object(&object_hash)? {
+                GitObject::Tree(_) => object_hash,
+                GitObject::Commit(commit) => commit.tree_hash,
+                _ => anyhow::bail!("{tree_ish} is not a tree-ish"),
+            };
+
+            let index = repository.index_from_tree(&tree_hash)?;
+            repository.write_index(&index)?;
         }
-        Command::CatFile {
-            pretty_print,
-            object_hash,
+        Command::LsFiles { stage } => {
+            let repository = Repository::discover()?;
+            let index = repository.read_index()?;
+
+            for entry in index.entries.iter() {
+                if stage {
+                    println!(
+                        "{:06o} {} {}\t{}",
+                        entry.mode, entry.sha, entry.stage, entry.path
+                    );
+                } else {
+                    println!("{}", entry.path);
+                }
+            }
+        }
+        Command::UpdateIndex {
+            add,
+            remove,
+            force_remove,
+            paths,
         } => {
-            anyhow::ensure!(pretty_print, "expected pretty print (-p)");
+            let repository = Repository::discover()?;
+            let mut index = repository.read_index()?;
+            let work_tree = find_work_tree()?;
 
-            let dirname = &object_hash[0..2];
-            let filename = &object_hash[2..];
-            let path = std::format!(".git/objects/{dirname}/{filename}");
+            for path in paths {
+                // Paths are given from the current directory, the index holds them from the root
+                let path = path_from_root(&path)?;
 
-            match File::open(&path) {
-                Ok(file) => {
-                    let decoder = ZlibDecoder::new(file);
-                    let mut reader = BufReader::new(decoder);
-                    let object = read_git_object(&mut reader)?;
+                if force_remove || (remove && work_tree.join(&path).symlink_metadata().is_err()) {
+                    index.remove(&path);
 
-                    match object {
-                        GitObject::Blob(blob) => {
-                            print!("{}", std::str::from_utf8(&blob.data)?);
-                        }
-                        _ => {
-                            anyhow::bail!("cat-file can only read blob objects");
-                        }
-                    }
+                    continue;
                 }
-                Err(_) => {
-                    anyhow::bail!("No such file or directory: {}", &path);
+
+                if !add && !index.entries.iter().any(|entry| entry.path == path) {
+                    anyhow::bail!("{path}: cannot add to the index, missing --add option?");
                 }
+
+                let entry = repository.index_entry_from_file(&work_tree, &path)?;
+                index.add(entry);
             }
-        }
-        Command::HashObject { write, filename } => {
-            anyhow::ensure!(write, "expected -w");
 
-            let hash = hash_object(PathBuf::from(filename))?;
-            print!("{hash}");
+            repository.write_index(&index)?;
         }
-        Command::LsTree {
-            name_only,
-            object_hash,
+        Command::CheckoutIndex {
+            all,
+            force,
+            prefix,
+            paths,
         } => {
-            anyhow::ensure!(name_only, "expected --name-only");
-
-            let dirname = &object_hash[0..2];
-            let filename = &object_hash[2..];
-            let path = std::format!(".git/objects/{dirname}/{filename}");
-
-            match File::open(&path) {
-                Ok(file) => {
-                    let decoder = ZlibDecoder::new(file);
-                    let mut reader = BufReader::new(decoder);
-                    let object = read_git_object(&mut reader)?;
-
-                    match object {
-                        GitObject::Tree(tree) => {
-                            for entry in tree.entries.into_iter() {
-                                println!("{}", entry.name);
-                            }
-                        }
-                        _ => {
-                            anyhow::bail!("ls-tree can only read tree objects");
-                        }
+            let repository = Repository::discover()?;
+            let index = repository.read_index()?;
+            let work_tree = find_work_tree()?;
+
+            let mut entries = Vec::new();
+            let mut failed = false;
+
+            if all {
+                // Unmerged paths have no single version to write
+                entries.extend(index.entries.iter().filter(|entry| entry.stage == 0));
+            }
+
+            for path in paths.iter() {
+                // Paths are given from the current directory, the index holds them from the root
+                let path = path_from_root(path)?;
+
+                match index.entries.iter().find(|entry| entry.path == path) {
+                    Some(entry) if entry.stage == 0 => entries.push(entry),
+                    Some(_) => {
+                        eprintln!("error: {path} is unmerged");
+                        failed = true;
+                    }
+                    None => {
+                        eprintln!("error: {path} is not in the index");
+                        failed = true;
                     }
                 }
-                Err(_) => {
-                    anyhow::bail!("No such file or directory: {}", &path);
+            }
+
+            // Checked before anything is written, so that a bad entry leaves the working tree as is
+            for entry in entries.iter() {
+                validate_path(&entry.path)?;
+            }
+
+            for entry in entries {
+                // Like git, `--prefix` is relative to the root of the working tree rather than
+                // the current directory
+                let relative_path = format!("{prefix}{}", entry.path);
+                let path = work_tree.join(&relative_path);
+
+                if !force && path.symlink_metadata().is_ok() {
+                    eprintln!("{relative_path} already exists, no checkout");
+                    failed = true;
+
+                    continue;
                 }
+
+                repository.checkout_entry(&work_tree, entry, &path)?;
+            }
+
+            if failed {
+                return Ok(ExitCode::FAILURE);
             }
-        }
-        Command::WriteTree => {
-            // Assume that every file in the current directory needs to be convered to git objects.
-            // Usually, only files / directories in the staging area need to be converted to git
-            // objects.
-            let tree_hash = write_tree(PathBuf::from("."))?;
-            print!("{tree_hash}");
         }
         Command::CommitTree {
             tree_hash,
-            parent_hash,
+            parent_hashes,
             commit_message,
+            message_file,
         } => {
+            let repository = Repository::discover()?;
+
+            // Messages from a file or stdin are kept as bytes, they need not be UTF-8
+            let mut commit_message = match (commit_message, message_file) {
+                (Some(commit_message), _) => commit_message.into_bytes(),
+                (None, Some(message_file)) if message_file != "-" => fs::read(&message_file)
+                    .with_context(|| format!("Could not read {message_file}"))?,
+                _ => {
+                    let mut commit_message = Vec::new();
+                    std::io::stdin().read_to_end(&mut commit_message)?;
+
+                    commit_message
+                }
+            };
+            // Messages end with exactly one newline however many were given, an empty message
+            // stays empty
+            let message_len = commit_message
+                .iter()
+                .rposition(|&byte| byte != b'\n')
+                .map_or(0, |position| position + 1);
+            commit_message.truncate(message_len);
+            if !commit_message.is_empty() {
+                commit_message.push(b'\n');
+            }
+
+            // A commit must point to a stored tree, and its parents to stored commits
+            let tree_hash = repository.resolve_object_hash_of_type(&tree_hash, ObjectType::Tree)?;
+            let parents = parent_hashes
+                .iter()
+                .map(|parent_hash| {
+                    repository.resolve_object_hash_of_type(parent_hash, ObjectType::Commit)
+                })
+                .collect::<Result<Vec<String>, GitError>>()?;
+
+            let (author_name, author_email) = repository.read_identity("AUTHOR")?;
+            let (committer_name, committer_email) = repository.read_identity("COMMITTER")?;
+
+            let now = SystemTime::now();
+            let timezone = format_timezone(local_timezone_offset(now)?);
+
+            let (author_date_seconds, author_date_timezone) =
+                read_date("AUTHOR")?.unwrap_or_else(|| (now, timezone.clone()));
+            let (committer_date_seconds, committer_date_timezone) =
+                read_date("COMMITTER")?.unwrap_or_else(|| (now, timezone.clone()));
+
             let commit = CommitObject {
                 tree_hash,
                 commit_message,
-                parents: vec![parent_hash],
-                author_date_seconds: SystemTime::now(),
-                author_date_timezone: "+0001".to_string(),
-                author_email: "bogus-mail@bogus-exchange.com".to_string(),
-                author_name: "A Koala".to_string(),
-                committer_date_seconds: SystemTime::now(),
-                committer_date_timezone: "+0001".to_string(),
-                committer_email: "bogus-mail@bogus-exchange.com".to_string(),
-                committer_name: "A Koala".to_string(),
+                parents,
+                author_date_seconds,
+                author_date_timezone,
+                author_email,
+                author_name,
+                committer_date_seconds,
+                committer_date_timezone,
+                committer_email,
+                committer_name,
+                extra_headers: Vec::new(),
             };
 
-            let packed_commit = commit.pack()?;
-            let commit_hash = write_object_file(packed_commit)?;
+            let commit_hash = repository.write_object(&GitObject::Commit(commit))?;
 
-            print!("{commit_hash}");
+            println!("{commit_hash}");
         }
-    }
+        Command::UpdateRef {
+            refname,
+            new_value,
+            old_value,
+        } => {
+            let repository = Repository::discover()?;
 
-    return Ok(());
-}
+            repository.update_ref(&refname, &new_value, old_value.as_deref())?;
+        }
+        Command::RevParse { verify, revisions } => {
+            let repository = Repository::discover()?;
+
+            if verify {
+                anyhow::ensure!(revisions.len() == 1, "Needed a single revision");
+
+                match repository.resolve_ref(&revisions[0])? {
+                    Some(hash) => println!("{hash}"),
+                    None => anyhow::bail!("Needed a single revision"),
+                }
+
+                return Ok(ExitCode::SUCCESS);
+            }
+
+            for revision in revisions.iter() {
+                match repository.resolve_ref(revision)? {
+                    Some(hash) => println!("{hash}"),
+                    None => anyhow::bail!("Ambiguous argument `{revision}`: unknown revision"),
+                }
+            }
+        }
+        Command::Log { max_count, commit } => {
+            let repository = Repository::discover()?;
+
+            let start = match repository.resolve_ref(&commit)? {
+                Some(hash) => hash,
+                None => anyhow::bail!("Ambiguous argument `{commit}`: unknown revision"),
+            };
+
+            let mut stdout = std::io::stdout().lock();
+
+            for (i, (hash, commit)) in repository.rev_list(&[start], max_count)?.iter().enumerate()
+            {
+                if i > 0 {
+                    writeln!(stdout)?;
+                }
+
+                writeln!(stdout, "commit {hash}")?;
+
+                // Parents are abbreviated, the way git shows them
+                if commit.parents.len() > 1 {
+                    let parents = commit
+                        .parents
+                        .iter()
+                        .map(|parent| repository.abbreviate_object_hash(parent, 7))
+                        .collect::<Result<Vec<String>, GitError>>()?;
+                    writeln!(stdout, "Merge: {}", parents.join(" "))?;
+                }
 
-fn write_object_file(packed: Vec<u8>) -> Result<String, anyhow::Error> {
-    let mut hasher = Sha1::new();
-    hasher.write_all(&packed)?;
+                writeln!(
+                    stdout,
+                    "Author: {} <{}>",
+                    commit.author_name, commit.author_email
+                )?;
+                writeln!(
+                    stdout,
+                    "Date:   {}",
+                    format_date(commit.author_date_seconds, &commit.author_date_timezone)?
+                )?;
+                writeln!(stdout)?;
+
+                // Messages in another encoding are only displayed approximately
+                let commit_message = String::from_utf8_lossy(&commit.commit_message);
+                let commit_message = commit_message.strip_suffix('\n').unwrap_or(&commit_message);
+
+                for line in commit_message.split('\n') {
+                    writeln!(stdout, "    {line}")?;
+                }
+            }
+        }
+        Command::MergeBase { all, first, second } => {
+            let repository = Repository::discover()?;
+
+            let mut hashes = Vec::new();
+            for commit in [&first, &second] {
+                match repository.resolve_ref(commit)? {
+                    Some(hash) => hashes.push(hash),
+                    None => anyhow::bail!("Not a valid object name {commit}"),
+                }
+            }
 
-    let sha = hasher.finalize();
-    let hash = hex::encode(sha);
+            let bases = repository.merge_bases(&hashes[0], &hashes[1])?;
+
+            // Unrelated histories have no common ancestor
+            if bases.is_empty() {
+                return Ok(ExitCode::FAILURE);
+            }
+
+            let count = if all { bases.len() } else { 1 };
+            for base in bases.iter().take(count) {
+                println!("{base}");
+            }
+        }
+        Command::RevList { max_count, commits } => {
+            let repository = Repository::discover()?;
+
+            let mut starts = Vec::new();
+            for commit in commits.iter() {
+                match repository.resolve_ref(commit)? {
+                    Some(hash) => starts.push(hash),
+                    None => anyhow::bail!("Ambiguous argument `{commit}`: unknown revision"),
+                }
+            }
 
-    let dirname = &hash[0..2];
-    let filename = &hash[2..];
+            for (hash, _) in repository.rev_list(&starts, max_count)? {
+                println!("{hash}");
+            }
+        }
+        Command::ShowRef { heads, tags } => {
+            let repository = Repository::discover()?;
+            let mut found = false;
+
+            for (refname, hash) in repository.list_refs()? {
+                let is_shown = (!heads && !tags)
+                    || (heads && refname.starts_with("refs/heads/"))
+                    || (tags && refname.starts_with("refs/tags/"));
+
+                if is_shown {
+                    println!("{hash} {refname}");
+                    found = true;
+                }
+            }
 
-    fs::create_dir(format!(".git/objects/{dirname}"))?;
-    let output_file = File::create(format!(".git/objects/{dirname}/{filename}"))?;
-    let mut encoder = ZlibEncoder::new(output_file, Compression::best());
+            // Like git, finding nothing is an error
+            if !found {
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+        Command::SymbolicRef { name, target } => {
+            let repository = Repository::discover()?;
 
-    encoder.write(&packed)?;
+            match target {
+                Some(target) => repository.write_symbolic_ref(&name, &target)?,
+                None => println!("{}", repository.read_symbolic_ref(&name)?),
+            }
+        }
+    }
 
-    return Ok(hash);
+    return Ok(ExitCode::SUCCESS);
 }
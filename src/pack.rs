@@ -0,0 +1,778 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, File},
+    io::{BufRead, BufReader, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use flate2::{bufread::ZlibDecoder, write::ZlibEncoder, Compression};
+
+use crate::{
+    compute_object_hash, object_dirs, open_object, pack_object, read_object_content,
+    read_object_header, write_object_file, GitError, ObjectFormat, ObjectType, Repository,
+};
+
+// Where each object of a `.pack` lives, read from the `.idx` next to it
+pub struct PackIndex {
+    pub pack_path: PathBuf,
+    // Of the repository the pack belongs to, deciding the width of the hashes it holds
+    pub object_format: ObjectFormat,
+    // Raw hashes, back to back and sorted, so objects can be looked up by binary search without
+    // hex encoding every one of them
+    hashes: Vec<u8>,
+    pub offsets: Vec<u64>,
+}
+
+impl PackIndex {
+    // Number of objects in the pack
+    pub fn len(self: &PackIndex) -> usize {
+        return self.offsets.len();
+    }
+
+    pub fn is_empty(self: &PackIndex) -> bool {
+        return self.offsets.is_empty();
+    }
+
+    fn raw_hash(self: &PackIndex, position: usize) -> &[u8] {
+        let hash_length = self.object_format.raw_len();
+
+        return &self.hashes[position * hash_length..(position + 1) * hash_length];
+    }
+
+    // Position of the first hash that isn't smaller than `raw`
+    fn lower_bound(self: &PackIndex, raw: &[u8]) -> usize {
+        let (mut low, mut high) = (0, self.len());
+
+        while low < high {
+            let middle = low + (high - low) / 2;
+
+            if self.raw_hash(middle) < raw {
+                low = middle + 1;
+            } else {
+                high = middle;
+            }
+        }
+
+        return low;
+    }
+
+    // Every hash of the pack, hex encoded, sorted
+    pub fn hashes(self: &PackIndex) -> impl Iterator<Item = String> + '_ {
+        return (0..self.len()).map(|position| hex::encode(self.raw_hash(position)));
+    }
+
+    pub fn find(self: &PackIndex, object_hash: &str) -> Option<u64> {
+        let raw = match hex::decode(object_hash) {
+            Ok(raw) if raw.len() == self.object_format.raw_len() => raw,
+            _ => return None,
+        };
+
+        let position = self.lower_bound(&raw);
+        if position < self.len() && self.raw_hash(position) == raw.as_slice() {
+            return Some(self.offsets[position]);
+        }
+
+        return None;
+    }
+
+    // Every hash starting with `prefix`
+    pub fn find_prefix(self: &PackIndex, prefix: &str) -> Vec<String> {
+        // Padded with zeros, an odd length prefix is the smallest hash it can start
+        let raw = match hex::decode(format!("{prefix}{}", "0".repeat(prefix.len() % 2))) {
+            Ok(raw) => raw,
+            Err(_) => return Vec::new(),
+        };
+
+        return (self.lower_bound(&raw)..self.len())
+            .map(|position| hex::encode(self.raw_hash(position)))
+            .take_while(|hash| hash.starts_with(prefix))
+            .collect();
+    }
+}
+
+fn read_u32(content: &[u8], offset: usize) -> Result<u32, GitError> {
+    let bytes = content
+        .get(offset..offset + 4)
+        .ok_or_else(|| GitError::InvalidPack("truncated index".to_string()))?;
+
+    return Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]));
+}
+
+// Version 2 `.idx`:
+// `\377tOc <version> <fanout: 256 x u32> <hashes: n x sha> <crc32s: n x u32>
+// <offsets: n x u32> <large offsets: m x u64> <pack sha> <index sha>`
+// Offsets with their high bit set index into the large offsets table instead
+pub fn read_pack_index(path: &Path, object_format: ObjectFormat) -> Result<PackIndex, GitError> {
+    let content = fs::read(path)?;
+
+    if content.get(0..4) != Some(b"\xfftOc".as_slice()) || read_u32(&content, 4)? != 2 {
+        return Err(GitError::InvalidPack(format!(
+            "{} is not a version 2 pack index",
+            path.display()
+        )));
+    }
+
+    let count = read_u32(&content, 8 + 255 * 4)? as usize;
+
+    let hash_length = object_format.raw_len();
+    let hashes_start = 8 + 256 * 4;
+    let offsets_start = hashes_start + count * hash_length + count * 4;
+    let large_offsets_start = offsets_start + count * 4;
+
+    let hashes = content
+        .get(hashes_start..hashes_start + count * hash_length)
+        .ok_or_else(|| GitError::InvalidPack("truncated index".to_string()))?
+        .to_vec();
+    let mut offsets = Vec::with_capacity(count);
+
+    for i in 0..count {
+        let offset = read_u32(&content, offsets_start + i * 4)?;
+
+        let offset = if offset & 0x8000_0000 != 0 {
+            let position = large_offsets_start + (offset & 0x7fff_ffff) as usize * 8;
+            let high = read_u32(&content, position)? as u64;
+            let low = read_u32(&content, position + 4)? as u64;
+
+            (high << 32) | low
+        } else {
+            offset as u64
+        };
+        offsets.push(offset);
+    }
+
+    return Ok(PackIndex {
+        pack_path: path.with_extension("pack"),
+        object_format,
+        hashes,
+        offsets,
+    });
+}
+
+// Every pack objects can be read from, through their `.idx`, alternates included
+// See `Repository::packs`, which only reads them once
+pub fn read_pack_indexes(
+    git_dir: &Path,
+    object_format: ObjectFormat,
+) -> Result<Vec<PackIndex>, GitError> {
+    let mut indexes = Vec::new();
+
+    for objects_dir in object_dirs(git_dir)? {
+        let directory = match fs::read_dir(objects_dir.join("pack")) {
+            Ok(directory) => directory,
+            Err(_) => continue,
+        };
+
+        for entry in directory {
+            let path = entry?.path();
+
+            if path.extension().is_some_and(|extension| extension == "idx") {
+                indexes.push(read_pack_index(&path, object_format)?);
+            }
+        }
+    }
+
+    return Ok(indexes);
+}
+
+// An entry of a pack as stored, deltas are yet to be applied to their base
+pub enum PackEntry {
+    Object(ObjectType, Vec<u8>),
+    // Base is the entry found `base_offset` bytes into the pack
+    OfsDelta { base_offset: u64, delta: Vec<u8> },
+    // Base is the object with this hash, in the pack or elsewhere
+    RefDelta { base_hash: String, delta: Vec<u8> },
+}
+
+fn read_byte(reader: &mut impl BufRead) -> Result<u8, GitError> {
+    let mut byte = [0u8; 1];
+    reader.read_exact(&mut byte)?;
+
+    return Ok(byte[0]);
+}
+
+// `<type and size>` then for deltas `<base>`, then the zlib compressed data
+// The first byte holds a continuation bit, 3 bits of type and the low 4 bits of the size, every
+// following byte holds a continuation bit and the next 7 bits of the size
+// `offset` is where the entry starts in the pack, `reader` is left right after the entry
+pub fn read_pack_entry(
+    reader: &mut impl BufRead,
+    offset: u64,
+    object_format: ObjectFormat,
+) -> Result<PackEntry, GitError> {
+    let mut byte = read_byte(reader)?;
+    let kind = (byte >> 4) & 0b111;
+    let mut size = (byte & 0b1111) as usize;
+    let mut shift = 4;
+
+    while byte & 0x80 != 0 {
+        if shift >= usize::BITS {
+            return Err(GitError::InvalidPack(format!(
+                "entry size at {offset} is too large"
+            )));
+        }
+
+        byte = read_byte(reader)?;
+        size |= ((byte & 0x7f) as usize) << shift;
+        shift += 7;
+    }
+
+    let entry = match kind {
+        1 => PackEntry::Object(ObjectType::Commit, inflate_entry(reader, size, offset)?),
+        2 => PackEntry::Object(ObjectType::Tree, inflate_entry(reader, size, offset)?),
+        3 => PackEntry::Object(ObjectType::Blob, inflate_entry(reader, size, offset)?),
+        4 => PackEntry::Object(ObjectType::Tag, inflate_entry(reader, size, offset)?),
+        // Offset back from this entry, big-endian 7 bits at a time, each continuation adding one
+        // so that no two encodings mean the same offset
+        6 => {
+            let mut byte = read_byte(reader)?;
+            let mut distance = (byte & 0x7f) as u64;
+
+            let bad_base = || GitError::InvalidPack(format!("bad delta base at {offset}"));
+
+            while byte & 0x80 != 0 {
+                if distance > u64::MAX >> 7 {
+                    return Err(bad_base());
+                }
+
+                byte = read_byte(reader)?;
+                distance = ((distance + 1) << 7) | (byte & 0x7f) as u64;
+            }
+
+            // The base comes strictly before the delta
+            let base_offset = match offset.checked_sub(distance) {
+                Some(base_offset) if distance != 0 => base_offset,
+                _ => return Err(bad_base()),
+            };
+
+            PackEntry::OfsDelta {
+                base_offset,
+                delta: inflate_entry(reader, size, offset)?,
+            }
+        }
+        7 => {
+            let mut base_hash = vec![0u8; object_format.raw_len()];
+            reader.read_exact(&mut base_hash)?;
+
+            PackEntry::RefDelta {
+                base_hash: hex::encode(base_hash),
+                delta: inflate_entry(reader, size, offset)?,
+            }
+        }
+        _ => {
+            return Err(GitError::InvalidPack(format!(
+                "unknown entry type {kind} at {offset}"
+            )))
+        }
+    };
+
+    return Ok(entry);
+}
+
+// The zlib stream holding `size` bytes of data, only what belongs to it is consumed from `reader`
+fn inflate_entry(reader: &mut impl BufRead, size: usize, offset: u64) -> Result<Vec<u8>, GitError> {
+    let mut data = Vec::with_capacity(size);
+    ZlibDecoder::new(&mut *reader).read_to_end(&mut data)?;
+
+    if data.len() != size {
+        return Err(GitError::InvalidPack(format!(
+            "entry at {offset} inflates to {} bytes, expected {size}",
+            data.len()
+        )));
+    }
+
+    return Ok(data);
+}
+
+// Sizes in a delta are little-endian 7 bits at a time
+fn read_delta_size(delta: &[u8], position: &mut usize) -> Result<usize, GitError> {
+    let mut size = 0;
+    let mut shift = 0;
+
+    loop {
+        if shift >= usize::BITS {
+            return Err(GitError::InvalidPack("delta size is too large".to_string()));
+        }
+
+        let byte = *delta
+            .get(*position)
+            .ok_or_else(|| GitError::InvalidPack("truncated delta".to_string()))?;
+        *position += 1;
+
+        size |= ((byte & 0x7f) as usize) << shift;
+        shift += 7;
+
+        if byte & 0x80 == 0 {
+            return Ok(size);
+        }
+    }
+}
+
+// `<base size> <result size>` followed by instructions, either
+// - copy: `1xxxxxxx` then up to 4 offset bytes and 3 size bytes, the low bits telling which are
+//   present, a size of 0 meaning 0x10000
+// - insert: `0nnnnnnn` then the `n` bytes to insert
+pub fn apply_delta(base: &[u8], delta: &[u8]) -> Result<Vec<u8>, GitError> {
+    let truncated = || GitError::InvalidPack("truncated delta".to_string());
+
+    let mut position = 0;
+    let base_size = read_delta_size(delta, &mut position)?;
+    let result_size = read_delta_size(delta, &mut position)?;
+
+    if base_size != base.len() {
+        return Err(GitError::InvalidPack(format!(
+            "delta expects a base of {base_size} bytes, got {}",
+            base.len()
+        )));
+    }
+
+    let mut result = Vec::with_capacity(result_size);
+
+    while position < delta.len() {
+        let instruction = delta[position];
+        position += 1;
+
+        if instruction & 0x80 != 0 {
+            let mut copy_offset = 0;
+            let mut copy_size = 0;
+
+            for i in 0..7 {
+                if instruction & (1 << i) == 0 {
+                    continue;
+                }
+
+                let byte = *delta.get(position).ok_or_else(truncated)? as usize;
+                position += 1;
+
+                if i < 4 {
+                    copy_offset |= byte << (8 * i);
+                } else {
+                    copy_size |= byte << (8 * (i - 4));
+                }
+            }
+
+            if copy_size == 0 {
+                copy_size = 0x10000;
+            }
+
+            let chunk = base
+                .get(copy_offset..copy_offset + copy_size)
+                .ok_or_else(|| {
+                    GitError::InvalidPack("delta copies outside its base".to_string())
+                })?;
+            result.extend_from_slice(chunk);
+        } else if instruction != 0 {
+            let size = instruction as usize;
+            let chunk = delta.get(position..position + size).ok_or_else(truncated)?;
+            result.extend_from_slice(chunk);
+            position += size;
+        } else {
+            return Err(GitError::InvalidPack(
+                "reserved delta instruction".to_string(),
+            ));
+        }
+    }
+
+    if result.len() != result_size {
+        return Err(GitError::InvalidPack(format!(
+            "delta produced {} bytes, expected {result_size}",
+            result.len()
+        )));
+    }
+
+    return Ok(result);
+}
+
+// Longest chain of deltas followed before the pack is deemed corrupt, the largest `--depth` git
+// packs objects with, so that a delta cycle is an error rather than an endless loop
+const MAX_DELTA_DEPTH: usize = 4095;
+
+// Type and content of the object at `offset` in the pack, with every delta applied
+// The chain of deltas is followed down to its base, then applied from the base up
+pub fn read_packed_object(
+    repository: &Repository,
+    pack: &PackIndex,
+    offset: u64,
+) -> Result<(ObjectType, Vec<u8>), GitError> {
+    let mut deltas = Vec::new();
+    let mut entry_offset = offset;
+
+    let (object_type, mut content) = loop {
+        if deltas.len() > MAX_DELTA_DEPTH {
+            return Err(GitError::InvalidPack(format!(
+                "delta chain at {offset} is too deep"
+            )));
+        }
+
+        let mut file = File::open(&pack.pack_path)?;
+        file.seek(SeekFrom::Start(entry_offset))?;
+        let mut reader = BufReader::new(file);
+
+        match read_pack_entry(&mut reader, entry_offset, pack.object_format)? {
+            PackEntry::Object(object_type, content) => break (object_type, content),
+            PackEntry::OfsDelta { base_offset, delta } => {
+                deltas.push(delta);
+                entry_offset = base_offset;
+            }
+            // A base in the same pack is followed like an offset delta, so that cycles are caught
+            PackEntry::RefDelta { base_hash, delta } => {
+                deltas.push(delta);
+
+                match pack.find(&base_hash) {
+                    Some(base_offset) => entry_offset = base_offset,
+                    None => {
+                        let mut reader = open_object(repository, &base_hash)?;
+                        let (object_type, size) = read_object_header(&mut reader, &base_hash)?;
+                        break (object_type, read_object_content(&mut reader, size)?);
+                    }
+                }
+            }
+        }
+    };
+
+    for delta in deltas.iter().rev() {
+        content = apply_delta(&content, delta)?;
+    }
+
+    return Ok((object_type, content));
+}
+
+// Looks `object_hash` up in every pack of the repository
+pub fn find_packed_object(
+    repository: &Repository,
+    object_hash: &str,
+) -> Result<Option<(ObjectType, Vec<u8>)>, GitError> {
+    for pack in repository.packs()? {
+        if let Some(offset) = pack.find(object_hash) {
+            return Ok(Some(read_packed_object(repository, pack, offset)?));
+        }
+    }
+
+    return Ok(None);
+}
+
+// Table driven CRC-32 (IEEE), as stored for each entry in pack indexes
+const CRC32_TABLE: [u32; 256] = crc32_table();
+
+const fn crc32_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                0xedb8_8320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+
+        table[i] = crc;
+        i += 1;
+    }
+
+    return table;
+}
+
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+
+    for &byte in data {
+        crc = CRC32_TABLE[((crc ^ byte as u32) & 0xff) as usize] ^ (crc >> 8);
+    }
+
+    return !crc;
+}
+
+// An object of a pack with its deltas applied
+pub struct PackedObject {
+    pub offset: u64,
+    // Of the entry as stored in the pack, compressed
+    pub crc32: u32,
+    pub object_type: ObjectType,
+    pub content: Vec<u8>,
+    pub hash: String,
+}
+
+// `PACK <version> <object count>`, the entries, then the sha of everything before it
+// Returns every object in pack order along with the pack checksum
+// Bases of `REF_DELTA`s missing from the pack are looked up in `repository` when given
+pub fn parse_pack(
+    pack: &[u8],
+    repository: Option<&Repository>,
+    object_format: ObjectFormat,
+) -> Result<(Vec<PackedObject>, String), GitError> {
+    let hash_length = object_format.raw_len();
+
+    if pack.len() < 12 + hash_length || &pack[0..4] != b"PACK" {
+        return Err(GitError::InvalidPack("missing PACK signature".to_string()));
+    }
+
+    let version = u32::from_be_bytes([pack[4], pack[5], pack[6], pack[7]]);
+    if version != 2 && version != 3 {
+        return Err(GitError::InvalidPack(format!(
+            "version {version} is not supported"
+        )));
+    }
+
+    let count = u32::from_be_bytes([pack[8], pack[9], pack[10], pack[11]]) as usize;
+
+    let (body, checksum) = pack.split_at(pack.len() - hash_length);
+    let pack_checksum = hex::encode(checksum);
+
+    if object_format.digest(body) != pack_checksum {
+        return Err(GitError::InvalidPack("checksum mismatch".to_string()));
+    }
+
+    let mut entries = Vec::with_capacity(count);
+    let mut offset = 12;
+
+    for _ in 0..count {
+        let mut reader = body
+            .get(offset..)
+            .ok_or_else(|| GitError::InvalidPack("truncated".to_string()))?;
+        let remaining = reader.len();
+
+        let entry = read_pack_entry(&mut reader, offset as u64, object_format)?;
+        let length = remaining - reader.len();
+
+        entries.push((offset as u64, crc32(&body[offset..offset + length]), entry));
+        offset += length;
+    }
+
+    let by_offset: HashMap<u64, usize> = entries
+        .iter()
+        .enumerate()
+        .map(|(i, (offset, _, _))| (*offset, i))
+        .collect();
+    let mut by_hash: HashMap<String, usize> = HashMap::new();
+    let mut resolved: Vec<Option<(ObjectType, Vec<u8>, String)>> = vec![None; count];
+
+    // A delta can only be applied once its base is known, which for `REF_DELTA`s may come later in
+    // the pack, so keep going over what is left until nothing more can be resolved
+    loop {
+        let mut progress = false;
+
+        for i in 0..count {
+            if resolved[i].is_some() {
+                continue;
+            }
+
+            let object = match &entries[i].2 {
+                PackEntry::Object(object_type, content) => Some((*object_type, content.clone())),
+                PackEntry::OfsDelta { base_offset, delta } => {
+                    let base = by_offset
+                        .get(base_offset)
+                        .and_then(|&base| resolved[base].as_ref());
+
+                    match base {
+                        Some((object_type, base, _)) => {
+                            Some((*object_type, apply_delta(base, delta)?))
+                        }
+                        None => None,
+                    }
+                }
+                PackEntry::RefDelta { base_hash, delta } => {
+                    let base = by_hash
+                        .get(base_hash)
+                        .and_then(|&base| resolved[base].as_ref());
+
+                    match (base, repository) {
+                        (Some((object_type, base, _)), _) => {
+                            Some((*object_type, apply_delta(base, delta)?))
+                        }
+                        (None, Some(repository)) => match open_object(repository, base_hash) {
+                            Ok(mut reader) => {
+                                let (object_type, size) =
+                                    read_object_header(&mut reader, base_hash)?;
+                                let base = read_object_content(&mut reader, size)?;
+
+                                Some((object_type, apply_delta(&base, delta)?))
+                            }
+                            Err(_) => None,
+                        },
+                        (None, None) => None,
+                    }
+                }
+            };
+
+            if let Some((object_type, content)) = object {
+                let hash = compute_object_hash(&pack_object(object_type, &content), object_format)?;
+
+                by_hash.insert(hash.clone(), i);
+                resolved[i] = Some((object_type, content, hash));
+                progress = true;
+            }
+        }
+
+        if !progress {
+            break;
+        }
+    }
+
+    let mut objects = Vec::with_capacity(count);
+
+    for ((offset, crc32, _), object) in entries.into_iter().zip(resolved) {
+        let (object_type, content, hash) = object.ok_or_else(|| {
+            GitError::InvalidPack(format!("could not resolve the delta at {offset}"))
+        })?;
+
+        objects.push(PackedObject {
+            offset,
+            crc32,
+            object_type,
+            content,
+            hash,
+        });
+    }
+
+    return Ok((objects, pack_checksum));
+}
+
+// Version 2 `.idx` for the objects of a pack, see `read_pack_index` for the layout
+pub fn write_pack_index(
+    path: &Path,
+    objects: &[PackedObject],
+    pack_checksum: &str,
+    object_format: ObjectFormat,
+) -> Result<(), GitError> {
+    let mut sorted = objects.iter().collect::<Vec<&PackedObject>>();
+    sorted.sort_by(|a, b| a.hash.cmp(&b.hash));
+
+    let mut content = Vec::new();
+    content.extend_from_slice(b"\xfftOc");
+    content.extend_from_slice(&2u32.to_be_bytes());
+
+    // Number of objects whose first hash byte is at most the index
+    let mut fanout = [0u32; 256];
+    for object in sorted.iter() {
+        let first_byte = u8::from_str_radix(&object.hash[0..2], 16)
+            .map_err(|_| GitError::InvalidPack(format!("bad hash {}", object.hash)))?;
+
+        for count in fanout[first_byte as usize..].iter_mut() {
+            *count += 1;
+        }
+    }
+    for count in fanout.iter() {
+        content.extend_from_slice(&count.to_be_bytes());
+    }
+
+    for object in sorted.iter() {
+        let hash = hex::decode(&object.hash)
+            .map_err(|_| GitError::InvalidPack(format!("bad hash {}", object.hash)))?;
+        content.extend_from_slice(&hash);
+    }
+
+    for object in sorted.iter() {
+        content.extend_from_slice(&object.crc32.to_be_bytes());
+    }
+
+    // Offsets that don't fit in 31 bits go to the large offsets table
+    let mut large_offsets = Vec::new();
+    for object in sorted.iter() {
+        let offset = if object.offset < 0x8000_0000 {
+            object.offset as u32
+        } else {
+            large_offsets.push(object.offset);
+            0x8000_0000 | (large_offsets.len() - 1) as u32
+        };
+        content.extend_from_slice(&offset.to_be_bytes());
+    }
+    for offset in large_offsets.iter() {
+        content.extend_from_slice(&offset.to_be_bytes());
+    }
+
+    let pack_checksum = hex::decode(pack_checksum)
+        .map_err(|_| GitError::InvalidPack(format!("bad checksum {pack_checksum}")))?;
+    content.extend_from_slice(&pack_checksum);
+
+    let mut hasher = object_format.hasher();
+    hasher.update(&content);
+    content.extend_from_slice(&hasher.finalize());
+
+    fs::write(path, content)?;
+
+    return Ok(());
+}
+
+// Stores every object of `pack` as a loose object, returning how many there were
+pub fn unpack_objects(repository: &Repository, pack: &[u8]) -> Result<usize, GitError> {
+    let object_format = repository.object_format();
+    let (objects, _) = parse_pack(pack, Some(repository), object_format)?;
+
+    for object in objects.iter() {
+        write_object_file(
+            repository.git_dir(),
+            pack_object(object.object_type, &object.content),
+            object_format,
+        )?;
+    }
+
+    return Ok(objects.len());
+}
+
+// Pack of the given objects, each stored whole (no deltas), along with what is needed to index it
+pub fn build_pack(
+    repository: &Repository,
+    object_hashes: &[String],
+) -> Result<(Vec<u8>, Vec<PackedObject>, String), GitError> {
+    let mut seen = HashSet::new();
+    let object_hashes = object_hashes
+        .iter()
+        .filter(|object_hash| seen.insert(object_hash.as_str()))
+        .collect::<Vec<&String>>();
+
+    let mut pack = Vec::new();
+    pack.extend_from_slice(b"PACK");
+    pack.extend_from_slice(&2u32.to_be_bytes());
+    pack.extend_from_slice(&(object_hashes.len() as u32).to_be_bytes());
+
+    let mut objects = Vec::with_capacity(object_hashes.len());
+
+    for object_hash in object_hashes {
+        let mut reader = open_object(repository, object_hash)?;
+        let (object_type, size) = read_object_header(&mut reader, object_hash)?;
+        let content = read_object_content(&mut reader, size)?;
+
+        let offset = pack.len();
+
+        // See `read_pack_entry` for the layout of the type and size
+        let kind: u8 = match object_type {
+            ObjectType::Commit => 1,
+            ObjectType::Tree => 2,
+            ObjectType::Blob => 3,
+            ObjectType::Tag => 4,
+        };
+        let mut byte = (kind << 4) | (size & 0b1111) as u8;
+        let mut remaining = size >> 4;
+
+        while remaining > 0 {
+            pack.push(byte | 0x80);
+            byte = (remaining & 0x7f) as u8;
+            remaining >>= 7;
+        }
+        pack.push(byte);
+
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&content)?;
+        pack.extend_from_slice(&encoder.finish()?);
+
+        objects.push(PackedObject {
+            offset: offset as u64,
+            crc32: crc32(&pack[offset..]),
+            object_type,
+            content,
+            hash: object_hash.to_string(),
+        });
+    }
+
+    let mut hasher = repository.object_format().hasher();
+    hasher.update(&pack);
+    let pack_checksum = hasher.finalize();
+    pack.extend_from_slice(&pack_checksum);
+
+    return Ok((pack, objects, hex::encode(pack_checksum)));
+}
@@ -0,0 +1,305 @@
+use std::{
+    fs,
+    io::BufRead,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+    time::Duration,
+};
+
+use crate::{
+    abbreviate_object_hash, check_object, checkout_entry, diff_trees, find_git_dir, git_dir_at,
+    index_entry_from_file, index_from_tree, list_refs, merge_bases, object_exists, open_object,
+    prunable_objects, read_commit_object, read_git_object, read_identity, read_index,
+    read_object_format, read_pack_indexes, read_symbolic_ref, read_tree, read_verified_content,
+    resolve_object_hash, resolve_object_hash_of_type, resolve_revision, rev_list, unpack_objects,
+    update_ref, write_index, write_object_file, write_symbolic_ref, write_tree,
+    write_tree_from_index, CommitObject, Config, Filters, GitError, GitObject, Index, IndexEntry,
+    ObjectFormat, ObjectType, PackIndex, TreeChange, TreeObject,
+};
+
+// A resolved git directory, every object and ref lookup goes through it
+pub struct Repository {
+    git_dir: PathBuf,
+    // Read from the config once, it can't change after the repository is created
+    object_format: ObjectFormat,
+    // Read on first use, packs written afterwards by this process aren't seen
+    packs: OnceLock<Vec<PackIndex>>,
+}
+
+impl Repository {
+    fn at(git_dir: PathBuf) -> Result<Repository, GitError> {
+        let object_format = read_object_format(&git_dir)?;
+
+        return Ok(Repository {
+            git_dir,
+            object_format,
+            packs: OnceLock::new(),
+        });
+    }
+
+    // `path` is either the root of a repository or a git directory itself
+    pub fn open(path: impl AsRef<Path>) -> Result<Repository, GitError> {
+        let path = path.as_ref();
+
+        match git_dir_at(path)? {
+            Some(git_dir) => return Repository::at(git_dir),
+            // A linked worktree's git directory only has a `commondir` pointing to the objects
+            None if path.join("objects").is_dir() || path.join("commondir").is_file() => {
+                return Repository::at(path.to_path_buf())
+            }
+            None => return Err(GitError::NotARepository),
+        }
+    }
+
+    // Looks for a repository from the current directory upwards, see `find_git_dir`
+    pub fn discover() -> Result<Repository, GitError> {
+        return Repository::at(find_git_dir()?);
+    }
+
+    // Lays out an empty repository in `git_dir`, with `HEAD` on a yet unborn `initial_branch`
+    // The object format is an extension, which requires version 1 of the repository format so
+    // that versions of git predating it refuse the repository instead of misreading it
+    pub fn init(
+        git_dir: impl Into<PathBuf>,
+        bare: bool,
+        initial_branch: &str,
+        object_format: ObjectFormat,
+    ) -> Result<Repository, GitError> {
+        let git_dir = git_dir.into();
+
+        fs::create_dir_all(&git_dir)?;
+        fs::create_dir(git_dir.join("objects"))?;
+        fs::create_dir(git_dir.join("refs"))?;
+        fs::write(
+            git_dir.join("HEAD"),
+            format!("ref: refs/heads/{initial_branch}\n"),
+        )?;
+        fs::write(
+            git_dir.join("config"),
+            format!(
+                "[core]\n\trepositoryformatversion = 1\n\tbare = {bare}\n\
+                 [extensions]\n\tobjectformat = {}\n",
+                object_format.as_str()
+            ),
+        )?;
+
+        return Ok(Repository {
+            git_dir,
+            object_format,
+            packs: OnceLock::new(),
+        });
+    }
+
+    pub fn git_dir(self: &Repository) -> &Path {
+        return &self.git_dir;
+    }
+
+    // Hash function naming objects, from `extensions.objectformat`
+    pub fn object_format(self: &Repository) -> ObjectFormat {
+        return self.object_format;
+    }
+
+    // Indexes of every pack objects can be read from, see `read_pack_indexes`
+    pub fn packs(self: &Repository) -> Result<&[PackIndex], GitError> {
+        if let Some(packs) = self.packs.get() {
+            return Ok(packs);
+        }
+
+        let packs = read_pack_indexes(&self.git_dir, self.object_format)?;
+
+        return Ok(self.packs.get_or_init(|| packs));
+    }
+
+    pub fn resolve_object_hash(self: &Repository, prefix: &str) -> Result<String, GitError> {
+        return resolve_object_hash(self, prefix);
+    }
+
+    // Shortest unambiguous prefix of a full `object_hash`, at least `min_len` long
+    pub fn abbreviate_object_hash(
+        self: &Repository,
+        object_hash: &str,
+        min_len: usize,
+    ) -> Result<String, GitError> {
+        return abbreviate_object_hash(self, object_hash, min_len);
+    }
+
+    pub fn resolve_object_hash_of_type(
+        self: &Repository,
+        prefix: &str,
+        expected_type: ObjectType,
+    ) -> Result<String, GitError> {
+        return resolve_object_hash_of_type(self, prefix, expected_type);
+    }
+
+    // `object_hash` must be a full hash, see `resolve_object_hash` for abbreviated ones
+    pub fn open_object(self: &Repository, object_hash: &str) -> Result<Box<dyn BufRead>, GitError> {
+        return open_object(self, object_hash);
+    }
+
+    // `object_hash` may be abbreviated
+    pub fn read_object(self: &Repository, object_hash: &str) -> Result<GitObject, GitError> {
+        let object_hash = self.resolve_object_hash(object_hash)?;
+        let mut reader = self.open_object(&object_hash)?;
+
+        return read_git_object(&mut reader, &object_hash, self.object_format);
+    }
+
+    // Raw content of a full `object_hash`, checked against its hash
+    pub fn read_verified_content(
+        self: &Repository,
+        object_hash: &str,
+    ) -> Result<(ObjectType, Vec<u8>), GitError> {
+        return read_verified_content(self, object_hash);
+    }
+
+    // Checks a full `object_hash` is intact and everything it references is stored
+    pub fn check_object(self: &Repository, object_hash: &str) -> Result<(), GitError> {
+        return check_object(self, object_hash);
+    }
+
+    // Unreferenced loose objects older than `expire`, as `(hash, path)`
+    pub fn prunable_objects(
+        self: &Repository,
+        expire: Duration,
+    ) -> Result<Vec<(String, PathBuf)>, GitError> {
+        return prunable_objects(self, expire);
+    }
+
+    pub fn read_commit(self: &Repository, object_hash: &str) -> Result<CommitObject, GitError> {
+        return read_commit_object(self, object_hash);
+    }
+
+    // `starts` and their ancestors, newest first, as `(hash, commit)`
+    pub fn rev_list(
+        self: &Repository,
+        starts: &[String],
+        max_count: Option<usize>,
+    ) -> Result<Vec<(String, CommitObject)>, GitError> {
+        return rev_list(self, starts, max_count);
+    }
+
+    // Best common ancestors of two commits, newest first, empty if their histories are unrelated
+    pub fn merge_bases(
+        self: &Repository,
+        first: &str,
+        second: &str,
+    ) -> Result<Vec<String>, GitError> {
+        return merge_bases(self, first, second);
+    }
+
+    // Paths that differ between two full tree hashes, see `diff_trees`
+    pub fn diff_trees(
+        self: &Repository,
+        old_tree: &str,
+        new_tree: &str,
+        recursive: bool,
+    ) -> Result<Vec<TreeChange>, GitError> {
+        return diff_trees(self, old_tree, new_tree, recursive);
+    }
+
+    // Whether a full `object_hash` is stored, loose or packed
+    pub fn object_exists(self: &Repository, object_hash: &str) -> Result<bool, GitError> {
+        return object_exists(self, object_hash);
+    }
+
+    pub fn write_object(self: &Repository, object: &GitObject) -> Result<String, GitError> {
+        return write_object_file(&self.git_dir, object.pack()?, self.object_format);
+    }
+
+    pub fn read_tree(self: &Repository, object_hash: &str) -> Result<TreeObject, GitError> {
+        return read_tree(self, object_hash);
+    }
+
+    // Stores what is staged as a tree, only what is under `prefix` when it isn't empty
+    pub fn write_tree_from_index(self: &Repository, prefix: &str) -> Result<String, GitError> {
+        let index = self.read_index()?;
+
+        return write_tree_from_index(self, &index, prefix);
+    }
+
+    // Stores the content of `path` as a tree, recursively, see `write_tree` for `prefix`
+    pub fn write_tree(self: &Repository, path: PathBuf, prefix: &str) -> Result<String, GitError> {
+        return write_tree(self, path, prefix);
+    }
+
+    // Hash of a ref or object name, `None` if it doesn't name anything
+    pub fn resolve_ref(self: &Repository, name: &str) -> Result<Option<String>, GitError> {
+        return resolve_revision(self, name);
+    }
+
+    pub fn update_ref(
+        self: &Repository,
+        refname: &str,
+        new_value: &str,
+        old_value: Option<&str>,
+    ) -> Result<(), GitError> {
+        return update_ref(self, refname, new_value, old_value);
+    }
+
+    pub fn list_refs(self: &Repository) -> Result<Vec<(String, String)>, GitError> {
+        return list_refs(self);
+    }
+
+    pub fn read_symbolic_ref(self: &Repository, name: &str) -> Result<String, GitError> {
+        return read_symbolic_ref(&self.git_dir, name);
+    }
+
+    pub fn write_symbolic_ref(self: &Repository, name: &str, target: &str) -> Result<(), GitError> {
+        return write_symbolic_ref(&self.git_dir, name, target);
+    }
+
+    // Explodes a pack into loose objects, returning how many there were
+    pub fn unpack_objects(self: &Repository, pack: &[u8]) -> Result<usize, GitError> {
+        return unpack_objects(self, pack);
+    }
+
+    // The staging area, empty if nothing was ever staged
+    pub fn read_index(self: &Repository) -> Result<Index, GitError> {
+        return read_index(self);
+    }
+
+    // Read afresh on every call, so it reflects changes made since the repository was opened
+    pub fn read_config(self: &Repository) -> Result<Config, GitError> {
+        return Config::read(&self.git_dir);
+    }
+
+    // Conversions between the file at `path` and its blob, see `Filters::for_file`
+    pub fn filters(self: &Repository, work_tree: &Path, path: &str) -> Result<Filters, GitError> {
+        return Filters::for_file(&self.git_dir, work_tree, path);
+    }
+
+    // Replaces the staging area
+    pub fn write_index(self: &Repository, index: &Index) -> Result<(), GitError> {
+        return write_index(self, index);
+    }
+
+    // Everything in a full `tree_hash`, staged as is, see `index_from_tree`
+    pub fn index_from_tree(self: &Repository, tree_hash: &str) -> Result<Index, GitError> {
+        return index_from_tree(self, tree_hash);
+    }
+
+    // Stores the file at `path`, relative to the root of `work_tree`, and describes it as an index
+    // entry
+    pub fn index_entry_from_file(
+        self: &Repository,
+        work_tree: &Path,
+        path: &str,
+    ) -> Result<IndexEntry, GitError> {
+        return index_entry_from_file(self, work_tree, path);
+    }
+
+    // Writes a staged blob to `path`, see `checkout_entry`
+    pub fn checkout_entry(
+        self: &Repository,
+        work_tree: &Path,
+        entry: &IndexEntry,
+        path: &Path,
+    ) -> Result<(), GitError> {
+        return checkout_entry(self, work_tree, entry, path);
+    }
+
+    // `role` is either `AUTHOR` or `COMMITTER`
+    pub fn read_identity(self: &Repository, role: &str) -> Result<(String, String), GitError> {
+        return read_identity(&self.git_dir, role);
+    }
+}
@@ -0,0 +1,301 @@
+mod common;
+
+use common::*;
+use git_starter_rust::{GitError, Repository};
+
+// A blob, written by git
+fn repository_with_blob() -> (TempDir, String) {
+    let directory = git_repository();
+    directory.write("hello.txt", "hello world\n");
+    let blob_hash = git(directory.path(), &["hash-object", "-w", "hello.txt"]);
+
+    return (directory, blob_hash.trim_end().to_string());
+}
+
+#[test]
+fn cat_file_t_prints_the_type() {
+    let (directory, blob_hash) = repository_with_blob();
+
+    assert_eq!(
+        run_ok(directory.path(), &["cat-file", "-t", &blob_hash]),
+        "blob\n"
+    );
+
+    let missing = run(directory.path(), &["cat-file", "-t", &"0".repeat(40)]);
+    assert!(!missing.status.success());
+    assert!(!String::from_utf8_lossy(&missing.stderr).contains("panicked"));
+}
+
+#[test]
+fn cat_file_s_prints_the_size() {
+    let directory = git_repository();
+    directory.write("thirteen.txt", "twelve bytes\n");
+    let blob_hash = git(directory.path(), &["hash-object", "-w", "thirteen.txt"]);
+
+    assert_eq!(
+        run_ok(directory.path(), &["cat-file", "-s", blob_hash.trim_end()]),
+        "13\n"
+    );
+}
+
+#[test]
+fn cat_file_e_reports_existence_through_the_exit_status() {
+    let (directory, blob_hash) = repository_with_blob();
+
+    let present = run(directory.path(), &["cat-file", "-e", &blob_hash]);
+    assert!(present.status.success());
+    assert_eq!(present.stdout, b"");
+
+    let absent = run(directory.path(), &["cat-file", "-e", &"0".repeat(40)]);
+    assert!(!absent.status.success());
+    assert_eq!(absent.stdout, b"");
+
+    // Stored under the name of the blob, but not decompressible
+    let path = directory.join(format!(
+        ".git/objects/{}/{}",
+        &blob_hash[..2],
+        &blob_hash[2..]
+    ));
+    std::fs::remove_file(&path).unwrap();
+    std::fs::write(&path, "not zlib").unwrap();
+    assert!(!run(directory.path(), &["cat-file", "-e", &blob_hash])
+        .status
+        .success());
+}
+
+#[test]
+fn cat_file_p_prints_trees_like_git() {
+    let directory = git_repository();
+    directory.write("file.txt", "file\n");
+    directory.write("sub/nested.txt", "nested\n");
+    git(directory.path(), &["add", "."]);
+    let tree_hash = git(directory.path(), &["write-tree"]);
+    let tree_hash = tree_hash.trim_end();
+
+    let expected = git(directory.path(), &["cat-file", "-p", tree_hash]);
+    assert_eq!(
+        run_ok(directory.path(), &["cat-file", "-p", tree_hash]),
+        expected
+    );
+    assert!(expected.starts_with("100644 blob "));
+    assert!(expected.contains("\n040000 tree "));
+}
+
+#[test]
+fn cat_file_p_prints_commits_as_stored() {
+    let directory = git_repository();
+    let tree_hash = git(directory.path(), &["write-tree"]);
+    let commit_hash = run_ok(
+        directory.path(),
+        &[
+            "commit-tree",
+            tree_hash.trim_end(),
+            "-m",
+            "First line\n\nBody",
+        ],
+    );
+    let commit_hash = commit_hash.trim_end();
+
+    let expected = git(directory.path(), &["cat-file", "-p", commit_hash]);
+    assert_eq!(
+        run_ok(directory.path(), &["cat-file", "-p", commit_hash]),
+        expected
+    );
+    assert_eq!(
+        expected,
+        format!(
+            "tree {}\n\
+             author A U Thor <author@example.com> 1700000000 +0100\n\
+             committer C O Mitter <committer@example.com> 1700000100 +0100\n\
+             \n\
+             First line\n\nBody\n",
+            tree_hash.trim_end()
+        )
+    );
+}
+
+#[test]
+fn objects_are_found_by_abbreviated_hash() {
+    let (directory, blob_hash) = repository_with_blob();
+
+    assert_eq!(
+        run_ok(directory.path(), &["cat-file", "-p", &blob_hash[..7]]),
+        "hello world\n"
+    );
+
+    let output = run(directory.path(), &["cat-file", "-p", &blob_hash[..3]]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("too short"), "{stderr}");
+
+    let repository = Repository::open(directory.path()).unwrap();
+    assert_eq!(
+        repository.resolve_object_hash(&blob_hash[..4]).unwrap(),
+        blob_hash
+    );
+
+    let prefix = ambiguous_prefix(&repository);
+    let output = run(directory.path(), &["cat-file", "-t", &prefix]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("ambiguous"), "{stderr}");
+
+    assert!(matches!(
+        repository.resolve_object_hash(&prefix),
+        Err(GitError::AmbiguousObjectName(_))
+    ));
+}
+
+#[test]
+fn cat_file_p_prints_binary_blobs_byte_for_byte() {
+    let directory = git_repository();
+    let content = b"binary\0with \xff and \xfe\n\0";
+    let blob_hash = git_with_stdin(directory.path(), &["hash-object", "-w", "--stdin"], content);
+    let blob_hash = String::from_utf8(blob_hash).unwrap();
+
+    assert_eq!(
+        run_ok_with_stdin(
+            directory.path(),
+            &["cat-file", "-p", blob_hash.trim_end()],
+            b""
+        ),
+        content
+    );
+}
+
+// A blob and the tree holding it, along with a name that doesn't exist, one per line
+fn batch_input(directory: &TempDir) -> String {
+    directory.write("file.txt", "file\n");
+    git(directory.path(), &["add", "file.txt"]);
+    let tree_hash = git(directory.path(), &["write-tree"]);
+    let blob_hash = git(directory.path(), &["rev-parse", ":file.txt"]);
+
+    return format!("{blob_hash}{tree_hash}{}\n", "1".repeat(40));
+}
+
+#[test]
+fn cat_file_batch_prints_headers_and_contents() {
+    let directory = git_repository();
+    let input = batch_input(&directory);
+    let args = ["cat-file", "--batch"];
+
+    let output = run_ok_with_stdin(directory.path(), &args, input.as_bytes());
+    assert_eq!(
+        output,
+        git_with_stdin(directory.path(), &args, input.as_bytes())
+    );
+
+    let blob_hash = input.lines().next().unwrap();
+    let expected_start = format!("{blob_hash} blob 5\nfile\n\n");
+    assert!(output.starts_with(expected_start.as_bytes()));
+    let expected_end = format!("{} missing\n", "1".repeat(40));
+    assert!(output.ends_with(expected_end.as_bytes()));
+}
+
+#[test]
+fn cat_file_batch_check_prints_headers_only() {
+    let directory = git_repository();
+    let input = batch_input(&directory);
+    let args = ["cat-file", "--batch-check"];
+
+    let output = run_ok_with_stdin(directory.path(), &args, input.as_bytes());
+    assert_eq!(
+        output,
+        git_with_stdin(directory.path(), &args, input.as_bytes())
+    );
+
+    let output = String::from_utf8(output).unwrap();
+    let lines: Vec<&str> = output.lines().collect();
+    let names: Vec<&str> = input.lines().collect();
+    assert_eq!(lines[0], format!("{} blob 5", names[0]));
+    assert_eq!(lines[1], format!("{} tree 36", names[1]));
+    assert_eq!(lines[2], format!("{} missing", names[2]));
+}
+
+#[test]
+fn short_and_non_hex_names_are_errors_not_panics() {
+    let (directory, _) = repository_with_blob();
+
+    for name in ["a", "", "zzzzzzz", "é", "éé12345"] {
+        for args in [
+            &["cat-file", "-p", name][..],
+            &["cat-file", "-t", name],
+            &["ls-tree", name],
+        ] {
+            let output = run(directory.path(), args);
+            let stderr = String::from_utf8(output.stderr).unwrap();
+
+            assert!(!output.status.success(), "{args:?}");
+            assert!(!stderr.contains("panicked"), "{args:?}: {stderr}");
+        }
+    }
+}
+
+#[test]
+fn exit_codes_follow_git() {
+    let (directory, _) = repository_with_blob();
+    let missing = "1".repeat(40);
+
+    for args in [
+        &["cat-file", "-p", missing.as_str()][..],
+        &["cat-file", "-t", &missing],
+        &["cat-file", "-e", &missing],
+        &["cat-file", "-p"],
+        &["cat-file", "--no-such-option", &missing],
+    ] {
+        assert_eq!(
+            run(directory.path(), args).status.code(),
+            git_status(directory.path(), args),
+            "{args:?}"
+        );
+    }
+}
+
+#[test]
+fn cat_file_batch_all_objects_lists_every_stored_object() {
+    let directory = git_repository();
+    directory.write("file.txt", "file\n");
+    git(directory.path(), &["add", "file.txt"]);
+    let blob_hash = git(directory.path(), &["rev-parse", ":file.txt"]);
+    let tree_hash = git(directory.path(), &["write-tree"]);
+    let commit_hash = git(
+        directory.path(),
+        &["commit-tree", tree_hash.trim_end(), "-m", "Commit"],
+    );
+
+    let args = ["cat-file", "--batch-check", "--batch-all-objects"];
+    let output = run_ok(directory.path(), &args);
+    assert_eq!(output, git(directory.path(), &args));
+
+    let commit_size = git(
+        directory.path(),
+        &["cat-file", "-s", commit_hash.trim_end()],
+    );
+    let mut expected = vec![
+        format!("{} blob 5", blob_hash.trim_end()),
+        format!("{} tree 36", tree_hash.trim_end()),
+        format!(
+            "{} commit {}",
+            commit_hash.trim_end(),
+            commit_size.trim_end()
+        ),
+    ];
+    expected.sort();
+    assert_eq!(output.lines().collect::<Vec<&str>>(), expected);
+
+    // Packed objects are listed along with loose ones
+    git(
+        directory.path(),
+        &["update-ref", "refs/heads/main", commit_hash.trim_end()],
+    );
+    git(directory.path(), &["repack", "-a", "-d"]);
+    assert!(!directory
+        .join(".git/objects")
+        .join(&blob_hash[..2])
+        .exists());
+    directory.write("loose.txt", "loose\n");
+    git(directory.path(), &["hash-object", "-w", "loose.txt"]);
+    let output = run_ok(directory.path(), &args);
+    assert_eq!(output, git(directory.path(), &args));
+    assert_eq!(output.lines().count(), 4);
+}
@@ -0,0 +1,481 @@
+mod common;
+
+use std::time::{Duration, UNIX_EPOCH};
+
+use common::*;
+use git_starter_rust::{
+    pack_object, read_git_object, CommitObject, GitObject, ObjectType, Repository,
+};
+
+const EMPTY_TREE: &str = "4b825dc642cb6eb9a060e54bf8d69288fbee4904";
+
+// The commit git's `commit-tree` writes for `tree_hash` with the identities and dates of the tests
+fn commit_object(tree_hash: &str, parents: &[&str], commit_message: &str) -> CommitObject {
+    return CommitObject {
+        tree_hash: tree_hash.to_string(),
+        parents: parents.iter().map(|parent| parent.to_string()).collect(),
+        author_name: "A U Thor".to_string(),
+        author_email: "author@example.com".to_string(),
+        author_date_seconds: UNIX_EPOCH + Duration::from_secs(1700000000),
+        author_date_timezone: "+0100".to_string(),
+        committer_name: "C O Mitter".to_string(),
+        committer_email: "committer@example.com".to_string(),
+        committer_date_seconds: UNIX_EPOCH + Duration::from_secs(1700000100),
+        committer_date_timezone: "+0100".to_string(),
+        extra_headers: Vec::new(),
+        commit_message: commit_message.as_bytes().to_vec(),
+    };
+}
+
+#[test]
+fn written_commits_are_read_back_as_commits() {
+    let directory = git_repository();
+    let repository = Repository::open(directory.path()).unwrap();
+
+    let commit = commit_object(EMPTY_TREE, &[], "Initial commit\n");
+    let commit_hash = repository.write_object(&GitObject::Commit(commit)).unwrap();
+
+    let expected = git(
+        directory.path(),
+        &["commit-tree", EMPTY_TREE, "-m", "Initial commit"],
+    );
+    assert_eq!(format!("{commit_hash}\n"), expected);
+
+    let mut reader = repository.open_object(&commit_hash).unwrap();
+    match read_git_object(&mut reader, &commit_hash, repository.object_format()).unwrap() {
+        GitObject::Commit(commit) => {
+            assert_eq!(commit.tree_hash, EMPTY_TREE);
+            assert_eq!(commit.commit_message, b"Initial commit\n");
+        }
+        _ => panic!("{commit_hash} wasn't read as a commit"),
+    }
+}
+
+#[test]
+fn commits_in_another_encoding_are_read_back_byte_for_byte() {
+    let directory = git_repository();
+    let repository = Repository::open(directory.path()).unwrap();
+
+    // `é` in ISO-8859-1, which isn't valid UTF-8
+    let content = [
+        format!("tree {EMPTY_TREE}\n").as_bytes(),
+        b"author A U Thor <author@example.com> 1700000000 +0100\n",
+        b"committer C O Mitter <committer@example.com> 1700000100 +0100\n",
+        b"encoding ISO-8859-1\n",
+        b"\n",
+        b"Caf\xe9\n\nMore caf\xe9\n",
+    ]
+    .concat();
+    let commit_hash = git_with_stdin(
+        directory.path(),
+        &["hash-object", "-t", "commit", "-w", "--stdin"],
+        &content,
+    );
+    let commit_hash = String::from_utf8(commit_hash).unwrap();
+    let commit_hash = commit_hash.trim_end();
+
+    let commit = repository.read_commit(commit_hash).unwrap();
+    assert_eq!(
+        commit.extra_headers,
+        vec![("encoding".to_string(), b"ISO-8859-1".to_vec())]
+    );
+    assert_eq!(commit.commit_message, b"Caf\xe9\n\nMore caf\xe9\n");
+
+    let written_hash = repository.write_object(&GitObject::Commit(commit)).unwrap();
+    assert_eq!(written_hash, commit_hash);
+}
+
+#[test]
+fn messages_without_a_trailing_newline_are_kept_as_is() {
+    let directory = git_repository();
+    let repository = Repository::open(directory.path()).unwrap();
+
+    let content = [
+        format!("tree {EMPTY_TREE}\n").as_bytes(),
+        b"author A U Thor <author@example.com> 1700000000 +0100\n",
+        b"committer C O Mitter <committer@example.com> 1700000100 +0100\n",
+        b"\n",
+        b"No newline",
+    ]
+    .concat();
+    let commit_hash = git_with_stdin(
+        directory.path(),
+        &["hash-object", "-t", "commit", "-w", "--stdin"],
+        &content,
+    );
+    let commit_hash = String::from_utf8(commit_hash).unwrap();
+    let commit_hash = commit_hash.trim_end();
+
+    let commit = repository.read_commit(commit_hash).unwrap();
+    assert_eq!(commit.commit_message, b"No newline");
+
+    let written_hash = repository.write_object(&GitObject::Commit(commit)).unwrap();
+    assert_eq!(written_hash, commit_hash);
+}
+
+#[test]
+fn commit_tree_reads_the_message_from_stdin() {
+    let directory = git_repository();
+    let message = "Café from stdin\n\nBody\n".as_bytes();
+
+    assert_eq!(
+        run_ok_with_stdin(directory.path(), &["commit-tree", EMPTY_TREE], message),
+        git_with_stdin(directory.path(), &["commit-tree", EMPTY_TREE], message)
+    );
+}
+
+#[test]
+fn commit_tree_reads_the_message_from_a_file() {
+    let directory = git_repository();
+    directory.write("message", "Café from a file\n");
+
+    assert_eq!(
+        run_ok(
+            directory.path(),
+            &["commit-tree", EMPTY_TREE, "-F", "message"]
+        ),
+        git(
+            directory.path(),
+            &["commit-tree", EMPTY_TREE, "-F", "message"]
+        )
+    );
+    assert_eq!(
+        run_ok_with_stdin(
+            directory.path(),
+            &["commit-tree", EMPTY_TREE, "-F", "-"],
+            b"From stdin\n"
+        ),
+        git_with_stdin(
+            directory.path(),
+            &["commit-tree", EMPTY_TREE, "-F", "-"],
+            b"From stdin\n"
+        )
+    );
+}
+
+// git reencodes what isn't UTF-8 as if it were ISO-8859-1, the bytes are stored as given instead
+#[test]
+fn commit_tree_keeps_message_bytes_that_are_not_utf8() {
+    let directory = git_repository();
+    let repository = Repository::open(directory.path()).unwrap();
+    let message = b"Caf\xe9\n";
+    directory.write("message", message);
+
+    let from_file = run_ok(
+        directory.path(),
+        &["commit-tree", EMPTY_TREE, "-F", "message"],
+    );
+    let from_stdin = run_ok_with_stdin(directory.path(), &["commit-tree", EMPTY_TREE], message);
+    assert_eq!(from_file.as_bytes(), from_stdin);
+
+    let commit = repository.read_commit(from_file.trim_end()).unwrap();
+    assert_eq!(commit.commit_message, message);
+}
+
+// Message stored by commit-tree when given `message` on stdin
+fn commit_tree_message(message: &[u8]) -> Vec<u8> {
+    let directory = git_repository();
+    let repository = Repository::open(directory.path()).unwrap();
+
+    let commit_hash = run_ok_with_stdin(directory.path(), &["commit-tree", EMPTY_TREE], message);
+    let commit_hash = String::from_utf8(commit_hash).unwrap();
+
+    let (_, content) = repository
+        .read_verified_content(commit_hash.trim_end())
+        .unwrap();
+    let position = content.windows(2).position(|pair| pair == b"\n\n").unwrap();
+
+    return content[position + 2..].to_vec();
+}
+
+#[test]
+fn commit_tree_messages_end_with_exactly_one_newline() {
+    assert_eq!(commit_tree_message(b"Subject"), b"Subject\n");
+    assert_eq!(commit_tree_message(b"Subject\n"), b"Subject\n");
+    assert_eq!(commit_tree_message(b"Subject\n\n\n"), b"Subject\n");
+    assert_eq!(
+        commit_tree_message(b"Subject\n\nBody\n\n"),
+        b"Subject\n\nBody\n"
+    );
+}
+
+#[test]
+fn commit_tree_keeps_empty_messages_empty() {
+    assert_eq!(commit_tree_message(b""), b"");
+    assert_eq!(commit_tree_message(b"\n\n"), b"");
+}
+
+#[test]
+fn commit_tree_dates_commits_in_the_local_timezone() {
+    let directory = git_repository();
+    let repository = Repository::open(directory.path()).unwrap();
+
+    // POSIX timezones need no timezone database, offsets west of UTC are positive
+    for (timezone, expected) in [("IST-5:30", "+0530"), ("EST5", "-0500"), ("UTC0", "+0000")] {
+        let commit_hash = run_ok_in_timezone(
+            directory.path(),
+            &["commit-tree", EMPTY_TREE, "-m", timezone],
+            timezone,
+        );
+        let commit = repository.read_commit(commit_hash.trim_end()).unwrap();
+
+        assert_eq!(commit.author_date_timezone, expected);
+        assert_eq!(commit.committer_date_timezone, expected);
+    }
+}
+
+#[test]
+fn commits_are_parsed_with_any_number_of_parents() {
+    let directory = git_repository();
+    let repository = Repository::open(directory.path()).unwrap();
+    let commit_tree = |args: &[&str]| {
+        let commit_hash = git(
+            directory.path(),
+            &[&["commit-tree", EMPTY_TREE], args].concat(),
+        );
+
+        return commit_hash.trim_end().to_string();
+    };
+
+    let root = commit_tree(&["-m", "Root\n\nWith a body\nover two lines"]);
+    let first = commit_tree(&["-p", &root, "-m", "First"]);
+    let second = commit_tree(&["-p", &root, "-m", "Second"]);
+    let merge = commit_tree(&["-p", &first, "-p", &second, "-m", "Merge"]);
+
+    let expected = [
+        (
+            &root,
+            commit_object(EMPTY_TREE, &[], "Root\n\nWith a body\nover two lines\n"),
+        ),
+        (&first, commit_object(EMPTY_TREE, &[&root], "First\n")),
+        (
+            &merge,
+            commit_object(EMPTY_TREE, &[&first, &second], "Merge\n"),
+        ),
+    ];
+    for (commit_hash, expected) in expected {
+        let commit = repository.read_commit(commit_hash).unwrap();
+
+        assert_eq!(commit.parents, expected.parents);
+        assert_eq!(commit.commit_message, expected.commit_message);
+        // Every other field, from the tree to the timezones
+        assert_eq!(commit.pack().unwrap(), expected.pack().unwrap());
+    }
+}
+
+// The content of the commit `commit_hash`, as stored
+fn commit_content(directory: &TempDir, commit_hash: &str) -> String {
+    return git(
+        directory.path(),
+        &["cat-file", "commit", commit_hash.trim_end()],
+    );
+}
+
+#[test]
+fn commit_tree_writes_root_commits_without_parent_lines() {
+    let directory = git_repository();
+
+    let commit_hash = run_ok(directory.path(), &["commit-tree", EMPTY_TREE, "-m", "Root"]);
+    assert_eq!(
+        commit_hash,
+        git(directory.path(), &["commit-tree", EMPTY_TREE, "-m", "Root"])
+    );
+    assert!(!commit_content(&directory, &commit_hash).contains("\nparent "));
+}
+
+#[test]
+fn commit_tree_writes_merge_commits_with_parents_in_order() {
+    let directory = git_repository();
+    let first = git(
+        directory.path(),
+        &["commit-tree", EMPTY_TREE, "-m", "First"],
+    );
+    let first = first.trim_end();
+    let second = git(
+        directory.path(),
+        &["commit-tree", EMPTY_TREE, "-m", "Second"],
+    );
+    let second = second.trim_end();
+
+    let args = [
+        "commit-tree",
+        EMPTY_TREE,
+        "-p",
+        second,
+        "-p",
+        first,
+        "-m",
+        "Merge",
+    ];
+    let commit_hash = run_ok(directory.path(), &args);
+    assert_eq!(commit_hash, git(directory.path(), &args));
+    assert!(commit_content(&directory, &commit_hash)
+        .contains(&format!("\nparent {second}\nparent {first}\n")));
+
+    // Parents must be commits
+    let output = run(
+        directory.path(),
+        &[
+            "commit-tree",
+            EMPTY_TREE,
+            "-p",
+            first,
+            "-p",
+            EMPTY_TREE,
+            "-m",
+            "Merge",
+        ],
+    );
+    assert!(!output.status.success());
+}
+
+#[test]
+fn commit_tree_takes_identities_from_the_environment_then_the_config() {
+    let directory = git_repository();
+
+    let commit_hash = run_ok(directory.path(), &["commit-tree", EMPTY_TREE, "-m", "Env"]);
+    let content = commit_content(&directory, &commit_hash);
+    assert!(content.contains("\nauthor A U Thor <author@example.com> 1700000000 +0100\n"));
+    assert!(content.contains("\ncommitter C O Mitter <committer@example.com> 1700000100 +0100\n"));
+
+    let output = run_without_identity(directory.path(), &["commit-tree", EMPTY_TREE, "-m", "None"]);
+    assert!(!output.status.success());
+
+    git(directory.path(), &["config", "user.name", "Con Fig"]);
+    git(
+        directory.path(),
+        &["config", "user.email", "config@example.com"],
+    );
+    let output = run_without_identity(
+        directory.path(),
+        &["commit-tree", EMPTY_TREE, "-m", "Config"],
+    );
+    assert!(output.status.success());
+    let content = commit_content(&directory, &String::from_utf8(output.stdout).unwrap());
+    assert!(content.contains("\nauthor Con Fig <config@example.com> 1700000000 +0100\n"));
+    assert!(content.contains("\ncommitter Con Fig <config@example.com> 1700000100 +0100\n"));
+}
+
+#[test]
+fn packed_commits_wrap_emails_in_angle_brackets() {
+    let packed = commit_object(EMPTY_TREE, &[], "Message\n").pack().unwrap();
+
+    let content = [
+        format!("tree {EMPTY_TREE}\n").as_bytes(),
+        b"author A U Thor <author@example.com> 1700000000 +0100\n",
+        b"committer C O Mitter <committer@example.com> 1700000100 +0100\n",
+        b"\n",
+        b"Message\n",
+    ]
+    .concat();
+    assert_eq!(packed, pack_object(ObjectType::Commit, &content));
+}
+
+#[test]
+fn commit_tree_dates_commits_from_the_environment() {
+    let directory = git_repository();
+    let repository = Repository::open(directory.path()).unwrap();
+    let args = ["commit-tree", EMPTY_TREE, "-m", "Dated"];
+
+    let commit_hash = run_ok(directory.path(), &args);
+    std::thread::sleep(Duration::from_millis(1100));
+    assert_eq!(run_ok(directory.path(), &args), commit_hash);
+    assert_eq!(git(directory.path(), &args), commit_hash);
+
+    let commit = repository.read_commit(commit_hash.trim_end()).unwrap();
+    assert_eq!(
+        commit.author_date_seconds,
+        UNIX_EPOCH + Duration::from_secs(1700000000)
+    );
+    assert_eq!(
+        commit.committer_date_seconds,
+        UNIX_EPOCH + Duration::from_secs(1700000100)
+    );
+}
+
+#[test]
+fn commit_tree_rejects_objects_of_the_wrong_type() {
+    let directory = git_repository();
+    let blob_hash = git_with_stdin(
+        directory.path(),
+        &["hash-object", "-w", "--stdin"],
+        b"blob\n",
+    );
+    let blob_hash = String::from_utf8(blob_hash).unwrap();
+    let blob_hash = blob_hash.trim_end();
+    let objects_before = git(directory.path(), &["count-objects"]);
+
+    let output = run(directory.path(), &["commit-tree", blob_hash, "-m", "Blob"]);
+    assert!(!output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap().trim_end(),
+        format!("fatal: {blob_hash} is a blob object, expected a tree")
+    );
+
+    let args = [
+        "commit-tree",
+        EMPTY_TREE,
+        "-p",
+        blob_hash,
+        "-m",
+        "Blob parent",
+    ];
+    let output = run(directory.path(), &args);
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("expected a commit"));
+
+    // A tree that isn't stored is rejected as well
+    let missing = "0123456789012345678901234567890123456789";
+    assert!(
+        !run(directory.path(), &["commit-tree", missing, "-m", "Missing"])
+            .status
+            .success()
+    );
+
+    // Nothing was written
+    assert_eq!(git(directory.path(), &["count-objects"]), objects_before);
+}
+
+#[test]
+fn signed_commits_are_read_back_byte_for_byte() {
+    let directory = git_repository();
+    let repository = Repository::open(directory.path()).unwrap();
+
+    // The signature of `git commit -S`, its blank line is a lone space
+    let signature = "-----BEGIN PGP SIGNATURE-----\n\n\
+                     iQEzBAABCAAdFiEEexampleexampleexampleexample\n\
+                     =AbCd\n\
+                     -----END PGP SIGNATURE-----";
+    let content = format!(
+        "tree {EMPTY_TREE}\n\
+         author A U Thor <author@example.com> 1700000000 +0100\n\
+         committer C O Mitter <committer@example.com> 1700000100 +0100\n\
+         gpgsig {}\n\
+         \n\
+         Signed\n",
+        signature.replace('\n', "\n ")
+    );
+    let commit_hash = git_with_stdin(
+        directory.path(),
+        &["hash-object", "-t", "commit", "-w", "--stdin"],
+        content.as_bytes(),
+    );
+    let commit_hash = String::from_utf8(commit_hash).unwrap();
+    let commit_hash = commit_hash.trim_end();
+
+    let commit = repository.read_commit(commit_hash).unwrap();
+    assert_eq!(
+        commit.extra_headers,
+        vec![("gpgsig".to_string(), signature.as_bytes().to_vec())]
+    );
+    assert_eq!(commit.commit_message, b"Signed\n");
+
+    let written_hash = repository.write_object(&GitObject::Commit(commit)).unwrap();
+    assert_eq!(written_hash, commit_hash);
+    assert_eq!(
+        run_ok(directory.path(), &["cat-file", "-p", commit_hash]),
+        git(directory.path(), &["cat-file", "-p", commit_hash])
+    );
+}
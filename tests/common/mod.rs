@@ -0,0 +1,230 @@
+// Helpers shared by the integration tests: throwaway directories, and running either this
+// implementation or git in them
+#![allow(dead_code)]
+
+use std::{
+    collections::HashMap,
+    fs,
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+    process::{Command, Output, Stdio},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use git_starter_rust::{BlobObject, GitObject, Repository};
+
+static TEMP_DIR_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+// A directory removed along with everything in it once dropped
+pub struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    pub fn new() -> TempDir {
+        let path = std::env::temp_dir().join(format!(
+            "git-starter-rust-test-{}-{}",
+            std::process::id(),
+            TEMP_DIR_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+
+        return TempDir { path };
+    }
+
+    pub fn path(self: &TempDir) -> &Path {
+        return &self.path;
+    }
+
+    pub fn join(self: &TempDir, path: impl AsRef<Path>) -> PathBuf {
+        return self.path.join(path);
+    }
+
+    // Creates the file at `path`, and its parents, with `content`
+    pub fn write(self: &TempDir, path: &str, content: impl AsRef<[u8]>) {
+        let path = self.path.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+// Identities and dates are fixed and user or system config is ignored, so that hashes don't
+// depend on who runs the tests or when
+fn command(program: &str, directory: &Path, args: &[&str]) -> Command {
+    let mut command = Command::new(program);
+    command
+        .args(args)
+        .current_dir(directory)
+        .env_remove("GIT_DIR")
+        .env("GIT_CONFIG_NOSYSTEM", "1")
+        .env("GIT_CONFIG_GLOBAL", "/dev/null")
+        .env("GIT_AUTHOR_NAME", "A U Thor")
+        .env("GIT_AUTHOR_EMAIL", "author@example.com")
+        .env("GIT_AUTHOR_DATE", "@1700000000 +0100")
+        .env("GIT_COMMITTER_NAME", "C O Mitter")
+        .env("GIT_COMMITTER_EMAIL", "committer@example.com")
+        .env("GIT_COMMITTER_DATE", "@1700000100 +0100");
+
+    return command;
+}
+
+fn output(mut command: Command, stdin: &[u8]) -> Output {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    // Commands failing early may exit without reading all of it
+    match child.stdin.take().unwrap().write_all(stdin) {
+        Err(error) if error.kind() == ErrorKind::BrokenPipe => {}
+        result => result.unwrap(),
+    }
+
+    return child.wait_with_output().unwrap();
+}
+
+fn expect_success(args: &[&str], output: Output) -> Vec<u8> {
+    assert!(
+        output.status.success(),
+        "`{}` failed with {}: {}",
+        args.join(" "),
+        output.status,
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    return output.stdout;
+}
+
+// Runs this implementation in `directory`, whatever the outcome
+pub fn run(directory: &Path, args: &[&str]) -> Output {
+    return run_with_stdin(directory, args, b"");
+}
+
+pub fn run_with_stdin(directory: &Path, args: &[&str], stdin: &[u8]) -> Output {
+    let command = command(env!("CARGO_BIN_EXE_git-starter-rust"), directory, args);
+
+    return output(command, stdin);
+}
+
+// Runs this implementation in `directory`, which must succeed, returning what it printed
+pub fn run_ok(directory: &Path, args: &[&str]) -> String {
+    return String::from_utf8(run_ok_with_stdin(directory, args, b"")).unwrap();
+}
+
+pub fn run_ok_with_stdin(directory: &Path, args: &[&str], stdin: &[u8]) -> Vec<u8> {
+    return expect_success(args, run_with_stdin(directory, args, stdin));
+}
+
+// Runs git in `directory`, which must succeed, returning what it printed
+pub fn git(directory: &Path, args: &[&str]) -> String {
+    return String::from_utf8(git_with_stdin(directory, args, b"")).unwrap();
+}
+
+pub fn git_with_stdin(directory: &Path, args: &[&str], stdin: &[u8]) -> Vec<u8> {
+    return expect_success(args, output(command("git", directory, args), stdin));
+}
+
+// An empty repository initialized by git, with `main` as its unborn branch
+pub fn git_repository() -> TempDir {
+    let directory = TempDir::new();
+    git(directory.path(), &["init", "-q", "-b", "main"]);
+
+    return directory;
+}
+
+// Deterministic bytes that don't compress, a xorshift generator seeded with `seed`
+pub fn random_bytes(length: usize, seed: u64) -> Vec<u8> {
+    let mut state = seed | 1;
+    let mut bytes = Vec::with_capacity(length);
+
+    while bytes.len() < length {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        bytes.extend_from_slice(&state.to_le_bytes());
+    }
+    bytes.truncate(length);
+
+    return bytes;
+}
+
+// Runs this implementation in `directory` with `TZ` set to `timezone` and without the dates of
+// the tests, which must succeed, returning what it printed
+pub fn run_ok_in_timezone(directory: &Path, args: &[&str], timezone: &str) -> String {
+    let mut command = command(env!("CARGO_BIN_EXE_git-starter-rust"), directory, args);
+    command
+        .env_remove("GIT_AUTHOR_DATE")
+        .env_remove("GIT_COMMITTER_DATE")
+        .env("TZ", timezone);
+
+    return String::from_utf8(expect_success(args, output(command, b""))).unwrap();
+}
+
+// Blobs are written until two of them share their first 4 hex digits
+pub fn ambiguous_prefix(repository: &Repository) -> String {
+    let mut prefixes = HashMap::new();
+
+    for i in 0.. {
+        let blob = GitObject::Blob(BlobObject {
+            data: format!("blob {i}\n").into_bytes(),
+        });
+        let object_hash = repository.write_object(&blob).unwrap();
+
+        if prefixes.insert(object_hash[..4].to_string(), i).is_some() {
+            return object_hash[..4].to_string();
+        }
+    }
+
+    unreachable!();
+}
+
+// Runs this implementation in `directory` with `GIT_DIR` set to `git_dir`, which must succeed,
+// returning what it printed
+pub fn run_ok_with_git_dir(directory: &Path, args: &[&str], git_dir: &Path) -> String {
+    let mut command = command(env!("CARGO_BIN_EXE_git-starter-rust"), directory, args);
+    command.env("GIT_DIR", git_dir);
+
+    return String::from_utf8(expect_success(args, output(command, b""))).unwrap();
+}
+
+// Runs this implementation in `directory` without the identities of the tests, whatever the
+// outcome
+pub fn run_without_identity(directory: &Path, args: &[&str]) -> Output {
+    let mut command = command(env!("CARGO_BIN_EXE_git-starter-rust"), directory, args);
+    for name in [
+        "GIT_AUTHOR_NAME",
+        "GIT_AUTHOR_EMAIL",
+        "GIT_COMMITTER_NAME",
+        "GIT_COMMITTER_EMAIL",
+        "EMAIL",
+    ] {
+        command.env_remove(name);
+    }
+
+    return output(command, b"");
+}
+
+// Runs git in `directory` with both dates set to `date`, which must succeed, returning what it
+// printed
+pub fn git_at_date(directory: &Path, args: &[&str], date: &str) -> String {
+    let mut command = command("git", directory, args);
+    command
+        .env("GIT_AUTHOR_DATE", date)
+        .env("GIT_COMMITTER_DATE", date);
+
+    return String::from_utf8(expect_success(args, output(command, b""))).unwrap();
+}
+
+// Runs git in `directory`, returning its exit code whatever the outcome
+pub fn git_status(directory: &Path, args: &[&str]) -> Option<i32> {
+    return output(command("git", directory, args), b"").status.code();
+}
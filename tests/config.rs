@@ -0,0 +1,72 @@
+mod common;
+
+use common::*;
+use git_starter_rust::{Config, GitError};
+
+const CONFIG: &str = "\
+# Written by hand
+[core]
+\trepositoryformatversion = 0
+\tfilemode = true
+\tBare = false
+\tlogallrefupdates
+[user]
+\tname = \"A U Thor\"  ; trailing comment
+\temail = author@example.com # another one
+[remote \"Origin\"]
+\turl = https://example.com/repo.git
+\tfetch = +refs/heads/*:refs/remotes/Origin/* \\
+continued
+[branch.Main]
+\tremote = Origin
+[user]
+\tname = Last Wins
+";
+
+#[test]
+fn config_values_are_read_like_git() {
+    let directory = TempDir::new();
+    directory.write("config", CONFIG);
+    let config = Config::parse(CONFIG).unwrap();
+
+    for (section, subsection, key) in [
+        ("core", None, "repositoryformatversion"),
+        ("core", None, "bare"),
+        ("CORE", None, "BARE"),
+        ("user", None, "name"),
+        ("user", None, "email"),
+        ("remote", Some("Origin"), "url"),
+        ("remote", Some("Origin"), "fetch"),
+        ("branch", Some("main"), "remote"),
+    ] {
+        let name = match subsection {
+            Some(subsection) => format!("{section}.{subsection}.{key}"),
+            None => format!("{section}.{key}"),
+        };
+        let expected = git(directory.path(), &["config", "--file", "config", &name]);
+
+        assert_eq!(
+            config.get(section, subsection, key),
+            Some(expected.strip_suffix('\n').unwrap()),
+            "{name}"
+        );
+    }
+
+    assert_eq!(config.get("user", None, "name"), Some("Last Wins"));
+    assert_eq!(config.get("core", None, "logallrefupdates"), Some("true"));
+    // Subsections are case sensitive, unless written `[section.subsection]`
+    assert_eq!(config.get("remote", Some("origin"), "url"), None);
+    assert_eq!(config.get("user", None, "missing"), None);
+}
+
+#[test]
+fn keys_outside_of_a_section_are_invalid() {
+    assert!(matches!(
+        Config::parse("[core]\n\tbare = false\n[broken\n"),
+        Err(GitError::InvalidConfig(3))
+    ));
+    assert!(matches!(
+        Config::parse("bare = false\n"),
+        Err(GitError::InvalidConfig(1))
+    ));
+}
@@ -0,0 +1,255 @@
+mod common;
+
+use common::*;
+
+#[test]
+fn hash_object_hashes_stdin() {
+    let directory = git_repository();
+    // The well-known hash of the blob `hello world\n`
+    let expected = "3b18e512dba79e4c8300dd08aeb37f8e728b8dad\n";
+
+    for args in [
+        &["hash-object", "--stdin"][..],
+        &["hash-object", "-"],
+        &["hash-object", "-w", "--stdin"],
+    ] {
+        assert_eq!(
+            run_ok_with_stdin(directory.path(), args, b"hello world\n"),
+            expected.as_bytes()
+        );
+    }
+    assert_eq!(
+        git(directory.path(), &["cat-file", "-p", expected.trim_end()]),
+        "hello world\n"
+    );
+}
+
+#[test]
+fn hash_object_without_w_stores_nothing() {
+    let directory = git_repository();
+    directory.write("file.txt", "not stored\n");
+
+    assert_eq!(
+        run_ok(directory.path(), &["hash-object", "file.txt"]),
+        git(directory.path(), &["hash-object", "file.txt"])
+    );
+    assert_eq!(
+        git(directory.path(), &["count-objects"]),
+        "0 objects, 0 kilobytes\n"
+    );
+}
+
+#[test]
+fn hash_object_t_hashes_other_object_types() {
+    let directory = git_repository();
+    let commit = b"tree 4b825dc642cb6eb9a060e54bf8d69288fbee4904\n\
+                   author A U Thor <author@example.com> 1700000000 +0100\n\
+                   committer C O Mitter <committer@example.com> 1700000100 +0100\n\
+                   \n\
+                   Initial commit\n";
+    directory.write("commit.txt", commit);
+
+    let args = ["hash-object", "-t", "commit", "commit.txt"];
+    let expected = git(directory.path(), &args);
+    assert_eq!(run_ok(directory.path(), &args), expected);
+    assert_eq!(
+        expected,
+        git(
+            directory.path(),
+            &[
+                "commit-tree",
+                "4b825dc642cb6eb9a060e54bf8d69288fbee4904",
+                "-m",
+                "Initial commit"
+            ]
+        )
+    );
+    assert_ne!(
+        expected,
+        git(directory.path(), &["hash-object", "commit.txt"])
+    );
+}
+
+#[test]
+fn hash_object_prints_one_hash_per_file_in_order() {
+    let directory = git_repository();
+    directory.write("c.txt", "c\n");
+    directory.write("a.txt", "a\n");
+    directory.write("b.txt", "b\n");
+
+    let args = ["hash-object", "-w", "c.txt", "a.txt", "b.txt"];
+    let output = run_ok(directory.path(), &args);
+    assert_eq!(output.lines().count(), 3);
+    assert_eq!(
+        output,
+        git(
+            directory.path(),
+            &["hash-object", "c.txt", "a.txt", "b.txt"]
+        )
+    );
+    for object_hash in output.lines() {
+        assert_eq!(
+            git(directory.path(), &["cat-file", "-t", object_hash]),
+            "blob\n"
+        );
+    }
+}
+
+#[test]
+fn hash_object_stdin_paths_hashes_every_listed_file() {
+    let directory = git_repository();
+    directory.write("a.txt", "a\n");
+    directory.write("sub/b.txt", "b\n");
+
+    let args = ["hash-object", "--stdin-paths"];
+    let expected = git_with_stdin(directory.path(), &args, b"a.txt\nsub/b.txt\n");
+    assert_eq!(
+        run_ok_with_stdin(directory.path(), &args, b"a.txt\nsub/b.txt\n"),
+        expected
+    );
+    assert_eq!(expected.split(|&byte| byte == b'\n').count(), 3);
+
+    let output = run_with_stdin(
+        directory.path(),
+        &["hash-object", "--stdin-paths", "a.txt"],
+        b"sub/b.txt\n",
+    );
+    assert!(!output.status.success());
+}
+
+#[test]
+fn hash_object_no_filters_hashes_raw_bytes() {
+    let directory = git_repository();
+    git(directory.path(), &["config", "core.autocrlf", "true"]);
+    directory.write("crlf.txt", "line\r\n");
+
+    let raw = git_with_stdin(directory.path(), &["hash-object", "--stdin"], b"line\r\n");
+    let args = ["hash-object", "--no-filters", "crlf.txt"];
+    assert_eq!(run_ok(directory.path(), &args).into_bytes(), raw);
+    assert_eq!(git(directory.path(), &args).into_bytes(), raw);
+
+    assert_ne!(
+        run_ok(directory.path(), &["hash-object", "crlf.txt"]).into_bytes(),
+        raw
+    );
+}
+
+#[test]
+fn hash_object_normalizes_line_endings_with_autocrlf() {
+    let directory = git_repository();
+    directory.write("crlf.txt", "one\r\ntwo\r\n");
+    directory.write("binary.bin", b"\0one\r\ntwo\r\n");
+    let args = ["hash-object", "crlf.txt", "binary.bin"];
+
+    let without = run_ok(directory.path(), &args);
+    assert_eq!(without, git(directory.path(), &args));
+
+    git(directory.path(), &["config", "core.autocrlf", "true"]);
+    let with = run_ok(directory.path(), &args);
+    assert_eq!(with, git(directory.path(), &args));
+
+    let (without, with): (Vec<&str>, Vec<&str>) =
+        (without.lines().collect(), with.lines().collect());
+    // Text is stored with LF line endings, binary content is left alone
+    assert_eq!(
+        format!("{}\n", with[0]).into_bytes(),
+        git_with_stdin(
+            directory.path(),
+            &["hash-object", "--stdin", "--no-filters"],
+            b"one\ntwo\n"
+        )
+    );
+    assert_ne!(with[0], without[0]);
+    assert_eq!(with[1], without[1]);
+}
+
+#[test]
+fn checkout_index_converts_line_endings_back_with_autocrlf() {
+    let directory = git_repository();
+    git(directory.path(), &["config", "core.autocrlf", "true"]);
+    directory.write("crlf.txt", "one\r\ntwo\r\n");
+    git(directory.path(), &["add", "crlf.txt"]);
+    std::fs::remove_file(directory.join("crlf.txt")).unwrap();
+
+    run_ok(directory.path(), &["checkout-index", "crlf.txt"]);
+    assert_eq!(
+        std::fs::read(directory.join("crlf.txt")).unwrap(),
+        b"one\r\ntwo\r\n"
+    );
+}
+
+#[test]
+fn hash_object_w_stores_files_of_any_size_like_git() {
+    let directory = git_repository();
+    directory.write("empty.txt", "");
+    directory.write("small.txt", "small\n");
+    directory.write("large.bin", random_bytes(1024 * 1024, 81));
+
+    let files = ["empty.txt", "small.txt", "large.bin"];
+    let object_hashes = run_ok(
+        directory.path(),
+        &[&["hash-object", "-w"][..], &files].concat(),
+    );
+    assert_eq!(
+        object_hashes,
+        git(directory.path(), &[&["hash-object"][..], &files].concat())
+    );
+
+    for (file, object_hash) in files.iter().zip(object_hashes.lines()) {
+        assert_eq!(
+            git_with_stdin(directory.path(), &["cat-file", "blob", object_hash], b""),
+            std::fs::read(directory.join(file)).unwrap()
+        );
+    }
+}
+
+#[test]
+fn hash_object_path_filters_stdin_as_that_file() {
+    let directory = git_repository();
+    git(directory.path(), &["config", "core.autocrlf", "true"]);
+    let normalized = git_with_stdin(directory.path(), &["hash-object", "--stdin"], b"one\ntwo\n");
+
+    let args = ["hash-object", "--stdin", "--path=x.txt"];
+    let output = run_ok_with_stdin(directory.path(), &args, b"one\r\ntwo\r\n");
+    assert_eq!(output, normalized);
+    assert_eq!(
+        output,
+        git_with_stdin(directory.path(), &args, b"one\r\ntwo\r\n")
+    );
+
+    // Without `--path` content read from stdin is hashed as is
+    let args = ["hash-object", "--stdin"];
+    assert_ne!(
+        run_ok_with_stdin(directory.path(), &args, b"one\r\ntwo\r\n"),
+        normalized
+    );
+}
+
+#[test]
+fn printed_hashes_end_with_a_newline_like_git() {
+    let directory = git_repository();
+    directory.write("file.txt", "file\n");
+    git(directory.path(), &["add", "file.txt"]);
+
+    let hash_object = run_ok(directory.path(), &["hash-object", "-w", "file.txt"]);
+    let write_tree = run_ok(directory.path(), &["write-tree"]);
+    let commit_tree = ["commit-tree", write_tree.trim_end(), "-m", "Message"];
+
+    for (output, args) in [
+        (hash_object, &["hash-object", "-w", "file.txt"][..]),
+        (write_tree.clone(), &["write-tree"]),
+        (run_ok(directory.path(), &commit_tree), &commit_tree),
+    ] {
+        assert_eq!(output.len(), 41, "{output:?}");
+        assert!(output.ends_with('\n'), "{output:?}");
+        assert_eq!(output, git(directory.path(), args), "{args:?}");
+    }
+
+    for args in [
+        &["cat-file", "-t", write_tree.trim_end()][..],
+        &["cat-file", "-s", write_tree.trim_end()],
+        &["cat-file", "-p", write_tree.trim_end()],
+    ] {
+        assert_eq!(run_ok(directory.path(), args), git(directory.path(), args));
+    }
+}
@@ -0,0 +1,150 @@
+mod common;
+
+use std::fs;
+
+use common::*;
+
+// `root - first - merge` on `main`, with `merge` also a child of `side`, a child of `root`
+// Every commit is dated after its parents
+fn repository_with_history() -> (TempDir, [String; 4]) {
+    let directory = git_repository();
+    let commit = |message: &str, parents: &[&str], seconds: u64| {
+        directory.write("file.txt", message);
+        git(directory.path(), &["add", "file.txt"]);
+        let tree_hash = git(directory.path(), &["write-tree"]);
+
+        let mut args = vec!["commit-tree", tree_hash.trim_end(), "-m", message];
+        for parent in parents {
+            args.extend(["-p", parent]);
+        }
+        let commit_hash = git_at_date(directory.path(), &args, &format!("@{seconds} +0100"));
+
+        return commit_hash.trim_end().to_string();
+    };
+
+    let root = commit("root", &[], 1700000000);
+    let first = commit("first", &[&root], 1700000100);
+    let side = commit("side", &[&root], 1700000200);
+    let merge = commit("merge", &[&first, &side], 1700000300);
+    git(directory.path(), &["update-ref", "refs/heads/main", &merge]);
+    git(directory.path(), &["update-ref", "refs/heads/side", &side]);
+
+    return (directory, [root, first, side, merge]);
+}
+
+#[test]
+fn rev_list_walks_linear_and_branched_histories_like_git() {
+    let (directory, [root, first, _, merge]) = repository_with_history();
+
+    for args in [
+        &["rev-list", first.as_str()][..],
+        &["rev-list", merge.as_str()],
+        &["rev-list", "main"],
+        &["rev-list", "-n", "2", "main"],
+        &["rev-list", "--max-count", "3", "main", "side"],
+    ] {
+        assert_eq!(run_ok(directory.path(), args), git(directory.path(), args));
+    }
+
+    let output = run_ok(directory.path(), &["rev-list", &merge]);
+    assert_eq!(output.lines().count(), 4);
+    assert_eq!(output.lines().filter(|line| *line == root).count(), 1);
+}
+
+#[test]
+fn log_prints_commits_newest_first_like_git() {
+    let (directory, [root, first, _, _]) = repository_with_history();
+    git(
+        directory.path(),
+        &["update-ref", "refs/heads/linear", &first],
+    );
+
+    let output = run_ok(directory.path(), &["log", "linear"]);
+    assert_eq!(output, git(directory.path(), &["log", "linear"]));
+    assert!(output.starts_with(&format!("commit {first}\n")), "{output}");
+    assert!(output.contains(&format!("\n\ncommit {root}\n")), "{output}");
+    assert!(output.ends_with("\n\n    root\n"), "{output}");
+
+    for args in [&["log"][..], &["log", "-n", "1", "main"]] {
+        assert_eq!(run_ok(directory.path(), args), git(directory.path(), args));
+    }
+}
+
+#[test]
+fn merge_base_finds_the_fork_point() {
+    let (directory, [root, first, side, merge]) = repository_with_history();
+
+    assert_eq!(
+        run_ok(directory.path(), &["merge-base", &first, &side]),
+        format!("{root}\n")
+    );
+    assert_eq!(
+        run_ok(directory.path(), &["merge-base", &merge, &side]),
+        format!("{side}\n")
+    );
+    for args in [
+        &["merge-base", "main", "side"][..],
+        &["merge-base", "-a", &first, &side],
+    ] {
+        assert_eq!(run_ok(directory.path(), args), git(directory.path(), args));
+    }
+
+    // An unrelated root commit
+    let unrelated = git(
+        directory.path(),
+        &[
+            "commit-tree",
+            "-m",
+            "unrelated",
+            "4b825dc642cb6eb9a060e54bf8d69288fbee4904",
+        ],
+    );
+    let output = run(
+        directory.path(),
+        &["merge-base", unrelated.trim_end(), &merge],
+    );
+    assert!(!output.status.success());
+    assert_eq!(output.stdout, b"");
+}
+
+#[test]
+fn log_reports_merges_with_malformed_parents_as_errors() {
+    let (directory, [root, _, _, _]) = repository_with_history();
+    let tree_hash = git(
+        directory.path(),
+        &["rev-parse", &format!("{root}^{{tree}}")],
+    );
+
+    // A parent named by an abbreviated hash, stored under that name so that it can be read, git
+    // only writes such a commit when told not to check it
+    let objects_dir = directory.join(".git/objects");
+    fs::copy(
+        objects_dir.join(&root[..2]).join(&root[2..]),
+        objects_dir.join(&root[..2]).join(&root[2..5]),
+    )
+    .unwrap();
+    let content = format!(
+        "tree {}parent {root}\nparent {}\n\
+         author A U Thor <author@example.com> 1700000000 +0000\n\
+         committer C O Mitter <committer@example.com> 1700000000 +0000\n\nBroken\n",
+        tree_hash,
+        &root[..5]
+    );
+    let args = [
+        "hash-object",
+        "-t",
+        "commit",
+        "-w",
+        "--literally",
+        "--stdin",
+    ];
+    let commit_hash = git_with_stdin(directory.path(), &args, content.as_bytes());
+    let commit_hash = String::from_utf8(commit_hash).unwrap();
+
+    let output = run(
+        directory.path(),
+        &["log", "-n", "1", commit_hash.trim_end()],
+    );
+    assert_eq!(output.status.code(), Some(128));
+    assert!(!String::from_utf8_lossy(&output.stderr).contains("panicked"));
+}
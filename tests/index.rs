@@ -0,0 +1,243 @@
+mod common;
+
+use std::{fs, path::Path};
+
+use common::*;
+use git_starter_rust::{GitError, Repository};
+
+// The same files in two repositories, one for git and one for this implementation
+fn twin_repositories(files: &[(&str, &str)]) -> (TempDir, TempDir) {
+    let expected = git_repository();
+    let actual = git_repository();
+
+    for (path, content) in files.iter() {
+        expected.write(path, content);
+        actual.write(path, content);
+    }
+
+    return (expected, actual);
+}
+
+#[test]
+fn update_index_from_a_subdirectory_stores_paths_from_the_root() {
+    let files = [
+        ("a.txt", "a\r\n"),
+        ("sub/b.txt", "b\r\n"),
+        ("sub/deeper/c.txt", "c\r\n"),
+        (".gitattributes", "sub/*.txt text\n"),
+    ];
+    let (expected, actual) = twin_repositories(&files);
+    let args = [
+        "update-index",
+        "--add",
+        "b.txt",
+        "./deeper/../deeper/c.txt",
+        "../a.txt",
+    ];
+
+    git(&expected.join("sub"), &args);
+    run_ok(&actual.join("sub"), &args);
+
+    let staged = git(actual.path(), &["ls-files", "--stage"]);
+    assert_eq!(staged, git(expected.path(), &["ls-files", "--stage"]));
+    assert!(staged.contains("\tsub/b.txt\n"), "{staged}");
+}
+
+#[test]
+fn update_index_rejects_paths_outside_of_the_working_tree() {
+    let directory = git_repository();
+    directory.write("sub/b.txt", "b\n");
+
+    let output = run(
+        &directory.join("sub"),
+        &["update-index", "--add", "../../b.txt"],
+    );
+    assert!(!output.status.success());
+}
+
+// `a.txt` and `sub/b.txt` staged by git, then removed from the working tree
+// Line endings are converted on checkout, except for `a.txt` which isn't text
+fn repository_with_staged_files() -> TempDir {
+    let directory = git_repository();
+    directory.write("a.txt", "a\n");
+    directory.write("sub/b.txt", "b\n");
+    directory.write(".gitattributes", "a.txt -text\n");
+    git(directory.path(), &["add", "."]);
+    git(directory.path(), &["config", "core.autocrlf", "true"]);
+    std::fs::remove_file(directory.join("a.txt")).unwrap();
+    std::fs::remove_file(directory.join("sub/b.txt")).unwrap();
+
+    return directory;
+}
+
+#[test]
+fn checkout_index_from_a_subdirectory_writes_under_the_root() {
+    let directory = repository_with_staged_files();
+
+    run_ok(
+        &directory.join("sub"),
+        &["checkout-index", "b.txt", "../a.txt"],
+    );
+
+    assert_eq!(std::fs::read(directory.join("a.txt")).unwrap(), b"a\n");
+    assert_eq!(
+        std::fs::read(directory.join("sub/b.txt")).unwrap(),
+        b"b\r\n"
+    );
+    assert!(!directory.join("sub/sub").exists());
+}
+
+#[test]
+fn checkout_index_prefix_is_relative_to_the_root() {
+    let directory = repository_with_staged_files();
+
+    run_ok(
+        &directory.join("sub"),
+        &["checkout-index", "--prefix=out/", "b.txt"],
+    );
+
+    assert_eq!(
+        std::fs::read(directory.join("out/sub/b.txt")).unwrap(),
+        b"b\r\n"
+    );
+    assert!(!directory.join("sub/out").exists());
+}
+
+#[test]
+fn ls_files_lists_the_index_written_by_git() {
+    let directory = git_repository();
+    directory.write("b.txt", "b\n");
+    directory.write("a/z.txt", "z\n");
+    directory.write("a-b.txt", "a-b\n");
+    directory.write("script.sh", "#!/bin/sh\n");
+    git(directory.path(), &["add", "."]);
+    git(
+        directory.path(),
+        &["update-index", "--chmod=+x", "script.sh"],
+    );
+
+    assert_eq!(
+        run_ok(directory.path(), &["ls-files"]),
+        "a-b.txt\na/z.txt\nb.txt\nscript.sh\n"
+    );
+    assert_eq!(
+        run_ok(directory.path(), &["ls-files", "--stage"]),
+        git(directory.path(), &["ls-files", "--stage"])
+    );
+}
+
+#[test]
+fn read_tree_writes_the_index_of_a_tree() {
+    let directory = git_repository();
+    directory.write("a.txt", "a\n");
+    directory.write("sub/b.txt", "b\n");
+    directory.write("sub/deeper/c.txt", "c\n");
+    git(directory.path(), &["add", "."]);
+    let tree_hash = git(directory.path(), &["write-tree"]);
+    let tree_hash = tree_hash.trim_end();
+    let expected = git(directory.path(), &["ls-files", "--stage"]);
+    std::fs::remove_file(directory.join(".git/index")).unwrap();
+
+    run_ok(directory.path(), &["read-tree", tree_hash]);
+    assert_eq!(
+        git(directory.path(), &["ls-files"]),
+        "a.txt\nsub/b.txt\nsub/deeper/c.txt\n"
+    );
+    assert_eq!(git(directory.path(), &["ls-files", "--stage"]), expected);
+    assert_eq!(
+        git(directory.path(), &["write-tree"]),
+        format!("{tree_hash}\n")
+    );
+}
+
+// A tree holding `entries` as they are, written without any of git's checks
+fn literal_tree(directory: &Path, entries: &[(&str, &[u8], &str)]) -> String {
+    let mut content = Vec::new();
+    for (mode, name, sha) in entries.iter() {
+        content.extend_from_slice(format!("{mode} ").as_bytes());
+        content.extend_from_slice(name);
+        content.push(0);
+        content.extend_from_slice(&hex::decode(sha).unwrap());
+    }
+
+    let args = ["hash-object", "-t", "tree", "-w", "--literally", "--stdin"];
+    let tree_hash = git_with_stdin(directory, &args, &content);
+
+    return String::from_utf8(tree_hash).unwrap().trim_end().to_string();
+}
+
+#[test]
+fn read_tree_rejects_names_leading_out_of_their_directory() {
+    let directory = git_repository();
+    directory.write("escaped", "escaped\n");
+    let blob_hash = git(directory.path(), &["hash-object", "-w", "escaped"]);
+    let blob_hash = blob_hash.trim_end();
+    let subtree = literal_tree(directory.path(), &[("100644", b"escaped", blob_hash)]);
+
+    for (mode, name, sha) in [
+        ("40000", &b".."[..], subtree.as_str()),
+        ("40000", b".", &subtree),
+        ("40000", b".git", &subtree),
+        ("100644", b"..", blob_hash),
+        ("100644", b".git", blob_hash),
+        ("100644", b"a/b", blob_hash),
+        ("100644", b"", blob_hash),
+    ] {
+        let tree_hash = literal_tree(directory.path(), &[(mode, name, sha)]);
+
+        let output = run(directory.path(), &["read-tree", &tree_hash]);
+        assert_eq!(output.status.code(), Some(128), "{name:?}");
+        assert!(!directory.join(".git/index").exists(), "{name:?}");
+    }
+}
+
+#[test]
+fn indexes_with_paths_leading_out_of_the_working_tree_are_invalid() {
+    let directory = git_repository();
+    directory.write("file.txt", "file\n");
+    git(directory.path(), &["add", "file.txt"]);
+
+    let repository = Repository::open(directory.path()).unwrap();
+    let mut index = repository.read_index().unwrap();
+    index.entries[0].path = "../escaped".to_string();
+    repository.write_index(&index).unwrap();
+
+    assert!(matches!(
+        repository.read_index(),
+        Err(GitError::InvalidPath(_))
+    ));
+    assert_eq!(
+        run(directory.path(), &["ls-files"]).status.code(),
+        Some(128)
+    );
+}
+
+#[test]
+fn checkout_index_never_writes_outside_of_the_working_tree() {
+    let outer = TempDir::new();
+    git(outer.path(), &["init", "-q", "-b", "main", "repository"]);
+    let work_tree = outer.join("repository");
+    fs::write(work_tree.join("escaped"), "escaped\n").unwrap();
+    let blob_hash = git(&work_tree, &["hash-object", "-w", "escaped"]);
+    let blob_hash = blob_hash.trim_end();
+
+    // `../escaped`, through a subtree named `..`
+    let subtree = literal_tree(&work_tree, &[("100644", b"escaped", blob_hash)]);
+    let tree_hash = literal_tree(&work_tree, &[("40000", b"..", &subtree)]);
+    assert_eq!(
+        run(&work_tree, &["read-tree", &tree_hash]).status.code(),
+        Some(128)
+    );
+    run(&work_tree, &["checkout-index", "-a"]);
+    assert!(!outer.join("escaped").exists());
+
+    // An entry of the index is checked again before being written
+    let repository = Repository::open(&work_tree).unwrap();
+    let mut entry = repository
+        .index_entry_from_file(&work_tree, "escaped")
+        .unwrap();
+    entry.path = "../escaped".to_string();
+    let result = repository.checkout_entry(&work_tree, &entry, &work_tree.join(&entry.path));
+    assert!(matches!(result, Err(GitError::InvalidPath(_))));
+    assert!(!outer.join("escaped").exists());
+}
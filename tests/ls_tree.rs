@@ -0,0 +1,223 @@
+mod common;
+
+use std::{ffi::OsStr, fs, os::unix::ffi::OsStrExt};
+
+use common::*;
+use git_starter_rust::Repository;
+
+// A tree with files at the root and two levels of directories, written by git
+fn repository_with_tree() -> (TempDir, String) {
+    let directory = git_repository();
+    directory.write("README.md", "readme\n");
+    directory.write("src/main.rs", "fn main() {}\n");
+    directory.write("src/lib/mod.rs", "// empty\n");
+    git(directory.path(), &["add", "."]);
+    let tree_hash = git(directory.path(), &["write-tree"]);
+
+    return (directory, tree_hash.trim_end().to_string());
+}
+
+#[test]
+fn ls_tree_r_lists_every_file_with_its_full_path() {
+    let (directory, tree_hash) = repository_with_tree();
+
+    assert_eq!(
+        run_ok(
+            directory.path(),
+            &["ls-tree", "-r", "--name-only", &tree_hash]
+        ),
+        "README.md\nsrc/lib/mod.rs\nsrc/main.rs\n"
+    );
+    assert_eq!(
+        run_ok(directory.path(), &["ls-tree", "-r", &tree_hash]),
+        git(directory.path(), &["ls-tree", "-r", &tree_hash])
+    );
+}
+
+#[test]
+fn ls_tree_l_prints_blob_sizes_aligned_like_git() {
+    let directory = git_repository();
+    directory.write("small.txt", "a\n");
+    directory.write("large.txt", "a".repeat(12345));
+    directory.write("sub/file.txt", "file\n");
+    git(directory.path(), &["add", "."]);
+    let tree_hash = git(directory.path(), &["write-tree"]);
+    let tree_hash = tree_hash.trim_end();
+
+    let expected = git(directory.path(), &["ls-tree", "-l", tree_hash]);
+    assert_eq!(
+        run_ok(directory.path(), &["ls-tree", "-l", tree_hash]),
+        expected
+    );
+    assert!(expected.contains(" 12345\tlarge.txt\n"), "{expected}");
+    assert!(expected.contains("       2\tsmall.txt\n"), "{expected}");
+    assert!(expected.contains("       -\tsub\n"), "{expected}");
+}
+
+#[test]
+fn ls_tree_prints_full_lines_unless_asked_for_names_only() {
+    let (directory, tree_hash) = repository_with_tree();
+
+    let expected = git(directory.path(), &["ls-tree", &tree_hash]);
+    assert_eq!(run_ok(directory.path(), &["ls-tree", &tree_hash]), expected);
+    assert!(expected.contains(" tree "), "{expected}");
+
+    assert_eq!(
+        run_ok(directory.path(), &["ls-tree", "--name-only", &tree_hash]),
+        "README.md\nsrc\n"
+    );
+}
+
+#[test]
+fn ls_tree_z_separates_unquoted_names_with_nul() {
+    let directory = git_repository();
+    directory.write("with space.txt", "space\n");
+    directory.write("dir/with\nnewline.txt", "newline\n");
+    git(directory.path(), &["add", "."]);
+    let tree_hash = git(directory.path(), &["write-tree"]);
+    let tree_hash = tree_hash.trim_end();
+
+    let args = ["ls-tree", "-r", "-z", "--name-only", tree_hash];
+    let output = run_ok_with_stdin(directory.path(), &args, b"");
+    assert_eq!(output, git_with_stdin(directory.path(), &args, b""));
+    assert_eq!(
+        output.split(|&byte| byte == 0).collect::<Vec<&[u8]>>(),
+        vec![&b"dir/with\nnewline.txt"[..], b"with space.txt", b""]
+    );
+
+    let args = ["ls-tree", "-r", "-z", tree_hash];
+    assert_eq!(
+        run_ok_with_stdin(directory.path(), &args, b""),
+        git_with_stdin(directory.path(), &args, b"")
+    );
+}
+
+#[test]
+fn ls_tree_quotes_unusual_names_like_git() {
+    let directory = git_repository();
+    directory.write("with\nnewline.txt", "newline\n");
+    directory.write("café.txt", "utf-8\n");
+    directory.write("tab\tand \"quotes\" and \\.txt", "escaped\n");
+    directory.write("plain name.txt", "plain\n");
+    git(directory.path(), &["add", "."]);
+    let tree_hash = git(directory.path(), &["write-tree"]);
+    let tree_hash = tree_hash.trim_end();
+
+    let output = run_ok(directory.path(), &["ls-tree", "--name-only", tree_hash]);
+    assert_eq!(
+        output,
+        "\"caf\\303\\251.txt\"\nplain name.txt\n\"tab\\tand \\\"quotes\\\" and \\\\.txt\"\n\"with\\nnewline.txt\"\n"
+    );
+    assert_eq!(
+        output,
+        git(directory.path(), &["ls-tree", "--name-only", tree_hash])
+    );
+    assert_eq!(
+        run_ok(directory.path(), &["ls-tree", tree_hash]),
+        git(directory.path(), &["ls-tree", tree_hash])
+    );
+}
+
+#[test]
+fn tree_entry_names_may_not_be_utf8() {
+    let directory = git_repository();
+    let name = OsStr::from_bytes(b"latin\xff.txt");
+    fs::write(directory.join(name), "latin-1\n").unwrap();
+    git(directory.path(), &["add", "."]);
+    let tree_hash = git(directory.path(), &["write-tree"]);
+    let tree_hash = tree_hash.trim_end();
+
+    let repository = Repository::open(directory.path()).unwrap();
+    let tree = repository.read_tree(tree_hash).unwrap();
+    assert_eq!(tree.entries[0].name, b"latin\xff.txt");
+
+    assert_eq!(
+        run_ok(directory.path(), &["ls-tree", tree_hash]),
+        git(directory.path(), &["ls-tree", tree_hash])
+    );
+    let args = ["ls-tree", "-z", "--name-only", tree_hash];
+    assert_eq!(
+        run_ok_with_stdin(directory.path(), &args, b""),
+        b"latin\xff.txt\0"
+    );
+}
+
+#[test]
+fn ls_tree_full_name_and_full_tree_from_a_nested_directory() {
+    let (directory, tree_hash) = repository_with_tree();
+    let nested = directory.join("src");
+
+    for args in [
+        &["ls-tree", "-r", "--name-only", &tree_hash][..],
+        &["ls-tree", "-r", "--full-name", &tree_hash],
+        &["ls-tree", "-r", "--full-tree", &tree_hash],
+        &["ls-tree", "--full-tree", &tree_hash],
+    ] {
+        assert_eq!(run_ok(&nested, args), git(&nested, args), "{args:?}");
+    }
+
+    let output = run_ok(
+        &nested,
+        &["ls-tree", "-r", "--full-name", "--name-only", &tree_hash],
+    );
+    assert_eq!(output, "src/lib/mod.rs\nsrc/main.rs\n");
+    let output = run_ok(
+        &nested,
+        &["ls-tree", "-r", "--full-tree", "--name-only", &tree_hash],
+    );
+    assert_eq!(output, "README.md\nsrc/lib/mod.rs\nsrc/main.rs\n");
+}
+
+#[test]
+fn ls_tree_d_lists_only_trees() {
+    let (directory, tree_hash) = repository_with_tree();
+
+    assert_eq!(
+        run_ok(
+            directory.path(),
+            &["ls-tree", "-d", "--name-only", &tree_hash]
+        ),
+        "src\n"
+    );
+    assert_eq!(
+        run_ok(
+            directory.path(),
+            &["ls-tree", "-d", "-r", "--name-only", &tree_hash]
+        ),
+        "src\nsrc/lib\n"
+    );
+    for args in [
+        &["ls-tree", "-d", &tree_hash][..],
+        &["ls-tree", "-d", "-r", &tree_hash],
+    ] {
+        assert_eq!(run_ok(directory.path(), args), git(directory.path(), args));
+    }
+}
+
+#[test]
+fn ls_tree_abbrev_prints_unique_abbreviations() {
+    let (directory, tree_hash) = repository_with_tree();
+
+    let output = run_ok(directory.path(), &["ls-tree", "--abbrev", &tree_hash]);
+    assert_eq!(
+        output,
+        git(directory.path(), &["ls-tree", "--abbrev", &tree_hash])
+    );
+    let first_sha = output.split(' ').nth(2).unwrap();
+    assert_eq!(first_sha.split('\t').next().unwrap().len(), 7);
+
+    // Objects sharing their first 4 hex digits need more of them
+    let repository = Repository::open(directory.path()).unwrap();
+    let prefix = ambiguous_prefix(&repository);
+    let disambiguate = format!("--disambiguate={prefix}");
+    let candidates = git(directory.path(), &["rev-parse", &disambiguate]);
+    let blob_hash = candidates.lines().next().unwrap();
+    let input = format!("100644 blob {blob_hash}\tambiguous.txt\n");
+    let ambiguous_tree = git_with_stdin(directory.path(), &["mktree"], input.as_bytes());
+    let ambiguous_tree = String::from_utf8(ambiguous_tree).unwrap();
+
+    let args = ["ls-tree", "--abbrev=4", ambiguous_tree.trim_end()];
+    let output = run_ok(directory.path(), &args);
+    assert_eq!(output, git(directory.path(), &args));
+    assert!(!output.contains(&format!(" {prefix}\t")), "{output}");
+}
@@ -0,0 +1,536 @@
+mod common;
+
+use std::{
+    fs,
+    io::{self, Read, Write},
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+};
+
+use common::*;
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+use git_starter_rust::{
+    for_each_loose_object, hash_content, hash_stream, object_encoder, pack_object,
+    read_object_header, write_object_file, BlobObject, GitError, GitObject, ObjectFormat,
+    ObjectType, Repository, TreeObject,
+};
+
+// Every file under `directory`, recursively
+fn files_under(directory: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+
+    for entry in fs::read_dir(directory).unwrap() {
+        let path = entry.unwrap().path();
+
+        if path.is_dir() {
+            files.extend(files_under(&path));
+        } else {
+            files.push(path);
+        }
+    }
+
+    return files;
+}
+
+// The decompressed content of the loose object `object_hash`
+fn inflate_loose_object(git_dir: &Path, object_hash: &str) -> Vec<u8> {
+    let path = git_dir
+        .join("objects")
+        .join(&object_hash[..2])
+        .join(&object_hash[2..]);
+    let mut content = Vec::new();
+    ZlibDecoder::new(fs::File::open(path).unwrap())
+        .read_to_end(&mut content)
+        .unwrap();
+
+    return content;
+}
+
+// Yields `content`, then fails
+struct FailingReader {
+    content: io::Cursor<Vec<u8>>,
+}
+
+impl Read for FailingReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.content.read(buf)? {
+            0 => Err(io::Error::other("read failed")),
+            n => Ok(n),
+        }
+    }
+}
+
+#[test]
+fn streamed_objects_hash_like_git() {
+    let directory = git_repository();
+    let content = random_bytes(300 * 1024, 84);
+    directory.write("large.bin", &content);
+
+    let object_hash = hash_stream(
+        content.as_slice(),
+        content.len() as u64,
+        ObjectType::Blob,
+        Some(&directory.join(".git")),
+        ObjectFormat::Sha1,
+    )
+    .unwrap();
+
+    assert_eq!(
+        format!("{object_hash}\n"),
+        git(directory.path(), &["hash-object", "large.bin"])
+    );
+    assert_eq!(
+        git_with_stdin(directory.path(), &["cat-file", "blob", &object_hash], b""),
+        content
+    );
+}
+
+#[test]
+fn failed_streams_leave_no_object_behind() {
+    let directory = git_repository();
+    let objects_dir = directory.join(".git/objects");
+    let content = random_bytes(200 * 1024, 84);
+
+    let reader = FailingReader {
+        content: io::Cursor::new(content.clone()),
+    };
+    let result = hash_stream(
+        reader,
+        2 * content.len() as u64,
+        ObjectType::Blob,
+        Some(&directory.join(".git")),
+        ObjectFormat::Sha1,
+    );
+    assert!(matches!(result, Err(GitError::Io(_))));
+    assert_eq!(files_under(&objects_dir), Vec::<PathBuf>::new());
+
+    // Fewer bytes than announced
+    let result = hash_stream(
+        content.as_slice(),
+        content.len() as u64 + 1,
+        ObjectType::Blob,
+        Some(&directory.join(".git")),
+        ObjectFormat::Sha1,
+    );
+    assert!(matches!(result, Err(GitError::Corrupt(_))));
+    assert_eq!(files_under(&objects_dir), Vec::<PathBuf>::new());
+}
+
+#[test]
+fn written_objects_are_complete_zlib_streams() {
+    let directory = git_repository();
+    let packed = pack_object(ObjectType::Blob, b"hello world\n");
+
+    let object_hash =
+        write_object_file(&directory.join(".git"), packed.clone(), ObjectFormat::Sha1).unwrap();
+
+    assert_eq!(
+        inflate_loose_object(&directory.join(".git"), &object_hash),
+        packed
+    );
+    assert_eq!(
+        git(directory.path(), &["cat-file", "-p", &object_hash]),
+        "hello world\n"
+    );
+}
+
+#[test]
+fn hashing_the_same_content_twice_succeeds() {
+    let directory = git_repository();
+    directory.write("a.txt", "same\n");
+    directory.write("b.txt", "same\n");
+
+    let first = run_ok(directory.path(), &["hash-object", "-w", "a.txt"]);
+    let second = run_ok(directory.path(), &["hash-object", "-w", "b.txt"]);
+
+    assert_eq!(first, second);
+    assert_eq!(first, git(directory.path(), &["hash-object", "a.txt"]));
+    assert_eq!(files_under(&directory.join(".git/objects")).len(), 1);
+}
+
+#[test]
+fn large_blobs_are_stored_whole() {
+    let directory = git_repository();
+    let content = random_bytes(1024 * 1024, 6);
+    let packed = pack_object(ObjectType::Blob, &content);
+
+    let object_hash =
+        write_object_file(&directory.join(".git"), packed.clone(), ObjectFormat::Sha1).unwrap();
+
+    assert_eq!(
+        inflate_loose_object(&directory.join(".git"), &object_hash),
+        packed
+    );
+    assert_eq!(
+        git_with_stdin(directory.path(), &["cat-file", "blob", &object_hash], b""),
+        content
+    );
+}
+
+#[test]
+fn objects_stored_through_the_library_are_read_by_git() {
+    let directory = git_repository();
+    directory.write("file.txt", "from the library\n");
+
+    let object_hash = hash_content(
+        &b"from the library\n"[..],
+        ObjectType::Blob,
+        Some(&directory.join(".git")),
+        ObjectFormat::Sha1,
+    )
+    .unwrap();
+
+    assert_eq!(
+        format!("{object_hash}\n"),
+        git(directory.path(), &["hash-object", "file.txt"])
+    );
+    assert_eq!(
+        git(directory.path(), &["cat-file", "-p", &object_hash]),
+        "from the library\n"
+    );
+}
+
+#[test]
+fn objects_not_hashing_to_their_name_are_corrupt() {
+    let directory = git_repository();
+    directory.write("file.txt", "original\n");
+    let object_hash = git(directory.path(), &["hash-object", "-w", "file.txt"]);
+    let object_hash = object_hash.trim_end();
+
+    // Still a valid object, with one byte of its content flipped
+    let path = directory.join(format!(
+        ".git/objects/{}/{}",
+        &object_hash[..2],
+        &object_hash[2..]
+    ));
+    let mut packed = inflate_loose_object(&directory.join(".git"), object_hash);
+    *packed.last_mut().unwrap() ^= 1;
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&packed).unwrap();
+    fs::remove_file(&path).unwrap();
+    fs::write(&path, encoder.finish().unwrap()).unwrap();
+
+    let repository = Repository::open(directory.path()).unwrap();
+    assert!(matches!(
+        repository.read_verified_content(object_hash),
+        Err(GitError::Corrupt(_))
+    ));
+    assert!(!run(directory.path(), &["cat-file", "-p", object_hash])
+        .status
+        .success());
+}
+
+#[test]
+fn streamed_and_buffered_hashes_agree() {
+    let directory = git_repository();
+    let content = random_bytes(4 * 1024 * 1024 + 3, 42);
+    directory.write("large.bin", &content);
+
+    let buffered = hash_content(
+        content.as_slice(),
+        ObjectType::Blob,
+        None,
+        ObjectFormat::Sha1,
+    )
+    .unwrap();
+    let streamed = hash_stream(
+        content.as_slice(),
+        content.len() as u64,
+        ObjectType::Blob,
+        None,
+        ObjectFormat::Sha1,
+    )
+    .unwrap();
+    assert_eq!(streamed, buffered);
+
+    assert_eq!(
+        run_ok(directory.path(), &["hash-object", "-w", "large.bin"]),
+        format!("{buffered}\n")
+    );
+    assert_eq!(
+        inflate_loose_object(&directory.join(".git"), &buffered),
+        pack_object(ObjectType::Blob, &content)
+    );
+}
+
+#[test]
+fn fsck_reports_exactly_the_corrupt_object() {
+    let directory = git_repository();
+    directory.write("a.txt", "a\n");
+    directory.write("b.txt", "b\n");
+    git(directory.path(), &["add", "."]);
+    git(directory.path(), &["write-tree"]);
+    assert!(run(directory.path(), &["fsck"]).status.success());
+
+    let object_hash = git(directory.path(), &["hash-object", "a.txt"]);
+    let object_hash = object_hash.trim_end();
+    let path = directory.join(format!(
+        ".git/objects/{}/{}",
+        &object_hash[..2],
+        &object_hash[2..]
+    ));
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder
+        .write_all(&pack_object(ObjectType::Blob, b"not a\n"))
+        .unwrap();
+    fs::remove_file(&path).unwrap();
+    fs::write(&path, encoder.finish().unwrap()).unwrap();
+
+    let output = run(directory.path(), &["fsck"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let reported: Vec<&str> = stderr
+        .lines()
+        .filter_map(|line| line.strip_prefix("error: "))
+        .map(|line| &line[..40])
+        .collect();
+    assert_eq!(reported, vec![object_hash], "{stderr}");
+}
+
+#[test]
+fn fsck_reports_trees_pointing_to_missing_objects() {
+    let directory = git_repository();
+    let missing = "1".repeat(40);
+    let tree_hash = git_with_stdin(
+        directory.path(),
+        &["mktree", "--missing"],
+        format!("100644 blob {missing}\tmissing.txt\n").as_bytes(),
+    );
+    let tree_hash = String::from_utf8(tree_hash).unwrap();
+
+    let output = run(directory.path(), &["fsck"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains(tree_hash.trim_end()), "{stderr}");
+}
+
+#[test]
+fn malformed_headers_are_reported_with_the_object_hash() {
+    let object_hash = "1".repeat(40);
+
+    for header in [
+        &b"blo"[..],
+        b"blob",
+        b"blob 12",
+        b"blob twelve\0",
+        b"blobblob 1\0x",
+        b"blobx 3\0abc",
+    ] {
+        let result = read_object_header(&mut io::Cursor::new(header), &object_hash);
+
+        match result {
+            Err(GitError::InvalidObjectHeader {
+                object_hash: reported,
+                ..
+            }) => assert_eq!(reported, object_hash),
+            _ => panic!("{header:?} wasn't rejected as an invalid header"),
+        }
+    }
+}
+
+#[test]
+fn malformed_stored_objects_are_errors_not_panics() {
+    let directory = git_repository();
+    // A tree declaring fewer bytes than its entry takes
+    let short_tree = [b"tree 5\0100644 a\0".as_slice(), &[0xab; 20]].concat();
+
+    for (object_hash, content) in [
+        ("1".repeat(40), &b"blob 12"[..]),
+        ("2".repeat(40), b"blobx 3\0abc"),
+        ("3".repeat(40), &short_tree),
+    ] {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(content).unwrap();
+        directory.write(
+            &format!(".git/objects/{}/{}", &object_hash[..2], &object_hash[2..]),
+            encoder.finish().unwrap(),
+        );
+
+        for args in [
+            ["cat-file", "-p"],
+            ["cat-file", "-t"],
+            ["cat-file", "-s"],
+            ["ls-tree", "-r"],
+        ] {
+            let output = run(directory.path(), &[args[0], args[1], &object_hash]);
+            let stderr = String::from_utf8(output.stderr).unwrap();
+
+            // The header of the tree is fine, only reading its entries fails
+            if content == short_tree && args[0] == "cat-file" && args[1] != "-p" {
+                assert!(output.status.success(), "{args:?}: {stderr}");
+                continue;
+            }
+
+            assert_eq!(output.status.code(), Some(128), "{args:?}: {stderr}");
+            assert!(!stderr.contains("panicked"), "{stderr}");
+            if content != short_tree {
+                assert!(stderr.contains(&object_hash), "{stderr}");
+            }
+        }
+    }
+}
+
+// Counts the writes reaching it
+#[derive(Default)]
+struct CountingWriter {
+    writes: usize,
+    written: Vec<u8>,
+}
+
+impl Write for CountingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.writes += 1;
+        self.written.extend_from_slice(buf);
+
+        return Ok(buf.len());
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        return Ok(());
+    }
+}
+
+#[test]
+fn compressed_objects_reach_their_file_in_few_writes() {
+    let packed = pack_object(ObjectType::Blob, &random_bytes(1024 * 1024, 83));
+
+    let mut encoder = object_encoder(CountingWriter::default());
+    // Small writes, like a stream read a little at a time
+    for chunk in packed.chunks(100) {
+        encoder.write_all(chunk).unwrap();
+    }
+    let output = encoder.finish().unwrap().into_inner().ok().unwrap();
+
+    // The default buffer of a `BufWriter` is 8 KiB
+    assert!(
+        output.writes <= output.written.len() / 8192 + 1,
+        "{}",
+        output.writes
+    );
+    let mut content = Vec::new();
+    ZlibDecoder::new(output.written.as_slice())
+        .read_to_end(&mut content)
+        .unwrap();
+    assert_eq!(content, packed);
+}
+
+#[test]
+fn written_objects_are_read_only() {
+    let directory = git_repository();
+    let git_dir = directory.join(".git");
+    let packed = pack_object(ObjectType::Blob, b"read-only\n");
+
+    let object_hash = write_object_file(&git_dir, packed.clone(), ObjectFormat::Sha1).unwrap();
+    let path = git_dir.join(format!(
+        "objects/{}/{}",
+        &object_hash[..2],
+        &object_hash[2..]
+    ));
+    assert_eq!(
+        fs::metadata(&path).unwrap().permissions().mode() & 0o777,
+        0o444
+    );
+
+    // Stored again over the read-only file, by every way of writing objects
+    assert_eq!(
+        write_object_file(&git_dir, packed, ObjectFormat::Sha1).unwrap(),
+        object_hash
+    );
+    let streamed = hash_stream(
+        &b"read-only\n"[..],
+        10,
+        ObjectType::Blob,
+        Some(&git_dir),
+        ObjectFormat::Sha1,
+    )
+    .unwrap();
+    assert_eq!(streamed, object_hash);
+    assert_eq!(files_under(&git_dir.join("objects")), vec![path]);
+}
+
+#[test]
+fn empty_blobs_and_trees_are_written_and_read_back() {
+    let directory = git_repository();
+    let repository = Repository::open(directory.path()).unwrap();
+    let empty_blob = "e69de29bb2d1d6434b8b29ae775ad8c2e48c5391";
+    let empty_tree = "4b825dc642cb6eb9a060e54bf8d69288fbee4904";
+
+    let blob = GitObject::Blob(BlobObject { data: Vec::new() });
+    assert_eq!(repository.write_object(&blob).unwrap(), empty_blob);
+    let tree = GitObject::Tree(TreeObject {
+        entries: Vec::new(),
+    });
+    assert_eq!(repository.write_object(&tree).unwrap(), empty_tree);
+
+    match repository.read_object(empty_blob).unwrap() {
+        GitObject::Blob(blob) => assert!(blob.data.is_empty()),
+        _ => panic!("{empty_blob} should be a blob"),
+    }
+    assert!(repository.read_tree(empty_tree).unwrap().entries.is_empty());
+
+    directory.write("empty.txt", "");
+    assert_eq!(
+        run_ok(directory.path(), &["hash-object", "empty.txt"]),
+        format!("{empty_blob}\n")
+    );
+    for object_hash in [empty_blob, empty_tree] {
+        assert_eq!(
+            run_ok(directory.path(), &["cat-file", "-s", object_hash]),
+            "0\n"
+        );
+        assert_eq!(
+            run_ok(directory.path(), &["cat-file", "-p", object_hash]),
+            ""
+        );
+    }
+    assert_eq!(run_ok(directory.path(), &["ls-tree", empty_tree]), "");
+}
+
+#[test]
+fn for_each_loose_object_enumerates_exactly_the_loose_objects() {
+    let directory = git_repository();
+    let repository = Repository::open(directory.path()).unwrap();
+
+    // Packed objects aren't loose
+    directory.write("packed.txt", "packed\n");
+    git(directory.path(), &["add", "packed.txt"]);
+    let tree_hash = git(directory.path(), &["write-tree"]);
+    let commit_hash = git(
+        directory.path(),
+        &["commit-tree", tree_hash.trim_end(), "-m", "Packed"],
+    );
+    git(
+        directory.path(),
+        &["update-ref", "refs/heads/main", commit_hash.trim_end()],
+    );
+    git(directory.path(), &["repack", "-a", "-d"]);
+
+    let mut expected = Vec::new();
+    for content in ["one\n", "two\n", "three\n"] {
+        let blob = GitObject::Blob(BlobObject {
+            data: content.as_bytes().to_vec(),
+        });
+        expected.push(repository.write_object(&blob).unwrap());
+    }
+    expected.sort();
+
+    // Files that aren't objects, next to and inside fan-out directories
+    let objects_dir = directory.join(".git/objects");
+    fs::create_dir_all(objects_dir.join("info")).unwrap();
+    fs::write(objects_dir.join("info/alternates"), "").unwrap();
+    fs::create_dir_all(objects_dir.join("zz")).unwrap();
+    fs::write(objects_dir.join("zz").join("0".repeat(38)), "").unwrap();
+    let fan_out = objects_dir.join(&expected[0][..2]);
+    fs::write(fan_out.join("tmp_obj_123456"), "").unwrap();
+    fs::write(fan_out.join("abc"), "").unwrap();
+
+    let mut enumerated = Vec::new();
+    for_each_loose_object(&repository, |object_hash| {
+        enumerated.push(object_hash.to_string());
+        return Ok(());
+    })
+    .unwrap();
+    enumerated.sort();
+
+    assert_eq!(enumerated, expected);
+}
@@ -0,0 +1,334 @@
+mod common;
+
+use std::{
+    fs,
+    io::{Cursor, Write},
+    path::PathBuf,
+};
+
+use common::*;
+use flate2::{write::ZlibEncoder, Compression};
+use git_starter_rust::{
+    apply_delta, crc32, find_packed_object, read_pack_entry, write_pack_index, GitError, GitObject,
+    ObjectFormat, ObjectType, PackedObject, Repository,
+};
+use sha1::{Digest, Sha1};
+
+// A repository whose only objects are in a single pack written by git
+// `large.txt` only changes by a line, so that one of its versions is stored as a delta
+fn packed_repository() -> TempDir {
+    let directory = git_repository();
+    let lines: String = (0..500).map(|i| format!("line {i}\n")).collect();
+    directory.write("a.txt", "first file\n");
+    directory.write("dir/b.txt", "second file\n");
+    directory.write("large.txt", &lines);
+    git(directory.path(), &["add", "."]);
+    git(directory.path(), &["commit", "-q", "-m", "Initial commit"]);
+    directory.write("a.txt", "first file, changed\n");
+    directory.write("large.txt", format!("{lines}one more line\n"));
+    git(
+        directory.path(),
+        &["commit", "-q", "-a", "-m", "Second commit"],
+    );
+    git(directory.path(), &["repack", "-a", "-d", "-q"]);
+    git(directory.path(), &["prune-packed"]);
+
+    return directory;
+}
+
+#[test]
+fn pack_indexes_list_and_find_every_packed_object() {
+    let directory = packed_repository();
+    let repository = Repository::open(directory.path()).unwrap();
+
+    let expected: Vec<String> = git(
+        directory.path(),
+        &[
+            "cat-file",
+            "--batch-all-objects",
+            "--batch-check=%(objectname)",
+        ],
+    )
+    .lines()
+    .map(str::to_string)
+    .collect();
+
+    let packs = repository.packs().unwrap();
+    assert_eq!(packs.len(), 1);
+    assert_eq!(packs[0].len(), expected.len());
+    assert_eq!(packs[0].hashes().collect::<Vec<_>>(), expected);
+
+    for object_hash in expected.iter() {
+        assert!(packs[0].find(object_hash).is_some(), "{object_hash}");
+        assert_eq!(
+            packs[0].find_prefix(&object_hash[..7]),
+            vec![object_hash.clone()]
+        );
+        assert_eq!(
+            packs[0].find_prefix(&object_hash[..5]),
+            vec![object_hash.clone()]
+        );
+    }
+
+    assert_eq!(packs[0].find(&"0".repeat(40)), None);
+    assert_eq!(packs[0].find("not a hash"), None);
+}
+
+#[test]
+fn packed_objects_are_read_by_abbreviated_hash() {
+    let directory = packed_repository();
+    let repository = Repository::open(directory.path()).unwrap();
+
+    let blob_hash = git(directory.path(), &["rev-parse", "HEAD:dir/b.txt"]);
+    let blob_hash = blob_hash.trim_end();
+
+    match repository.read_object(&blob_hash[..8]).unwrap() {
+        GitObject::Blob(blob) => assert_eq!(blob.data, b"second file\n"),
+        _ => panic!("{blob_hash} is not a blob"),
+    }
+    assert!(repository.object_exists(blob_hash).unwrap());
+}
+
+// The path of the only pack of `directory`, without its extension
+fn pack_base_name(directory: &TempDir) -> PathBuf {
+    let pack_dir = directory.join(".git/objects/pack");
+    let pack = fs::read_dir(&pack_dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .find(|path| path.extension() == Some("pack".as_ref()))
+        .unwrap();
+
+    return pack.with_extension("");
+}
+
+#[test]
+fn index_pack_writes_the_index_git_writes() {
+    let directory = packed_repository();
+    let base_name = pack_base_name(&directory);
+    let copy = TempDir::new();
+    fs::copy(base_name.with_extension("pack"), copy.join("copy.pack")).unwrap();
+
+    let verified = git(
+        directory.path(),
+        &[
+            "verify-pack",
+            "-v",
+            base_name.with_extension("idx").to_str().unwrap(),
+        ],
+    );
+    assert!(verified.contains("chain length = 1: "), "{verified}");
+
+    let output = run_ok(copy.path(), &["index-pack", "copy.pack"]);
+    let checksum = base_name.file_name().unwrap().to_str().unwrap();
+    assert_eq!(
+        output,
+        format!("{}\n", checksum.strip_prefix("pack-").unwrap())
+    );
+    assert_eq!(
+        fs::read(copy.join("copy.idx")).unwrap(),
+        fs::read(base_name.with_extension("idx")).unwrap()
+    );
+
+    run_ok(copy.path(), &["index-pack", "-o", "other.idx", "copy.pack"]);
+    assert_eq!(
+        fs::read(copy.join("other.idx")).unwrap(),
+        fs::read(base_name.with_extension("idx")).unwrap()
+    );
+}
+
+// The path of the loose object `object_hash` in `directory`
+fn loose_object_path(directory: &TempDir, object_hash: &str) -> PathBuf {
+    return directory.join(format!(
+        ".git/objects/{}/{}",
+        &object_hash[..2],
+        &object_hash[2..]
+    ));
+}
+
+#[test]
+fn unpack_objects_writes_every_object_loose() {
+    let source = git_repository();
+    let mut object_hashes = String::new();
+    for content in ["one\n", "two\n", "three\n"] {
+        let object_hash = git_with_stdin(
+            source.path(),
+            &["hash-object", "-w", "--stdin"],
+            content.as_bytes(),
+        );
+        object_hashes.push_str(&String::from_utf8(object_hash).unwrap());
+    }
+    let pack = git_with_stdin(
+        source.path(),
+        &["pack-objects", "--stdout"],
+        object_hashes.as_bytes(),
+    );
+
+    let directory = git_repository();
+    assert_eq!(
+        run_ok_with_stdin(directory.path(), &["unpack-objects"], &pack),
+        b"Unpacked 3 objects\n"
+    );
+
+    for object_hash in object_hashes.lines() {
+        assert!(
+            loose_object_path(&directory, object_hash).is_file(),
+            "{object_hash}"
+        );
+    }
+    assert_eq!(
+        git_with_stdin(
+            directory.path(),
+            &["cat-file", "--batch-check"],
+            object_hashes.as_bytes()
+        ),
+        git_with_stdin(
+            source.path(),
+            &["cat-file", "--batch-check"],
+            object_hashes.as_bytes()
+        )
+    );
+
+    // From a path instead of stdin, into a repository that already has the objects of the pack
+    fs::write(directory.join("objects.pack"), &pack).unwrap();
+    run_ok(directory.path(), &["unpack-objects", "objects.pack"]);
+    git(directory.path(), &["fsck", "--strict"]);
+}
+
+#[test]
+fn pack_objects_packs_round_trip_through_unpack_objects() {
+    let source = git_repository();
+    source.write("a.txt", "a\n");
+    source.write("b.txt", "b\n");
+    let object_hashes = git(source.path(), &["hash-object", "-w", "a.txt", "b.txt"]);
+
+    let pack = run_ok_with_stdin(
+        source.path(),
+        &["pack-objects", "--stdout"],
+        object_hashes.as_bytes(),
+    );
+    assert_eq!(&pack[..4], b"PACK");
+
+    let directory = git_repository();
+    run_ok_with_stdin(directory.path(), &["unpack-objects"], &pack);
+    for object_hash in object_hashes.lines() {
+        assert!(
+            loose_object_path(&directory, object_hash).is_file(),
+            "{object_hash}"
+        );
+    }
+    assert_eq!(
+        git(
+            directory.path(),
+            &["cat-file", "-p", object_hashes.lines().next().unwrap()]
+        ),
+        "a\n"
+    );
+
+    // Written next to its index, which git accepts
+    let checksum = run_ok_with_stdin(
+        source.path(),
+        &["pack-objects", "objects"],
+        object_hashes.as_bytes(),
+    );
+    let checksum = String::from_utf8(checksum).unwrap();
+    let base_name = source.join(format!("objects-{}", checksum.trim_end()));
+    assert_eq!(fs::read(base_name.with_extension("pack")).unwrap(), pack);
+    git(
+        source.path(),
+        &[
+            "verify-pack",
+            base_name.with_extension("idx").to_str().unwrap(),
+        ],
+    );
+}
+
+#[test]
+fn count_objects_counts_loose_and_packed_objects_like_git() {
+    let directory = packed_repository();
+    for content in ["one\n", "two\n", "three\n"] {
+        git_with_stdin(
+            directory.path(),
+            &["hash-object", "-w", "--stdin"],
+            content.as_bytes(),
+        );
+    }
+
+    let expected = git(directory.path(), &["count-objects", "-v"]);
+    let expected: Vec<&str> = expected.lines().collect();
+    assert_eq!(expected[0], "count: 3");
+
+    let output = run_ok(directory.path(), &["count-objects"]);
+    assert_eq!(output.lines().collect::<Vec<&str>>(), expected[..2]);
+
+    let output = run_ok(directory.path(), &["count-objects", "-v"]);
+    assert_eq!(output.lines().collect::<Vec<&str>>(), expected[..6]);
+}
+
+#[test]
+fn malformed_pack_entries_are_errors_not_panics() {
+    let invalid_pack = |bytes: &[u8]| {
+        let result = read_pack_entry(&mut Cursor::new(bytes), 100, ObjectFormat::Sha1);
+        assert!(matches!(result, Err(GitError::InvalidPack(_))), "{bytes:?}");
+    };
+
+    // A size continuing past 64 bits
+    invalid_pack(&[[0xb0].as_slice(), &[0xff; 12]].concat());
+    // An offset delta based on itself, or continuing past 64 bits
+    invalid_pack(&[0x60, 0x00]);
+    invalid_pack(&[[0x60].as_slice(), &[0xff; 12]].concat());
+
+    // A delta size continuing past 64 bits
+    let delta = [[0xff; 12].as_slice(), &[0x00, 0x00]].concat();
+    assert!(matches!(
+        apply_delta(b"", &delta),
+        Err(GitError::InvalidPack(_))
+    ));
+}
+
+#[test]
+fn delta_cycles_are_errors_not_stack_overflows() {
+    let directory = git_repository();
+    let first = "a".repeat(40);
+    let second = "b".repeat(40);
+
+    // Two deltas, each based on the other, both producing an empty object from an empty base
+    let mut pack = [b"PACK".as_slice(), &2u32.to_be_bytes(), &2u32.to_be_bytes()].concat();
+    let mut objects = Vec::new();
+    for (object_hash, base_hash) in [(&first, &second), (&second, &first)] {
+        let offset = pack.len() as u64;
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&[0x00, 0x00]).unwrap();
+        let entry = [
+            [0x72].as_slice(),
+            &hex::decode(base_hash).unwrap(),
+            &encoder.finish().unwrap(),
+        ]
+        .concat();
+        pack.extend_from_slice(&entry);
+        objects.push(PackedObject {
+            offset,
+            crc32: crc32(&entry),
+            object_type: ObjectType::Blob,
+            content: Vec::new(),
+            hash: object_hash.clone(),
+        });
+    }
+    let checksum = hex::encode(Sha1::digest(&pack));
+    pack.extend_from_slice(&hex::decode(&checksum).unwrap());
+
+    let pack_dir = directory.join(".git/objects/pack");
+    fs::create_dir_all(&pack_dir).unwrap();
+    fs::write(pack_dir.join(format!("pack-{checksum}.pack")), &pack).unwrap();
+    let index_path = pack_dir.join(format!("pack-{checksum}.idx"));
+    write_pack_index(&index_path, &objects, &checksum, ObjectFormat::Sha1).unwrap();
+
+    let repository = Repository::open(directory.path()).unwrap();
+    assert!(matches!(
+        find_packed_object(&repository, &first),
+        Err(GitError::InvalidPack(_))
+    ));
+
+    let output = run(directory.path(), &["cat-file", "-p", &second]);
+    assert_eq!(output.status.code(), Some(128));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("too deep"));
+}
@@ -0,0 +1,64 @@
+mod common;
+
+use std::fs;
+
+use common::*;
+
+// Two commits and an annotated tag, every ref packed, along with a blob nothing references
+fn repository_with_packed_refs() -> (TempDir, String) {
+    let directory = git_repository();
+    directory.write("a.txt", "first\n");
+    git(directory.path(), &["add", "a.txt"]);
+    git(directory.path(), &["commit", "-q", "-m", "First commit"]);
+    git(directory.path(), &["tag", "-a", "-m", "Version 1", "v1"]);
+    directory.write("a.txt", "second\n");
+    git(
+        directory.path(),
+        &["commit", "-q", "-a", "-m", "Second commit"],
+    );
+    git(directory.path(), &["pack-refs", "--all"]);
+
+    let unreferenced = git_with_stdin(
+        directory.path(),
+        &["hash-object", "-w", "--stdin"],
+        b"unreferenced\n",
+    );
+    let unreferenced = String::from_utf8(unreferenced).unwrap();
+
+    return (directory, unreferenced.trim_end().to_string());
+}
+
+#[test]
+fn prune_keeps_objects_reachable_from_packed_refs() {
+    let (directory, unreferenced) = repository_with_packed_refs();
+    let reachable = git(directory.path(), &["rev-list", "--objects", "--all"]);
+
+    assert_eq!(
+        run_ok(directory.path(), &["prune", "--dry-run", "--expire", "0"]),
+        format!("{unreferenced} blob\n")
+    );
+    run_ok(directory.path(), &["prune", "--expire", "0"]);
+
+    for line in reachable.lines() {
+        let object_hash = &line[..40];
+        git(directory.path(), &["cat-file", "-e", object_hash]);
+    }
+    git(directory.path(), &["fsck", "--full", "--no-dangling"]);
+    assert!(!directory
+        .join(".git/objects")
+        .join(&unreferenced[..2])
+        .join(&unreferenced[2..])
+        .exists());
+}
+
+#[test]
+fn prune_fails_on_a_broken_ref_instead_of_pruning_its_history() {
+    let (directory, _) = repository_with_packed_refs();
+    fs::write(directory.join(".git/refs/heads/main"), "not a hash\n").unwrap();
+
+    let output = run(directory.path(), &["prune", "--expire", "0"]);
+    assert!(!output.status.success());
+
+    fs::remove_file(directory.join(".git/refs/heads/main")).unwrap();
+    git(directory.path(), &["fsck", "--full", "--no-dangling"]);
+}
@@ -0,0 +1,220 @@
+mod common;
+
+use std::fs;
+
+use common::*;
+use git_starter_rust::Repository;
+
+// Two commits on `main` and an annotated tag on the first, with every ref packed by git
+fn repository_with_packed_refs() -> TempDir {
+    let directory = git_repository();
+    directory.write("a.txt", "first\n");
+    git(directory.path(), &["add", "a.txt"]);
+    git(directory.path(), &["commit", "-q", "-m", "First commit"]);
+    git(directory.path(), &["tag", "-a", "-m", "Version 1", "v1"]);
+    directory.write("a.txt", "second\n");
+    git(
+        directory.path(),
+        &["commit", "-q", "-a", "-m", "Second commit"],
+    );
+    git(directory.path(), &["pack-refs", "--all"]);
+    assert!(!directory.join(".git/refs/heads/main").exists());
+
+    return directory;
+}
+
+#[test]
+fn packed_refs_are_resolved() {
+    let directory = repository_with_packed_refs();
+
+    for revision in [
+        "HEAD",
+        "main",
+        "refs/heads/main",
+        "v1",
+        "tags/v1",
+        "refs/tags/v1",
+    ] {
+        assert_eq!(
+            run_ok(directory.path(), &["rev-parse", revision]),
+            git(directory.path(), &["rev-parse", revision]),
+            "{revision}"
+        );
+    }
+    assert_eq!(
+        run_ok(directory.path(), &["symbolic-ref", "HEAD"]),
+        "refs/heads/main\n"
+    );
+}
+
+#[test]
+fn loose_refs_shadow_packed_ones() {
+    let directory = repository_with_packed_refs();
+    let first_commit = git(directory.path(), &["rev-parse", "HEAD~1"]);
+    fs::write(directory.join(".git/refs/heads/main"), &first_commit).unwrap();
+
+    assert_eq!(
+        run_ok(directory.path(), &["rev-parse", "main"]),
+        first_commit
+    );
+    assert_eq!(
+        run_ok(directory.path(), &["rev-parse", "HEAD"]),
+        first_commit
+    );
+}
+
+#[test]
+fn updating_a_packed_ref_checks_its_packed_value() {
+    let directory = repository_with_packed_refs();
+    let first_commit = git(directory.path(), &["rev-parse", "HEAD~1"]);
+    let first_commit = first_commit.trim_end();
+    let second_commit = git(directory.path(), &["rev-parse", "HEAD"]);
+    let second_commit = second_commit.trim_end();
+
+    let output = run(
+        directory.path(),
+        &["update-ref", "refs/heads/main", second_commit, first_commit],
+    );
+    assert!(!output.status.success());
+
+    run_ok(
+        directory.path(),
+        &["update-ref", "refs/heads/main", first_commit, second_commit],
+    );
+    assert_eq!(
+        git(directory.path(), &["rev-parse", "main"]),
+        format!("{first_commit}\n")
+    );
+}
+
+#[test]
+fn ambiguous_object_names_are_not_reported_as_unknown() {
+    let directory = git_repository();
+    let repository = Repository::open(directory.path()).unwrap();
+    let prefix = ambiguous_prefix(&repository);
+
+    let output = run(directory.path(), &["rev-parse", &prefix]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("ambiguous"), "{stderr}");
+    assert!(!stderr.contains("unknown revision"), "{stderr}");
+
+    let output = run_ok_with_stdin(
+        directory.path(),
+        &["cat-file", "--batch-check"],
+        format!("{prefix}\n").as_bytes(),
+    );
+    assert_eq!(output, format!("{prefix} ambiguous\n").into_bytes());
+}
+
+#[test]
+fn show_ref_lists_packed_and_loose_refs() {
+    let directory = repository_with_packed_refs();
+    git(directory.path(), &["branch", "topic", "HEAD~1"]);
+    let first_commit = git(directory.path(), &["rev-parse", "HEAD~1"]);
+    fs::write(directory.join(".git/refs/heads/main"), &first_commit).unwrap();
+
+    assert_eq!(
+        run_ok(directory.path(), &["show-ref"]),
+        git(directory.path(), &["show-ref"])
+    );
+    assert_eq!(
+        run_ok(directory.path(), &["show-ref", "--heads"]),
+        git(directory.path(), &["show-ref", "--heads"])
+    );
+    assert_eq!(
+        run_ok(directory.path(), &["show-ref", "--tags"]),
+        git(directory.path(), &["show-ref", "--tags"])
+    );
+}
+
+#[test]
+fn update_ref_creates_and_moves_refs() {
+    let directory = git_repository();
+    let tree_hash = git(directory.path(), &["write-tree"]);
+    let first = git(
+        directory.path(),
+        &["commit-tree", tree_hash.trim_end(), "-m", "First"],
+    );
+    let first = first.trim_end();
+    let second = git(
+        directory.path(),
+        &["commit-tree", tree_hash.trim_end(), "-m", "Second"],
+    );
+    let second = second.trim_end();
+
+    run_ok(
+        directory.path(),
+        &["update-ref", "refs/heads/topic/new", first],
+    );
+    assert_eq!(
+        fs::read_to_string(directory.join(".git/refs/heads/topic/new")).unwrap(),
+        format!("{first}\n")
+    );
+
+    run_ok(
+        directory.path(),
+        &["update-ref", "refs/heads/topic/new", second, first],
+    );
+    assert_eq!(
+        git(directory.path(), &["rev-parse", "refs/heads/topic/new"]),
+        format!("{second}\n")
+    );
+
+    // The ref is at `second` now
+    let output = run(
+        directory.path(),
+        &["update-ref", "refs/heads/topic/new", first, first],
+    );
+    assert!(!output.status.success());
+    assert_eq!(
+        git(directory.path(), &["rev-parse", "refs/heads/topic/new"]),
+        format!("{second}\n")
+    );
+
+    let output = run(
+        directory.path(),
+        &["update-ref", "refs/heads/missing", &"1".repeat(40)],
+    );
+    assert!(!output.status.success());
+    assert!(!directory.join(".git/refs/heads/missing").exists());
+}
+
+#[test]
+fn symbolic_ref_reads_and_repoints_head() {
+    let directory = git_repository();
+
+    assert_eq!(
+        run_ok(directory.path(), &["symbolic-ref", "HEAD"]),
+        "refs/heads/main\n"
+    );
+
+    run_ok(
+        directory.path(),
+        &["symbolic-ref", "HEAD", "refs/heads/other"],
+    );
+    assert_eq!(
+        fs::read_to_string(directory.join(".git/HEAD")).unwrap(),
+        "ref: refs/heads/other\n"
+    );
+    assert_eq!(
+        git(directory.path(), &["symbolic-ref", "HEAD"]),
+        "refs/heads/other\n"
+    );
+
+    // A ref holding a hash isn't symbolic
+    let tree_hash = git(directory.path(), &["write-tree"]);
+    let commit_hash = git(
+        directory.path(),
+        &["commit-tree", tree_hash.trim_end(), "-m", "First"],
+    );
+    git(
+        directory.path(),
+        &["update-ref", "refs/heads/other", commit_hash.trim_end()],
+    );
+    assert!(
+        !run(directory.path(), &["symbolic-ref", "refs/heads/other"])
+            .status
+            .success()
+    );
+}
@@ -0,0 +1,343 @@
+mod common;
+
+use common::*;
+use git_starter_rust::{
+    BlobObject, GitError, GitObject, ObjectFormat, Repository, TreeEntry, TreeObject,
+};
+
+#[test]
+fn init_bare_lays_out_the_repository_in_the_current_directory() {
+    let directory = TempDir::new();
+    run_ok(directory.path(), &["init", "--bare"]);
+
+    assert!(directory.join("HEAD").is_file());
+    assert!(directory.join("objects").is_dir());
+    assert!(directory.join("refs").is_dir());
+    assert!(!directory.join(".git").exists());
+    assert_eq!(
+        git(directory.path(), &["rev-parse", "--is-bare-repository"]),
+        "true\n"
+    );
+}
+
+#[test]
+fn init_lays_out_the_repository_in_dot_git() {
+    let directory = TempDir::new();
+    run_ok(directory.path(), &["init"]);
+
+    assert_eq!(
+        git(directory.path(), &["rev-parse", "--is-bare-repository"]),
+        "false\n"
+    );
+    assert_eq!(
+        git(directory.path(), &["symbolic-ref", "HEAD"]),
+        "refs/heads/main\n"
+    );
+}
+
+#[test]
+fn init_b_sets_the_initial_branch() {
+    let directory = TempDir::new();
+    run_ok(directory.path(), &["init", "-b", "trunk"]);
+
+    assert_eq!(
+        std::fs::read_to_string(directory.join(".git/HEAD")).unwrap(),
+        "ref: refs/heads/trunk\n"
+    );
+
+    for invalid in ["", "with space", "a..b"] {
+        let directory = TempDir::new();
+        assert!(!run(directory.path(), &["init", "-b", invalid])
+            .status
+            .success());
+        assert!(!directory.join(".git/HEAD").exists());
+    }
+}
+
+#[test]
+fn commands_find_the_repository_from_a_nested_directory() {
+    let directory = git_repository();
+    directory.write("a/b/c/file.txt", "nested\n");
+    let nested = directory.join("a/b/c");
+
+    let blob_hash = run_ok(&nested, &["hash-object", "-w", "file.txt"]);
+    assert_eq!(
+        git(directory.path(), &["cat-file", "-p", blob_hash.trim_end()]),
+        "nested\n"
+    );
+    assert_eq!(
+        run_ok(&nested, &["cat-file", "-t", blob_hash.trim_end()]),
+        "blob\n"
+    );
+}
+
+#[test]
+fn commands_follow_a_git_file_to_the_repository() {
+    let repository = git_repository();
+    let directory = TempDir::new();
+    directory.write(
+        ".git",
+        format!("gitdir: {}\n", repository.join(".git").display()),
+    );
+    directory.write("sub/file.txt", "elsewhere\n");
+
+    let blob_hash = run_ok(&directory.join("sub"), &["hash-object", "-w", "file.txt"]);
+    assert_eq!(
+        git(repository.path(), &["cat-file", "-p", blob_hash.trim_end()]),
+        "elsewhere\n"
+    );
+}
+
+#[test]
+fn git_dir_points_commands_at_another_repository() {
+    let repository = TempDir::new();
+    let git_dir = repository.join("elsewhere.git");
+    let directory = TempDir::new();
+    directory.write("file.txt", "file\n");
+
+    run_ok_with_git_dir(directory.path(), &["init"], &git_dir);
+    assert!(git_dir.join("HEAD").is_file());
+    assert!(!directory.join(".git").exists());
+
+    let blob_hash = run_ok_with_git_dir(
+        directory.path(),
+        &["hash-object", "-w", "file.txt"],
+        &git_dir,
+    );
+    let blob_hash = blob_hash.trim_end();
+    assert!(git_dir
+        .join(format!("objects/{}/{}", &blob_hash[..2], &blob_hash[2..]))
+        .is_file());
+    assert_eq!(
+        run_ok_with_git_dir(directory.path(), &["cat-file", "-p", blob_hash], &git_dir),
+        "file\n"
+    );
+
+    let tree_hash = run_ok_with_git_dir(
+        directory.path(),
+        &["write-tree", "--working-tree"],
+        &git_dir,
+    );
+    assert_eq!(
+        git(
+            git_dir.as_path(),
+            &["ls-tree", "--name-only", tree_hash.trim_end()]
+        ),
+        "file.txt\n"
+    );
+}
+
+#[test]
+fn reading_a_missing_object_is_object_not_found() {
+    let directory = git_repository();
+    let repository = Repository::open(directory.path()).unwrap();
+    let missing = "0".repeat(40);
+
+    assert!(matches!(
+        repository.read_object(&missing),
+        Err(GitError::ObjectNotFound(_))
+    ));
+    assert!(matches!(
+        repository.resolve_object_hash("abcdef"),
+        Err(GitError::ObjectNotFound(_))
+    ));
+}
+
+#[test]
+fn repositories_round_trip_blobs() {
+    let directory = TempDir::new();
+    let repository =
+        Repository::init(directory.join(".git"), false, "main", ObjectFormat::Sha1).unwrap();
+
+    let blob = GitObject::Blob(BlobObject {
+        data: b"round trip\n".to_vec(),
+    });
+    let object_hash = repository.write_object(&blob).unwrap();
+
+    // Opened again, from the root of the working tree this time
+    let repository = Repository::open(directory.path()).unwrap();
+    assert_eq!(repository.git_dir(), directory.join(".git"));
+    match repository.read_object(&object_hash).unwrap() {
+        GitObject::Blob(blob) => assert_eq!(blob.data, b"round trip\n"),
+        _ => panic!("{object_hash} wasn't read as a blob"),
+    }
+    assert_eq!(
+        git(directory.path(), &["cat-file", "-p", &object_hash]),
+        "round trip\n"
+    );
+
+    assert_eq!(repository.resolve_ref("HEAD").unwrap(), None);
+    git(
+        directory.path(),
+        &["update-ref", "refs/tags/blob", &object_hash],
+    );
+    assert_eq!(
+        repository.resolve_ref("blob").unwrap(),
+        Some(object_hash.clone())
+    );
+}
+
+#[test]
+fn objects_are_named_by_the_object_format_of_the_repository() {
+    for (object_format, hex_len) in [("sha1", 40), ("sha256", 64)] {
+        let directory = TempDir::new();
+        run_ok(
+            directory.path(),
+            &["init", &format!("--object-format={object_format}")],
+        );
+        directory.write("file.txt", "file\n");
+        directory.write("sub/nested.txt", "nested\n");
+
+        let blob_hash = run_ok(directory.path(), &["hash-object", "-w", "file.txt"]);
+        assert_eq!(blob_hash.trim_end().len(), hex_len);
+        assert_eq!(
+            blob_hash,
+            git(directory.path(), &["hash-object", "file.txt"])
+        );
+
+        // Trees hold raw hashes of the same length
+        let tree_hash = run_ok(directory.path(), &["write-tree", "--working-tree"]);
+        assert_eq!(tree_hash.trim_end().len(), hex_len);
+        git(directory.path(), &["add", "."]);
+        assert_eq!(tree_hash, git(directory.path(), &["write-tree"]));
+        assert_eq!(
+            run_ok(directory.path(), &["ls-tree", "-r", tree_hash.trim_end()]),
+            git(directory.path(), &["ls-tree", "-r", tree_hash.trim_end()])
+        );
+    }
+}
+
+#[test]
+fn the_object_format_is_read_from_the_config() {
+    let directory = TempDir::new();
+    run_ok(directory.path(), &["init", "--object-format=sha256"]);
+    assert_eq!(
+        git(directory.path(), &["config", "extensions.objectformat"]),
+        "sha256\n"
+    );
+
+    let repository = Repository::open(directory.path()).unwrap();
+    assert_eq!(repository.object_format(), ObjectFormat::Sha256);
+
+    let tree = GitObject::Tree(TreeObject {
+        entries: vec![TreeEntry {
+            mode: 0o100644,
+            name: b"file.txt".to_vec(),
+            sha: repository
+                .write_object(&GitObject::Blob(BlobObject {
+                    data: b"file\n".to_vec(),
+                }))
+                .unwrap(),
+        }],
+    });
+    let tree_hash = repository.write_object(&tree).unwrap();
+    assert_eq!(tree_hash.len(), 64);
+
+    let tree = repository.read_tree(&tree_hash).unwrap();
+    assert_eq!(tree.entries.len(), 1);
+    assert_eq!(tree.entries[0].name, b"file.txt");
+    assert_eq!(
+        format!("{}\n", tree.entries[0].sha).into_bytes(),
+        git_with_stdin(directory.path(), &["hash-object", "--stdin"], b"file\n")
+    );
+}
+
+#[test]
+fn linked_worktrees_read_the_shared_objects() {
+    let directory = git_repository();
+    directory.write("file.txt", "shared\n");
+    git(directory.path(), &["add", "file.txt"]);
+    git(directory.path(), &["commit", "-q", "-m", "Initial commit"]);
+    let worktree = TempDir::new();
+    let worktree_path = worktree.join("linked");
+    git(
+        directory.path(),
+        &["worktree", "add", "-q", worktree_path.to_str().unwrap()],
+    );
+    assert!(worktree_path.join(".git").is_file());
+
+    let blob_hash = git(directory.path(), &["rev-parse", "HEAD:file.txt"]);
+    assert_eq!(
+        run_ok(&worktree_path, &["cat-file", "-p", blob_hash.trim_end()]),
+        "shared\n"
+    );
+    assert_eq!(
+        run_ok(&worktree_path, &["rev-parse", "HEAD"]),
+        git(&worktree_path, &["rev-parse", "HEAD"])
+    );
+
+    // Objects written from the worktree land in the shared store
+    std::fs::write(worktree_path.join("new.txt"), "new\n").unwrap();
+    let new_hash = run_ok(&worktree_path, &["hash-object", "-w", "new.txt"]);
+    assert_eq!(
+        git(directory.path(), &["cat-file", "-p", new_hash.trim_end()]),
+        "new\n"
+    );
+}
+
+#[test]
+fn objects_are_borrowed_from_alternates() {
+    let shared = git_repository();
+    shared.write("file.txt", "only in the alternate\n");
+    let blob_hash = git(shared.path(), &["hash-object", "-w", "file.txt"]);
+    let blob_hash = blob_hash.trim_end();
+
+    let directory = git_repository();
+    directory.write(
+        ".git/objects/info/alternates",
+        format!("{}\n", shared.join(".git/objects").display()),
+    );
+
+    assert_eq!(
+        run_ok(directory.path(), &["cat-file", "-p", blob_hash]),
+        "only in the alternate\n"
+    );
+    assert_eq!(
+        run_ok(directory.path(), &["cat-file", "-p", &blob_hash[..8]]),
+        "only in the alternate\n"
+    );
+
+    std::fs::remove_file(directory.join(".git/objects/info/alternates")).unwrap();
+    assert!(!run(directory.path(), &["cat-file", "-p", blob_hash])
+        .status
+        .success());
+}
+
+#[test]
+fn c_and_git_dir_options_point_at_a_repository_elsewhere() {
+    let directory = git_repository();
+    directory.write("sub/file.txt", "elsewhere\n");
+    let blob_hash = git(directory.path(), &["hash-object", "-w", "sub/file.txt"]);
+    let blob_hash = blob_hash.trim_end();
+    let unrelated = TempDir::new();
+    let root = directory.path().to_str().unwrap();
+
+    assert_eq!(
+        run_ok(unrelated.path(), &["-C", root, "cat-file", "-p", blob_hash]),
+        "elsewhere\n"
+    );
+    // Each `-C` is relative to the previous one
+    assert_eq!(
+        run_ok(
+            unrelated.path(),
+            &["-C", root, "-C", "sub", "hash-object", "file.txt"]
+        ),
+        format!("{blob_hash}\n")
+    );
+
+    let git_dir = directory.join(".git");
+    let args = [
+        "--git-dir",
+        git_dir.to_str().unwrap(),
+        "cat-file",
+        "-t",
+        blob_hash,
+    ];
+    assert_eq!(run_ok(unrelated.path(), &args), "blob\n");
+
+    // The option wins over `GIT_DIR`
+    assert_eq!(
+        run_ok_with_git_dir(unrelated.path(), &args, &unrelated.join("missing")),
+        "blob\n"
+    );
+}
@@ -0,0 +1,80 @@
+mod common;
+
+use std::{
+    io::Cursor,
+    time::{Duration, UNIX_EPOCH},
+};
+
+use common::*;
+use git_starter_rust::{read_git_object, GitObject, ObjectFormat, ObjectType, TagObject};
+
+const EMPTY_TREE: &str = "4b825dc642cb6eb9a060e54bf8d69288fbee4904";
+
+fn tag_object() -> TagObject {
+    return TagObject {
+        object_hash: EMPTY_TREE.to_string(),
+        object_type: ObjectType::Tree,
+        tag_name: "v1".to_string(),
+        tagger_name: "C O Mitter".to_string(),
+        tagger_email: "committer@example.com".to_string(),
+        tagger_date_seconds: UNIX_EPOCH + Duration::from_secs(1700000100),
+        tagger_date_timezone: "+0100".to_string(),
+        tag_message: "Version 1".to_string(),
+    };
+}
+
+// `tag_object`, as git's `mktag` reads it
+const TAG_CONTENT: &str = "object 4b825dc642cb6eb9a060e54bf8d69288fbee4904\n\
+                           type tree\n\
+                           tag v1\n\
+                           tagger C O Mitter <committer@example.com> 1700000100 +0100\n\
+                           \n\
+                           Version 1\n";
+
+#[test]
+fn tags_round_trip_through_pack_and_read() {
+    let packed = tag_object().pack().unwrap();
+    let object_hash = ObjectFormat::Sha1.digest(&packed);
+
+    let mut reader = Cursor::new(packed);
+    match read_git_object(&mut reader, &object_hash, ObjectFormat::Sha1).unwrap() {
+        GitObject::Tag(tag) => {
+            let expected = tag_object();
+
+            assert_eq!(tag.object_hash, expected.object_hash);
+            assert_eq!(tag.object_type, expected.object_type);
+            assert_eq!(tag.tag_name, expected.tag_name);
+            assert_eq!(tag.tagger_name, expected.tagger_name);
+            assert_eq!(tag.tagger_email, expected.tagger_email);
+            assert_eq!(tag.tagger_date_seconds, expected.tagger_date_seconds);
+            assert_eq!(tag.tagger_date_timezone, expected.tagger_date_timezone);
+            assert_eq!(tag.tag_message, expected.tag_message);
+        }
+        _ => panic!("{object_hash} wasn't read as a tag"),
+    }
+}
+
+#[test]
+fn mktag_writes_the_tag_git_writes() {
+    let directory = git_repository();
+    // Stores the empty tree, for git to find what is tagged
+    git(directory.path(), &["write-tree"]);
+
+    let expected = git_with_stdin(directory.path(), &["mktag"], TAG_CONTENT.as_bytes());
+    assert_eq!(
+        run_ok_with_stdin(directory.path(), &["mktag"], TAG_CONTENT.as_bytes()),
+        expected
+    );
+    assert_eq!(
+        String::from_utf8(expected).unwrap(),
+        format!(
+            "{}\n",
+            ObjectFormat::Sha1.digest(&tag_object().pack().unwrap())
+        )
+    );
+
+    // The tagged object must be there
+    let dangling = TAG_CONTENT.replace(EMPTY_TREE, &"1".repeat(40));
+    let output = run_with_stdin(directory.path(), &["mktag"], dangling.as_bytes());
+    assert!(!output.status.success());
+}
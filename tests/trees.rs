@@ -0,0 +1,476 @@
+mod common;
+
+use common::*;
+use git_starter_rust::{BlobObject, GitObject, ObjectType, Repository, TreeEntry, TreeObject};
+
+// Files in a few directories, one of them ignored, all staged by git but the ignored one
+fn repository_with_files() -> TempDir {
+    let directory = git_repository();
+    directory.write("a.txt", "a\n");
+    directory.write("sub/b.txt", "b\n");
+    directory.write("sub/deeper/c.txt", "c\n");
+    directory.write("sub/ignored.log", "ignored\n");
+    directory.write(".gitignore", "*.log\n");
+    git(directory.path(), &["add", "."]);
+
+    return directory;
+}
+
+#[test]
+fn write_tree_of_the_working_tree_from_a_subdirectory_writes_the_whole_tree() {
+    let directory = repository_with_files();
+    let expected = git(directory.path(), &["write-tree"]);
+
+    assert_eq!(
+        run_ok(&directory.join("sub"), &["write-tree", "--working-tree"]),
+        expected
+    );
+    assert_eq!(
+        run_ok(directory.path(), &["write-tree", "--working-tree"]),
+        expected
+    );
+}
+
+#[test]
+fn write_tree_prefix_is_relative_to_the_root() {
+    let directory = repository_with_files();
+    let expected = git(directory.path(), &["write-tree", "--prefix=sub/deeper/"]);
+
+    assert_eq!(
+        run_ok(
+            &directory.join("sub"),
+            &["write-tree", "--working-tree", "--prefix=sub/deeper"]
+        ),
+        expected
+    );
+}
+
+#[test]
+fn write_tree_applies_nested_ignore_rules_and_attributes() {
+    let directory = git_repository();
+    git(directory.path(), &["config", "core.autocrlf", "true"]);
+    directory.write(".gitignore", "*.log\n");
+    directory.write(".gitattributes", "*.bin -text\n");
+    directory.write("a.txt", "a\r\n");
+    directory.write("a.bin", "a\r\n");
+    directory.write("a.log", "ignored\n");
+    directory.write("sub/.gitignore", "!kept.log\nlocal/\n");
+    directory.write("sub/.gitattributes", "*.txt -text\n");
+    directory.write("sub/b.txt", "b\r\n");
+    directory.write("sub/kept.log", "kept\n");
+    directory.write("sub/local/c.txt", "ignored\n");
+    directory.write("sub/deeper/d.txt", "d\r\n");
+    directory.write("sub/deeper/d.bin", "d\r\n");
+    git(directory.path(), &["add", "-A"]);
+
+    assert_eq!(
+        run_ok(directory.path(), &["write-tree", "--working-tree"]),
+        git(directory.path(), &["write-tree"])
+    );
+}
+
+// The hash git staged for `path`
+fn staged_hash(directory: &TempDir, path: &str) -> String {
+    return git(directory.path(), &["rev-parse", &format!(":{path}")])
+        .trim_end()
+        .to_string();
+}
+
+// A regular file, an executable, a symlink and a directory, all staged by git
+fn repository_with_every_mode() -> TempDir {
+    let directory = git_repository();
+    directory.write("file.txt", "file\n");
+    directory.write("script.sh", "#!/bin/sh\n");
+    directory.write("sub/nested.txt", "nested\n");
+    std::os::unix::fs::symlink("file.txt", directory.join("link")).unwrap();
+    git(directory.path(), &["add", "."]);
+    git(
+        directory.path(),
+        &["update-index", "--chmod=+x", "script.sh"],
+    );
+
+    return directory;
+}
+
+#[test]
+fn packed_trees_are_identical_to_the_ones_git_writes() {
+    let directory = repository_with_every_mode();
+    let expected = git(directory.path(), &["write-tree"]);
+    let expected = expected.trim_end();
+
+    let sub_tree = git(directory.path(), &["rev-parse", &format!("{expected}:sub")]);
+    let tree = TreeObject {
+        entries: vec![
+            TreeEntry {
+                mode: 0o40000,
+                name: b"sub".to_vec(),
+                sha: sub_tree.trim_end().to_string(),
+            },
+            TreeEntry {
+                mode: 0o100755,
+                name: b"script.sh".to_vec(),
+                sha: staged_hash(&directory, "script.sh"),
+            },
+            TreeEntry {
+                mode: 0o120000,
+                name: b"link".to_vec(),
+                sha: staged_hash(&directory, "link"),
+            },
+            TreeEntry {
+                mode: 0o100644,
+                name: b"file.txt".to_vec(),
+                sha: staged_hash(&directory, "file.txt"),
+            },
+        ],
+    };
+
+    let repository = Repository::open(directory.join(".git")).unwrap();
+    let object_hash = repository.write_object(&GitObject::Tree(tree)).unwrap();
+    assert_eq!(object_hash, expected);
+
+    let git_content = git_with_stdin(directory.path(), &["cat-file", "tree", expected], b"");
+    assert_eq!(
+        repository.read_verified_content(expected).unwrap(),
+        (ObjectType::Tree, git_content)
+    );
+}
+
+#[test]
+fn modes_of_trees_written_by_git_are_read_as_octal() {
+    let directory = repository_with_every_mode();
+    let tree_hash = git(directory.path(), &["write-tree"]);
+
+    let repository = Repository::open(directory.join(".git")).unwrap();
+    let tree = repository.read_tree(tree_hash.trim_end()).unwrap();
+    let modes = tree
+        .entries
+        .iter()
+        .map(|entry| (entry.name.as_slice(), entry.mode))
+        .collect::<Vec<(&[u8], u32)>>();
+
+    assert_eq!(
+        modes,
+        vec![
+            (&b"file.txt"[..], 0o100644),
+            (&b"link"[..], 0o120000),
+            (&b"script.sh"[..], 0o100755),
+            (&b"sub"[..], 0o40000),
+        ]
+    );
+}
+
+// The paths in the tree the working tree of `directory` is written as
+fn written_paths(directory: &TempDir) -> String {
+    let tree_hash = run_ok(directory.path(), &["write-tree", "--working-tree"]);
+
+    return git(
+        directory.path(),
+        &["ls-tree", "-r", "-t", "--name-only", tree_hash.trim_end()],
+    );
+}
+
+#[test]
+fn write_tree_leaves_ignored_paths_out() {
+    let directory = git_repository();
+    directory.write(".gitignore", "target/\n*.log\n!important.log\n");
+    directory.write("src/main.rs", "fn main() {}\n");
+    directory.write("src/debug.log", "debug\n");
+    directory.write("important.log", "kept\n");
+    directory.write("build.log", "build\n");
+    directory.write("target/debug/main", "binary\n");
+    directory.write("src/target/file.txt", "ignored too\n");
+
+    assert_eq!(
+        written_paths(&directory),
+        ".gitignore\nimportant.log\nsrc\nsrc/main.rs\n"
+    );
+}
+
+#[test]
+fn write_tree_skips_directories_without_files() {
+    let directory = git_repository();
+    directory.write(".gitignore", "*.log\n");
+    directory.write("file.txt", "file\n");
+    std::fs::create_dir_all(directory.join("empty/nested")).unwrap();
+    directory.write("only-ignored/debug.log", "debug\n");
+
+    assert_eq!(written_paths(&directory), ".gitignore\nfile.txt\n");
+
+    git(directory.path(), &["add", "."]);
+    assert_eq!(
+        run_ok(directory.path(), &["write-tree", "--working-tree"]),
+        git(directory.path(), &["write-tree"])
+    );
+}
+
+#[test]
+fn diff_tree_reports_changes_like_git() {
+    let directory = repository_with_files();
+    let old_tree = git(directory.path(), &["write-tree"]);
+    directory.write("sub/deeper/c.txt", "c, modified\n");
+    git(directory.path(), &["add", "."]);
+    let new_tree = git(directory.path(), &["write-tree"]);
+    let (old_tree, new_tree) = (old_tree.trim_end(), new_tree.trim_end());
+
+    let output = run_ok(directory.path(), &["diff-tree", "-r", old_tree, new_tree]);
+    assert_eq!(
+        output,
+        git(directory.path(), &["diff-tree", "-r", old_tree, new_tree])
+    );
+    assert!(output.ends_with(" M\tsub/deeper/c.txt\n"), "{output}");
+    assert_eq!(output.lines().count(), 1);
+
+    for args in [
+        &["diff-tree", old_tree, new_tree][..],
+        &["diff-tree", "-r", new_tree, old_tree],
+    ] {
+        assert_eq!(run_ok(directory.path(), args), git(directory.path(), args));
+    }
+
+    directory.write("added.txt", "added\n");
+    git(directory.path(), &["rm", "-q", "--cached", "a.txt"]);
+    git(directory.path(), &["add", "added.txt"]);
+    let other_tree = git(directory.path(), &["write-tree"]);
+    let args = ["diff-tree", "-r", old_tree, other_tree.trim_end()];
+    assert_eq!(
+        run_ok(directory.path(), &args),
+        git(directory.path(), &args)
+    );
+}
+
+#[test]
+fn mktree_writes_the_tree_git_writes() {
+    let directory = repository_with_files();
+    let blob_hash = staged_hash(&directory, "a.txt");
+    let tree_hash = git(directory.path(), &["write-tree", "--prefix=sub/"]);
+    // Out of order, mktree sorts the entries
+    let input = format!(
+        "040000 tree {}\tsub\n100644 blob {blob_hash}\ta.txt\n",
+        tree_hash.trim_end()
+    );
+
+    let output = run_ok_with_stdin(directory.path(), &["mktree"], input.as_bytes());
+    assert_eq!(
+        output,
+        git_with_stdin(directory.path(), &["mktree"], input.as_bytes())
+    );
+    let output = String::from_utf8(output).unwrap();
+    assert_eq!(
+        git(
+            directory.path(),
+            &["ls-tree", "--name-only", output.trim_end()]
+        ),
+        "a.txt\nsub\n"
+    );
+
+    let missing = format!("100644 blob {}\tmissing.txt\n", "1".repeat(40));
+    let output = run_with_stdin(directory.path(), &["mktree"], missing.as_bytes());
+    assert!(!output.status.success());
+    assert_eq!(
+        run_ok_with_stdin(
+            directory.path(),
+            &["mktree", "--missing"],
+            missing.as_bytes()
+        ),
+        git_with_stdin(
+            directory.path(),
+            &["mktree", "--missing"],
+            missing.as_bytes()
+        )
+    );
+
+    let invalid_mode = format!("100694 blob {blob_hash}\ta.txt\n");
+    let output = run_with_stdin(directory.path(), &["mktree"], invalid_mode.as_bytes());
+    assert!(!output.status.success());
+}
+
+#[test]
+fn write_tree_prefix_writes_the_subtree_of_the_full_tree() {
+    let directory = repository_with_files();
+    let full_tree = git(directory.path(), &["write-tree"]);
+    let subtree = git(
+        directory.path(),
+        &["rev-parse", &format!("{}:sub", full_tree.trim_end())],
+    );
+
+    for args in [
+        &["write-tree", "--prefix=sub/"][..],
+        &["write-tree", "--prefix=sub"],
+        &["write-tree", "--working-tree", "--prefix=sub/"],
+    ] {
+        assert_eq!(run_ok(directory.path(), args), subtree, "{args:?}");
+    }
+}
+
+#[test]
+fn identical_directories_share_one_tree_object() {
+    let directory = git_repository();
+    directory.write("a/file.txt", "same\n");
+    directory.write("b/file.txt", "same\n");
+
+    let tree_hash = run_ok(directory.path(), &["write-tree", "--working-tree"]);
+    let tree_hash = tree_hash.trim_end();
+    git(directory.path(), &["add", "."]);
+    assert_eq!(
+        git(directory.path(), &["write-tree"]),
+        format!("{tree_hash}\n")
+    );
+
+    let subtrees = git(
+        directory.path(),
+        &[
+            "rev-parse",
+            &format!("{tree_hash}:a"),
+            &format!("{tree_hash}:b"),
+        ],
+    );
+    let subtrees: Vec<&str> = subtrees.lines().collect();
+    assert_eq!(subtrees[0], subtrees[1]);
+
+    // The blob, the shared subtree and the root tree
+    let counts = git(directory.path(), &["count-objects", "-v"]);
+    assert!(counts.starts_with("count: 3\n"), "{counts}");
+}
+
+// `name` as a tree entry, a directory if `mode` is `0o40000`
+fn tree_entry(mode: u32, name: &str, sha: &str) -> TreeEntry {
+    return TreeEntry {
+        mode,
+        name: name.as_bytes().to_vec(),
+        sha: sha.to_string(),
+    };
+}
+
+// The tree git's `mktree` writes for `entries`
+fn git_mktree(directory: &TempDir, entries: &[TreeEntry]) -> String {
+    let input: String = entries
+        .iter()
+        .map(|entry| {
+            let line = entry.format_line(&entry.name);
+            format!("{}\n", String::from_utf8(line).unwrap())
+        })
+        .collect();
+    let tree_hash = git_with_stdin(directory.path(), &["mktree"], input.as_bytes());
+
+    return String::from_utf8(tree_hash).unwrap().trim_end().to_string();
+}
+
+#[test]
+fn directories_sort_as_if_their_name_ended_with_a_slash() {
+    let directory = git_repository();
+    let repository = Repository::open(directory.path()).unwrap();
+    let blob = repository
+        .write_object(&GitObject::Blob(BlobObject { data: Vec::new() }))
+        .unwrap();
+    let tree = git(directory.path(), &["write-tree"]);
+    let tree = tree.trim_end();
+
+    // `lib` < `lib.rs` < `lib/`, `foo-bar` < `foo/` but `foo` < `foo-bar`
+    for (entries, expected) in [
+        (
+            vec![
+                tree_entry(0o40000, "lib", tree),
+                tree_entry(0o100644, "lib.rs", &blob),
+            ],
+            ["lib.rs", "lib"],
+        ),
+        (
+            vec![
+                tree_entry(0o40000, "lib.rs", tree),
+                tree_entry(0o100644, "lib", &blob),
+            ],
+            ["lib", "lib.rs"],
+        ),
+        (
+            vec![
+                tree_entry(0o100644, "foo-bar", &blob),
+                tree_entry(0o40000, "foo", tree),
+            ],
+            ["foo-bar", "foo"],
+        ),
+        (
+            vec![
+                tree_entry(0o100644, "foo-bar", &blob),
+                tree_entry(0o100644, "foo", &blob),
+            ],
+            ["foo", "foo-bar"],
+        ),
+    ] {
+        let mut sorted = entries.clone();
+        sorted.sort();
+        let names: Vec<&[u8]> = sorted.iter().map(|entry| entry.name.as_slice()).collect();
+        assert_eq!(names, expected.map(str::as_bytes));
+
+        let tree_hash = repository
+            .write_object(&GitObject::Tree(TreeObject {
+                entries: entries.clone(),
+            }))
+            .unwrap();
+        assert_eq!(tree_hash, git_mktree(&directory, &entries), "{expected:?}");
+    }
+}
+
+#[test]
+fn tree_entries_sort_by_bytes() {
+    let directory = git_repository();
+    let repository = Repository::open(directory.path()).unwrap();
+    let blob = repository
+        .write_object(&GitObject::Blob(BlobObject { data: Vec::new() }))
+        .unwrap();
+    let entries: Vec<TreeEntry> = ["b", "a", "B", "A", "_", "é"]
+        .into_iter()
+        .map(|name| tree_entry(0o100644, name, &blob))
+        .collect();
+
+    let mut sorted = entries.clone();
+    sorted.sort();
+    let names: Vec<&[u8]> = sorted.iter().map(|entry| entry.name.as_slice()).collect();
+    assert_eq!(names, ["A", "B", "_", "a", "b", "é"].map(str::as_bytes));
+
+    let tree_hash = repository
+        .write_object(&GitObject::Tree(TreeObject {
+            entries: entries.clone(),
+        }))
+        .unwrap();
+    assert_eq!(tree_hash, git_mktree(&directory, &entries));
+}
+
+#[test]
+fn write_tree_of_many_files_is_deterministic() {
+    let directory = git_repository();
+    for i in 0..300 {
+        directory.write(&format!("dir{}/file{i}.txt", i % 7), format!("{i}\n"));
+    }
+
+    // Every blob written at once by the first run, none by the others
+    let tree_hash = run_ok(directory.path(), &["write-tree", "--working-tree"]);
+    for _ in 0..2 {
+        assert_eq!(
+            run_ok(directory.path(), &["write-tree", "--working-tree"]),
+            tree_hash
+        );
+    }
+
+    git(directory.path(), &["fsck", "--strict"]);
+    git(directory.path(), &["add", "."]);
+    assert_eq!(git(directory.path(), &["write-tree"]), tree_hash);
+}
+
+#[test]
+fn write_tree_only_includes_staged_files() {
+    let directory = git_repository();
+    directory.write("one.txt", "one\n");
+    directory.write("two.txt", "two\n");
+    directory.write("three.txt", "three\n");
+    git(directory.path(), &["add", "one.txt", "two.txt"]);
+
+    let tree_hash = run_ok(directory.path(), &["write-tree"]);
+    assert_eq!(tree_hash, git(directory.path(), &["write-tree"]));
+    assert_eq!(
+        git(
+            directory.path(),
+            &["ls-tree", "--name-only", tree_hash.trim_end()]
+        ),
+        "one.txt\ntwo.txt\n"
+    );
+}